@@ -38,8 +38,8 @@ async fn index(user: Option<User>) -> Template {
 }
 
 #[get("/logout")]
-fn logout(auth: Auth<'_>) -> Result<Template, Error> {
-    auth.logout()?;
+async fn logout(auth: Auth<'_>) -> Result<Template, Error> {
+    auth.logout().await?;
     Ok(Template::render("logout", json!({})))
 }
 #[get("/delete")]
@@ -49,8 +49,8 @@ async fn delete(auth: Auth<'_>) -> Result<Template, Error> {
 }
 
 #[get("/show_all_users")]
-async fn show_all_users(conn: &State<SqlitePool>, user: Option<User>) -> Result<Template, Error> {
-    let users: Vec<User> = query_as("select * from users;").fetch_all(&**conn).await?;
+async fn show_all_users(users: &State<Users>, user: Option<User>) -> Result<Template, Error> {
+    let users = users.list(&UserFilter::default(), &Page::default()).await?.users;
     println!("{:?}", users);
     Ok(Template::render(
         "users",
@@ -0,0 +1,181 @@
+//! Ready-made JSON endpoints to manage users, restricted to admins with the [`AdminUser`] guard.
+//! ```rust,no_run
+//! # use rocket_auth::{Users, Error};
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Error> {
+//! let users = Users::open_sqlite("mydb.db").await?;
+//!
+//! rocket::build()
+//!     .mount("/admin", rocket_auth::admin::routes())
+//!     .manage(users)
+//!     .launch()
+//!     .await;
+//! # Ok(()) }
+//! ```
+//! Errors are returned through the [`Error`] responder.
+
+use crate::audit::Client;
+use crate::prelude::*;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, Route, State};
+use serde_json::{json, Value};
+
+/// Returns the admin endpoints:
+/// * `GET /users`: Lists users as a [`UserList`]. It takes the fields of [`UserFilter`] and
+///   [`Page`] as query parameters, such as `/users?email=acme&active=true&order=created_at&limit=20`.
+/// * `GET /users/<id>`: Shows a user.
+/// * `POST /users/<id>/admin`: Toggles whether the user is an admin.
+/// * `POST /users/<id>/disable` and `POST /users/<id>/enable`: See [`Users::disable`].
+/// * `POST /users/<id>/logout`: Ends the session of the user.
+/// * `POST /users/<id>/password`: Sets a temporary password, sent as `{"password": "..."}`, and logs the user out.
+///   The user has to change it after logging in with it.
+/// * `POST /users/<id>/require-password-change`: See [`Users::require_password_change`].
+/// * `DELETE /users/<id>`: Deletes the user. See [`Users::delete`].
+///
+/// Every endpoint but `GET /users` and `DELETE /users/<id>` responds with the updated user.
+/// Admins can't disable, delete or demote their own account through these endpoints,
+/// so there is always an admin left to undo the change.
+pub fn routes() -> Vec<Route> {
+    rocket::routes![
+        list_users,
+        get_user,
+        toggle_admin,
+        disable_user,
+        enable_user,
+        logout_user,
+        reset_password,
+        require_password_change,
+        delete_user,
+    ]
+}
+
+/// The body of `POST /users/<id>/password`.
+#[derive(Deserialize, Clone, PartialEq, Eq)]
+struct PasswordReset {
+    password: String,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/users?<limit>&<offset>&<after>&<order>&<descending>&<filter..>")]
+async fn list_users(
+    filter: UserFilter,
+    limit: Option<u32>,
+    offset: Option<u64>,
+    after: Option<i32>,
+    order: Option<UserOrder>,
+    descending: Option<bool>,
+    _admin: AdminUser,
+    users: &State<Users>,
+) -> Result<Json<UserList>> {
+    let default = Page::default();
+    let page = Page {
+        limit: limit.unwrap_or(default.limit),
+        offset: offset.unwrap_or(default.offset),
+        after,
+        order: order.unwrap_or(default.order),
+        descending: descending.unwrap_or(default.descending),
+    };
+    Ok(Json(users.list(&filter, &page).await?))
+}
+
+#[get("/users/<id>")]
+async fn get_user(id: i32, _admin: AdminUser, users: &State<Users>) -> Result<Json<User>> {
+    Ok(Json(users.get_by_id(id).await?))
+}
+
+#[post("/users/<id>/admin")]
+async fn toggle_admin(
+    id: i32,
+    admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<User>> {
+    // admins can't demote themselves, so there is always an admin left to undo it.
+    reject_self(id, &admin)?;
+    let mut user = users.get_by_id(id).await?;
+    user.is_admin = !user.is_admin;
+    users.update_user(&user, Some(&client)).await?;
+    Ok(Json(user))
+}
+
+#[post("/users/<id>/disable")]
+async fn disable_user(
+    id: i32,
+    admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<User>> {
+    reject_self(id, &admin)?;
+    users.disable_user(id, Some(&client)).await?;
+    Ok(Json(users.get_by_id(id).await?))
+}
+
+#[post("/users/<id>/enable")]
+async fn enable_user(
+    id: i32,
+    _admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<User>> {
+    users.enable_user(id, Some(&client)).await?;
+    Ok(Json(users.get_by_id(id).await?))
+}
+
+#[post("/users/<id>/logout")]
+async fn logout_user(
+    id: i32,
+    _admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<User>> {
+    let user = users.get_by_id(id).await?;
+    users.logout_user(id, Some(&client)).await?;
+    Ok(Json(user))
+}
+
+#[post("/users/<id>/password", data = "<form>")]
+async fn reset_password(
+    id: i32,
+    form: Json<PasswordReset>,
+    _admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<User>> {
+    let mut user = users.get_by_id(id).await?;
+    users.set_password(&mut user, &form.password).await?;
+    user.require_password_change();
+    users.update_user(&user, Some(&client)).await?;
+    users.logout_user(id, Some(&client)).await?;
+    Ok(Json(user))
+}
+
+#[post("/users/<id>/require-password-change")]
+async fn require_password_change(
+    id: i32,
+    _admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<User>> {
+    users.require_user_password_change(id, Some(&client)).await?;
+    Ok(Json(users.get_by_id(id).await?))
+}
+
+#[delete("/users/<id>")]
+async fn delete_user(
+    id: i32,
+    admin: AdminUser,
+    client: Client,
+    users: &State<Users>,
+) -> Result<Json<Value>> {
+    reject_self(id, &admin)?;
+    users.get_by_id(id).await?;
+    users.delete_user(id, Some(&client)).await?;
+    Ok(Json(json!({ "status": "success" })))
+}
+
+#[throws(Error)]
+fn reject_self(id: i32, admin: &AdminUser) {
+    if id == admin.id() {
+        throw!(Error::SelfModificationError)
+    }
+}
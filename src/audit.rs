@@ -0,0 +1,256 @@
+//! An audit log of authentication events, such as logins, signups and password changes.
+//! Events are only recorded once a sink is set with [`Users::set_audit_sink`].
+//! The [`DatabaseSink`] stores them in the `auth_events` table, from which they can be
+//! queried with [`Users::audit_log`]:
+//! ```rust
+//! # use rocket_auth::{Users, Error, Page, audit::DatabaseSink};
+//! # async fn func(mut users: Users) -> Result<(), Error> {
+//! users.set_audit_sink(DatabaseSink);
+//! // ...
+//! for entry in users.audit_log(1, &Page::default()).await? {
+//!     println!("{:?} from {:?} at {}", entry.event, entry.ip, entry.created_at);
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::prelude::*;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::net::IpAddr;
+
+/// An authentication event.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEvent {
+    Login,
+    LoginFailed,
+    Logout,
+    Signup,
+    PasswordChanged,
+    EmailChanged,
+    AdminGranted,
+    AdminRevoked,
+    Deleted,
+    Disabled,
+    Enabled,
+    ForcedLogout,
+    PasswordChangeRequired,
+}
+
+impl AuthEvent {
+    /// The name the event is stored with.
+    pub fn as_str(&self) -> &'static str {
+        use AuthEvent::*;
+        match self {
+            Login => "login",
+            LoginFailed => "login_failed",
+            Logout => "logout",
+            Signup => "signup",
+            PasswordChanged => "password_changed",
+            EmailChanged => "email_changed",
+            AdminGranted => "admin_granted",
+            AdminRevoked => "admin_revoked",
+            Deleted => "deleted",
+            Disabled => "disabled",
+            Enabled => "enabled",
+            ForcedLogout => "forced_logout",
+            PasswordChangeRequired => "password_change_required",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<AuthEvent> {
+        use AuthEvent::*;
+        [
+            Login,
+            LoginFailed,
+            Logout,
+            Signup,
+            PasswordChanged,
+            EmailChanged,
+            AdminGranted,
+            AdminRevoked,
+            Deleted,
+            Disabled,
+            Enabled,
+            ForcedLogout,
+            PasswordChangeRequired,
+        ]
+        .iter()
+        .copied()
+        .find(|event| event.as_str() == name)
+    }
+}
+
+/// A recorded [`AuthEvent`], along with the client it originated from.
+/// Events emitted outside of a request, such as those caused by calling
+/// [`Users::delete`] directly, have no ip address or user agent.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AuditEntry {
+    pub id: i32,
+    /// The user the event refers to. Failed logins for unknown emails have no user.
+    pub user_id: Option<i32>,
+    pub event: AuthEvent,
+    /// The address the request came from. The `X-Real-IP` header is only honoured on requests
+    /// coming from one of the trusted proxies of [`ProxyAuth`](crate::ProxyAuth).
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+}
+
+/// Receives every [`AuditEntry`] emitted by [`Auth`] and [`Users`].
+/// If recording fails, the operation that emitted the event fails too.
+/// ```rust
+/// # use rocket_auth::{Users, Error, audit::{AuditEntry, AuditSink}};
+/// struct Stdout;
+///
+/// #[rocket::async_trait]
+/// impl AuditSink for Stdout {
+///     async fn record(&self, _users: &Users, entry: &AuditEntry) -> Result<(), Error> {
+///         println!("{:?} {:?}", entry.user_id, entry.event);
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn record(&self, users: &Users, entry: &AuditEntry) -> Result<()>;
+}
+
+/// Stores events in the `auth_events` table of the database used by [`Users`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DatabaseSink;
+
+#[async_trait]
+impl AuditSink for DatabaseSink {
+    async fn record(&self, users: &Users, entry: &AuditEntry) -> Result<()> {
+        users.conn.create_auth_event(entry).await
+    }
+}
+
+/// The ip address and user agent of the client making a request.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Client {
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl Client {
+    pub(crate) fn from_request(request: &Request<'_>) -> Client {
+        Client {
+            ip: client_ip(request).map(|ip| ip.to_string()),
+            user_agent: request.headers().get_one("User-Agent").map(String::from),
+        }
+    }
+}
+
+/// Any client can set the `X-Real-IP` header, so it is only trusted when the request
+/// comes from a proxy trusted by [`ProxyAuth`](crate::ProxyAuth).
+fn client_ip(request: &Request<'_>) -> Option<IpAddr> {
+    let remote = request.remote()?.ip();
+    let proxy = request
+        .rocket()
+        .state::<Users>()
+        .and_then(|users| users.proxy.as_ref());
+    match proxy {
+        Some(proxy) if proxy.is_trusted(remote) => request.real_ip().or(Some(remote)),
+        _ => Some(remote),
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Client {
+    type Error = std::convert::Infallible;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Client, Self::Error> {
+        Outcome::Success(Client::from_request(request))
+    }
+}
+
+impl Users {
+    /// Records the authentication events emitted by [`Auth`] and [`Users`].
+    /// No events are recorded until a sink is set.
+    /// ```rust
+    /// # use rocket_auth::{Users, audit::DatabaseSink};
+    /// # fn func(mut users: Users) {
+    /// users.set_audit_sink(DatabaseSink);
+    /// # }
+    /// ```
+    pub fn set_audit_sink(&mut self, sink: impl AuditSink + 'static) {
+        self.audit = Some(Box::new(sink));
+    }
+
+    /// Lists the events recorded by the [`DatabaseSink`] for a user, newest first.
+    /// Only the `limit`, `offset` and `after` fields of `page` are used,
+    /// where `after` is the id of the last entry of the previous page.
+    #[throws(Error)]
+    pub async fn audit_log(&self, user_id: i32, page: &Page) -> Vec<AuditEntry> {
+        let before = page.after.unwrap_or(i32::MAX);
+        let offset = if page.after.is_some() { 0 } else { page.offset as i64 };
+        self.conn
+            .get_auth_events(user_id, before, page.limit.into(), offset)
+            .await?
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn audit(&self, event: AuthEvent, user_id: Option<i32>, client: Option<&Client>) {
+        if let Some(sink) = &self.audit {
+            let client = client.cloned().unwrap_or_default();
+            let entry = AuditEntry {
+                id: 0,
+                user_id,
+                event,
+                ip: client.ip,
+                user_agent: client.user_agent,
+                created_at: now(),
+            };
+            sink.record(self, &entry).await?;
+        }
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> sqlx::Type<DB> for AuthEvent
+where
+    String: sqlx::Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as sqlx::Type<DB>>::type_info()
+    }
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as sqlx::Type<DB>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<'r, DB: sqlx::Database> sqlx::Decode<'r, DB> for AuthEvent
+where
+    String: sqlx::Decode<'r, DB>,
+{
+    fn decode(
+        value: <DB as sqlx::database::HasValueRef<'r>>::ValueRef,
+    ) -> Result<AuthEvent, sqlx::error::BoxDynError> {
+        let name = <String as sqlx::Decode<DB>>::decode(value)?;
+        Ok(AuthEvent::from_name(&name).ok_or("unknown auth event")?)
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl rusqlite::types::FromSql for AuthEvent {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let name = value.as_str()?;
+        AuthEvent::from_name(name).ok_or(rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+#[cfg(feature = "tokio-postgres")]
+impl<'a> tokio_postgres::types::FromSql<'a> for AuthEvent {
+    fn from_sql(
+        ty: &tokio_postgres::types::Type,
+        raw: &'a [u8],
+    ) -> Result<AuthEvent, Box<dyn std::error::Error + Sync + Send>> {
+        let name = <&str as tokio_postgres::types::FromSql>::from_sql(ty, raw)?;
+        Ok(AuthEvent::from_name(name).ok_or("unknown auth event")?)
+    }
+    fn accepts(ty: &tokio_postgres::types::Type) -> bool {
+        <&str as tokio_postgres::types::FromSql>::accepts(ty)
+    }
+}
@@ -0,0 +1,145 @@
+use crate::audit::Client;
+use crate::prelude::*;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{Response, State};
+
+/// The [`BasicAuthUser`] guard authenticates clients with the `Authorization: Basic` header,
+/// which is convenient for scripts and other machine clients. The credentials are verified
+/// the same way as in [`Auth::login`](crate::Auth::login), so each request is recorded in the audit log
+/// and runs the `before_login` hooks, but no session is created and no cookie is set.
+///
+/// **The [`BasicAuthChallenge`] fairing is required.** Request guards can't set response headers in Rocket,
+/// so without it, rejected requests are answered with a bare `401 Unauthorized` that lacks the `WWW-Authenticate`
+/// challenge required by [RFC 7617](https://datatracker.ietf.org/doc/html/rfc7617), and clients that wait for
+/// the challenge before sending credentials never send them.
+/// ```rust,no_run
+/// # use rocket::{get, routes};
+/// # use rocket_auth::{BasicAuthChallenge, BasicAuthUser, Users, Error};
+/// #[get("/api/report")]
+/// fn report(user: BasicAuthUser) -> String {
+///     format!("Report for {}", user.email())
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Error> {
+/// # let users = Users::open_sqlite("mydb.db").await?;
+/// rocket::build()
+///     .mount("/", routes![report])
+///     .attach(BasicAuthChallenge::new("api"))
+///     .manage(users)
+///     .launch()
+///     .await;
+/// # Ok(()) }
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
+pub struct BasicAuthUser(User);
+
+impl Debug for BasicAuthUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BasicAuth{:?}", self.0)
+    }
+}
+
+impl Deref for BasicAuthUser {
+    type Target = User;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<BasicAuthUser> for User {
+    fn from(user: BasicAuthUser) -> User {
+        user.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BasicAuthUser {
+    type Error = Error;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<BasicAuthUser, Error> {
+        let users: &State<Users> = match request.guard().await {
+            Outcome::Success(users) => users,
+            _ => {
+                return Outcome::Failure((Status::InternalServerError, Error::UnmanagedStateError))
+            }
+        };
+        let result = match basic_credentials(request) {
+            Some((email, password)) => {
+                let form = Login { email, password };
+                let client = Client::from_request(request);
+                users.authenticate(&form, &client).await
+            }
+            None => Err(Error::UnauthenticatedError),
+        };
+        match result {
+            Ok(user) if users.needs_password_change(&user) => {
+                Outcome::Failure((Status::Forbidden, Error::PasswordChangeRequired))
+            }
+            Ok(user) => Outcome::Success(BasicAuthUser(user)),
+            Err(error) => {
+                request.local_cache(|| Challenge(true));
+                Outcome::Failure((Status::Unauthorized, error))
+            }
+        }
+    }
+}
+
+/// Marks requests rejected by the [`BasicAuthUser`] guard.
+struct Challenge(bool);
+
+/// A fairing that adds a `WWW-Authenticate` header to the responses of requests rejected by [`BasicAuthUser`].
+/// It must be attached by every application that uses the guard, see [`BasicAuthUser`].
+/// ```rust,no_run
+/// # use rocket_auth::{BasicAuthChallenge, Users, Error};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Error> {
+/// # let users = Users::open_sqlite("mydb.db").await?;
+/// rocket::build()
+///     .attach(BasicAuthChallenge::new("api"))
+///     .manage(users)
+///     .launch()
+///     .await;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicAuthChallenge {
+    realm: String,
+}
+
+impl BasicAuthChallenge {
+    pub fn new(realm: &str) -> BasicAuthChallenge {
+        BasicAuthChallenge {
+            realm: realm.replace('"', ""),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for BasicAuthChallenge {
+    fn info(&self) -> Info {
+        Info {
+            name: "Basic authentication challenge",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if response.status() == Status::Unauthorized && request.local_cache(|| Challenge(false)).0 {
+            let challenge = format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm);
+            response.set_header(Header::new("WWW-Authenticate", challenge));
+        }
+    }
+}
+
+/// Parses the username and password of the `Authorization: Basic` header.
+pub(crate) fn basic_credentials(request: &Request<'_>) -> Option<(String, String)> {
+    let encoded = request
+        .headers()
+        .get_one("Authorization")?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(base64::decode(encoded.trim()).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.into(), password.into()))
+}
@@ -0,0 +1,172 @@
+use super::CredentialVerifier;
+use crate::prelude::*;
+use crate::user::rand_token;
+use ldap3::{dn_escape, ldap_escape, Ldap, LdapConnAsync, Scope, SearchEntry};
+
+/// The way [`LdapVerifier`] finds the distinguished name to bind with.
+/// In both cases `{email}` is replaced by the email of the login form,
+/// and `{username}` by the part of the email before the `@`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LdapBind {
+    /// Binds directly with a DN built from a template,
+    /// such as `"uid={username},ou=people,dc=example,dc=com"`.
+    Template(String),
+    /// Binds with a service account, searches for the user with `filter`
+    /// (such as `"(mail={email})"`) under `base_dn`, and then binds as that user.
+    Search {
+        bind_dn: String,
+        bind_password: String,
+        base_dn: String,
+        filter: String,
+    },
+}
+
+/// Authenticates users with an LDAP simple bind, such as against an Active Directory server.
+/// Users that authenticate successfully are provisioned in the local database, so they
+/// can be managed with [`Users`] like any other user. Their local password is random and unusable.
+/// ```rust,no_run
+/// # use rocket_auth::{Users, Error, credentials::{LdapBind, LdapVerifier}};
+/// # async fn func(mut users: Users) -> Result<(), Error> {
+/// let template = LdapBind::Template("uid={username},ou=people,dc=example,dc=com".into());
+/// let mut ldap = LdapVerifier::new("ldap://localhost:389", template);
+/// ldap.admin_group = Some("cn=admins,ou=groups,dc=example,dc=com".into());
+/// users.set_credential_verifier(ldap);
+/// # Ok(()) }
+/// ```
+/// Any server speaking LDAPv3 works, so tests can point `url` to a local stand-in such as OpenLDAP or GLAuth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdapVerifier {
+    /// The url of the server, such as `ldap://localhost:389`.
+    pub url: String,
+    pub bind: LdapBind,
+    /// The attribute of the user entry listing their groups. It defaults to `memberOf`.
+    pub group_attribute: String,
+    /// Members of this group are made admins, and non members lose their admin rights.
+    /// If it is `None` the `is_admin` flag of local users is left untouched.
+    pub admin_group: Option<String>,
+    /// Pairs of groups and role names. Members of a group are assigned its role,
+    /// and non members lose it. Roles must be created beforehand with [`Users::create_role`].
+    pub role_groups: Vec<(String, String)>,
+    /// Whether to create a local user the first time someone logs in. It defaults to `true`.
+    /// Otherwise, only users that already exist locally are allowed to log in.
+    pub provision: bool,
+}
+
+impl LdapVerifier {
+    pub fn new(url: &str, bind: LdapBind) -> LdapVerifier {
+        LdapVerifier {
+            url: url.into(),
+            bind,
+            group_attribute: "memberOf".into(),
+            admin_group: None,
+            role_groups: vec![],
+            provision: true,
+        }
+    }
+
+    #[throws(Error)]
+    async fn user_dn(&self, ldap: &mut Ldap, email: &str) -> String {
+        let username = email.split('@').next().unwrap_or_default();
+        match &self.bind {
+            LdapBind::Template(template) => template
+                .replace("{username}", &dn_escape(username))
+                .replace("{email}", &dn_escape(email)),
+            LdapBind::Search {
+                bind_dn,
+                bind_password,
+                base_dn,
+                filter,
+            } => {
+                ldap.simple_bind(bind_dn, bind_password).await?.success()?;
+                let filter = filter
+                    .replace("{username}", &ldap_escape(username))
+                    .replace("{email}", &ldap_escape(email));
+                let (mut entries, _) = ldap
+                    .search(base_dn, Scope::Subtree, &filter, vec!["1.1"])
+                    .await?
+                    .success()?;
+                if entries.len() != 1 {
+                    throw!(Error::UnauthorizedError)
+                }
+                SearchEntry::construct(entries.remove(0)).dn
+            }
+        }
+    }
+
+    /// Assigns and removes the roles mapped in `role_groups`. It returns whether any role changed.
+    #[throws(Error)]
+    async fn sync_roles(&self, users: &Users, user: &User, groups: &[String]) -> bool {
+        let mut changed = false;
+        for (group, role) in &self.role_groups {
+            let is_member = groups.iter().any(|name| name.eq_ignore_ascii_case(group));
+            if is_member && !user.has_role(role) {
+                users.assign_role(user.id, role).await?;
+                changed = true;
+            } else if !is_member && user.has_role(role) {
+                users.unassign_role(user.id, role).await?;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    #[throws(Error)]
+    async fn groups(&self, ldap: &mut Ldap, dn: &str) -> Vec<String> {
+        let attributes = vec![self.group_attribute.as_str()];
+        let (mut entries, _) = ldap
+            .search(dn, Scope::Base, "(objectClass=*)", attributes)
+            .await?
+            .success()?;
+        match entries.pop() {
+            Some(entry) => SearchEntry::construct(entry)
+                .attrs
+                .remove(&self.group_attribute)
+                .unwrap_or_default(),
+            None => vec![],
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialVerifier for LdapVerifier {
+    async fn verify(&self, users: &Users, email: &str, password: &str) -> Result<User> {
+        // an empty password would result in an unauthenticated bind, which always succeeds.
+        if password.is_empty() {
+            return Err(Error::UnauthorizedError);
+        }
+        let (conn, mut ldap) = LdapConnAsync::new(&self.url).await?;
+        ldap3::drive!(conn);
+
+        let dn = self.user_dn(&mut ldap, email).await?;
+        if ldap.simple_bind(&dn, password).await?.rc != 0 {
+            return Err(Error::UnauthorizedError);
+        }
+        let groups = self.groups(&mut ldap, &dn).await?;
+        ldap.unbind().await?;
+
+        let is_admin = self.admin_group.as_ref().map(|admin_group| {
+            groups
+                .iter()
+                .any(|group| group.eq_ignore_ascii_case(admin_group))
+        });
+        let user = match users.get_by_email(email).await {
+            Ok(mut user) => {
+                if let Some(is_admin) = is_admin.filter(|is_admin| *is_admin != user.is_admin) {
+                    user.is_admin = is_admin;
+                    users.modify(&user).await?;
+                }
+                user
+            }
+            Err(_) if self.provision => {
+                let is_admin = is_admin.unwrap_or(false);
+                users.create_user(email, &rand_token(40), is_admin).await?;
+                users.get_by_email(email).await?
+            }
+            Err(_) => return Err(Error::EmailDoesNotExist(email.into())),
+        };
+        if self.sync_roles(users, &user, &groups).await? {
+            return users.get_by_id(user.id).await;
+        }
+        Ok(user)
+    }
+}
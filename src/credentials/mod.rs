@@ -0,0 +1,81 @@
+//! Credential verifiers decide how the password submitted in a [`Login`] form is checked.
+//! By default, [`Users`] compares it against the hash stored in the `users` table,
+//! but it can be replaced with [`Users::set_credential_verifier`], for example,
+//! to authenticate against an LDAP directory.
+
+#[cfg(feature = "ldap")]
+mod ldap;
+
+#[cfg(feature = "ldap")]
+pub use ldap::{LdapBind, LdapVerifier};
+
+use crate::prelude::*;
+
+/// Verifies the credentials of a user trying to log in.
+/// On success it returns the local [`User`] the session will be created for.
+/// Implementations may create or update that user if it only exists in an external system.
+/// ```rust
+/// # use rocket_auth::{Users, User, Error, credentials::CredentialVerifier};
+/// struct AllowList(Vec<String>);
+///
+/// #[rocket::async_trait]
+/// impl CredentialVerifier for AllowList {
+///     async fn verify(&self, users: &Users, email: &str, password: &str) -> Result<User, Error> {
+///         if !self.0.iter().any(|allowed| allowed == email) {
+///             return Err(Error::UnauthorizedError);
+///         }
+///         let user = users.get_by_email(email).await?;
+///         if users.verify_password(&user, password).await? {
+///             Ok(user)
+///         } else {
+///             Err(Error::UnauthorizedError)
+///         }
+///     }
+///
+///     fn checks_local_passwords(&self) -> bool {
+///         true
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait CredentialVerifier: Send + Sync {
+    async fn verify(&self, users: &Users, email: &str, password: &str) -> Result<User>;
+
+    /// Whether the verifier checks the passwords stored by [`Users`], which then expire after the
+    /// [`max_age`](crate::PasswordPolicy::max_age) of the password policy. It is `false` by default,
+    /// since passwords kept in an external system, such as an LDAP directory, can't be changed with
+    /// [`Auth::change_password`](crate::Auth::change_password).
+    fn checks_local_passwords(&self) -> bool {
+        false
+    }
+}
+
+/// The default verifier. It compares the password against the hash stored in the database.
+/// Hashes that don't match the [`PasswordHasher`](crate::hashing::PasswordHasher) or the pepper of [`Users`]
+/// are replaced with a new hash of the password once it has been verified.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PasswordVerifier;
+
+#[async_trait]
+impl CredentialVerifier for PasswordVerifier {
+    async fn verify(&self, users: &Users, email: &str, password: &str) -> Result<User> {
+        let mut user = users
+            .conn
+            .get_user_by_email(email)
+            .await
+            .map_err(|_| Error::EmailDoesNotExist(email.into()))?;
+        if users.verify_password(&user, password).await? {
+            if users.needs_rehash(&user.password) {
+                user.password = users.hash_password(password).await?;
+                users.conn.update_user(&user).await?;
+            }
+            Ok(user)
+        } else {
+            Err(Error::UnauthorizedError)
+        }
+    }
+
+    fn checks_local_passwords(&self) -> bool {
+        true
+    }
+}
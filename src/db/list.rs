@@ -0,0 +1,145 @@
+//! Builds the queries of [`Users::list`] for every backend, since filters and
+//! sorting can't be expressed with a fixed set of statements.
+use crate::prelude::*;
+
+/// A value bound to a placeholder of a built query.
+pub(crate) enum Param {
+    Text(String),
+    Int(i32),
+    BigInt(i64),
+    Bool(bool),
+}
+
+pub(crate) struct Query {
+    pub(crate) sql: String,
+    pub(crate) params: Vec<Param>,
+}
+
+impl Query {
+    fn new(sql: &str) -> Query {
+        Query {
+            sql: sql.into(),
+            params: vec![],
+        }
+    }
+
+    /// Appends `sql`, replacing each `{}` with a placeholder for the next param.
+    fn push(&mut self, sql: &str, params: Vec<Param>, placeholder: fn(usize) -> String) {
+        let mut parts = sql.split("{}");
+        self.sql.push_str(parts.next().unwrap_or_default());
+        for (part, param) in parts.zip(params) {
+            self.params.push(param);
+            self.sql.push_str(&placeholder(self.params.len()));
+            self.sql.push_str(part);
+        }
+    }
+
+    fn filter(&mut self, filter: &UserFilter, placeholder: fn(usize) -> String) {
+        if let Some(email) = &filter.email {
+            let pattern = format!("%{}%", escape_like(&email.to_lowercase()));
+            let sql = " AND users.email LIKE {} ESCAPE '!'";
+            self.push(sql, vec![Param::Text(pattern)], placeholder);
+        }
+        if let Some(is_admin) = filter.is_admin {
+            let sql = " AND users.is_admin = {}";
+            self.push(sql, vec![Param::Bool(is_admin)], placeholder);
+        }
+        if let Some(created_after) = filter.created_after {
+            let sql = " AND users.created_at >= {}";
+            self.push(sql, vec![Param::BigInt(created_after)], placeholder);
+        }
+        if let Some(created_before) = filter.created_before {
+            let sql = " AND users.created_at < {}";
+            self.push(sql, vec![Param::BigInt(created_before)], placeholder);
+        }
+        match filter.active {
+            Some(true) => self.sql.push_str(" AND users.disabled_at IS NULL AND users.deleted_at IS NULL"),
+            Some(false) => self.sql.push_str(" AND (users.disabled_at IS NOT NULL OR users.deleted_at IS NOT NULL)"),
+            None => (),
+        }
+    }
+}
+
+/// Builds the query listing the users in `page`.
+/// `select` must end in a `WHERE` clause matching every user.
+pub(crate) fn list_users(
+    select: &str,
+    filter: &UserFilter,
+    page: &Page,
+    placeholder: fn(usize) -> String,
+) -> Query {
+    let mut query = Query::new(select);
+    query.filter(filter, placeholder);
+    let column = match page.order {
+        UserOrder::Id => None,
+        UserOrder::Email => Some("email"),
+        UserOrder::CreatedAt => Some("created_at"),
+    };
+    let (comparison, direction) = if page.descending {
+        ("<", "DESC")
+    } else {
+        (">", "ASC")
+    };
+    if let Some(after) = page.after {
+        let sql = match column {
+            None => format!(" AND users.id {} {{}}", comparison),
+            Some(column) => format!(
+                " AND (users.{0}, users.id) {1} ((SELECT last_user.{0} FROM users last_user WHERE last_user.id = {{}}), {{}})",
+                column, comparison
+            ),
+        };
+        let params = match column {
+            None => vec![Param::Int(after)],
+            Some(_) => vec![Param::Int(after), Param::Int(after)],
+        };
+        query.push(&sql, params, placeholder);
+    }
+    match column {
+        None => query.sql.push_str(&format!(" ORDER BY users.id {}", direction)),
+        Some(column) => query.sql.push_str(&format!(
+            " ORDER BY users.{0} {1}, users.id {1}",
+            column, direction
+        )),
+    }
+    let params = vec![Param::BigInt(page.limit.into())];
+    query.push(" LIMIT {}", params, placeholder);
+    if page.after.is_none() {
+        let params = vec![Param::BigInt(page.offset as i64)];
+        query.push(" OFFSET {}", params, placeholder);
+    }
+    query
+}
+
+/// Builds the query counting the users matching `filter`.
+pub(crate) fn count_users(filter: &UserFilter, placeholder: fn(usize) -> String) -> Query {
+    let mut query = Query::new("SELECT COUNT(*) FROM users WHERE 1 = 1");
+    query.filter(filter, placeholder);
+    query
+}
+
+/// `!` is used as the escape character, since backslashes are
+/// themselves escape characters in MySql string literals.
+fn escape_like(text: &str) -> String {
+    text.replace('!', "!!")
+        .replace('%', "!%")
+        .replace('_', "!_")
+}
+
+/// Binds the params of a built [`Query`] to a sqlx query.
+#[cfg(feature = "sqlx")]
+macro_rules! bind_params {
+    ($query:expr, $params:expr) => {{
+        let mut query = $query;
+        for param in $params {
+            query = match param {
+                Param::Text(text) => query.bind(text),
+                Param::Int(int) => query.bind(*int),
+                Param::BigInt(int) => query.bind(*int),
+                Param::Bool(boolean) => query.bind(*boolean),
+            };
+        }
+        query
+    }};
+}
+#[cfg(feature = "sqlx")]
+pub(crate) use bind_params;
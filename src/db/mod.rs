@@ -9,17 +9,73 @@ mod sqlite;
 #[cfg(feature = "tokio-postgres")]
 mod tokio_postgres;
 
+mod list;
+
+use crate::audit::AuditEntry;
+use crate::oauth::{OAuthClient, OAuthToken};
+use crate::invites::SignupInvite;
+use crate::organizations::{Invitation, Membership, Organization};
 use crate::prelude::*;
 
 #[rocket::async_trait]
 pub trait DBConnection: Send + Sync {
     async fn init(&self) -> Result<()>;
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<(), Error>;
+    async fn create_user(&self, email: &str, hash: &str, is_admin: bool, username: Option<&str>, profile: Option<&str>) -> Result<(), Error>;
     async fn update_user(&self, user: &User) -> Result<()>;
     async fn delete_user_by_id(&self, user_id: i32) -> Result<()>;
     async fn delete_user_by_email(&self, email: &str) -> Result<()>;
     async fn get_user_by_id(&self, user_id: i32) -> Result<User>;
     async fn get_user_by_email(&self, email: &str) -> Result<User>;
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()>;
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient>;
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()>;
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()>;
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>>;
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()>;
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()>;
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken>;
+    /// Returns `false` if there was no token to delete.
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool>;
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()>;
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()>;
+    async fn create_role(&self, role: &str) -> Result<()>;
+    async fn delete_role(&self, role: &str) -> Result<()>;
+    async fn get_roles(&self) -> Result<Vec<String>>;
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()>;
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()>;
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>>;
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()>;
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()>;
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()>;
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization>;
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization>;
+    async fn delete_organization(&self, organization_id: i32) -> Result<()>;
+    async fn set_membership(&self, membership: &Membership) -> Result<()>;
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>>;
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>>;
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>>;
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()>;
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()>;
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>>;
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>>;
+    /// Returns `false` if there was no invitation to delete.
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool>;
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()>;
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()>;
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>>;
+    /// Returns `false` if there was no invite to delete.
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool>;
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()>;
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()>;
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64>;
+    async fn get_user_by_username(&self, username: &str) -> Result<User>;
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>>;
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64>;
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()>;
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>>;
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()>;
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>>;
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()>;
 }
 
 #[rocket::async_trait]
@@ -27,8 +83,8 @@ impl<T: DBConnection> DBConnection for std::sync::Arc<T> {
     async fn init(&self) -> Result<()> {
         T::init(self).await
     }
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<(), Error> {
-        T::create_user(self, email, hash, is_admin).await
+    async fn create_user(&self, email: &str, hash: &str, is_admin: bool, username: Option<&str>, profile: Option<&str>) -> Result<(), Error> {
+        T::create_user(self, email, hash, is_admin, username, profile).await
     }
     async fn update_user(&self, user: &User) -> Result<()> {
         T::update_user(self, user).await
@@ -45,6 +101,147 @@ impl<T: DBConnection> DBConnection for std::sync::Arc<T> {
     async fn get_user_by_email(&self, email: &str) -> Result<User> {
         T::get_user_by_email(self, email).await
     }
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        T::create_oauth_client(self, client).await
+    }
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        T::get_oauth_client(self, client_id).await
+    }
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        T::delete_oauth_client(self, client_id).await
+    }
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        T::set_oauth_consent(self, user_id, client_id, scope).await
+    }
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        T::get_oauth_consent(self, user_id, client_id).await
+    }
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        T::delete_oauth_consent(self, user_id, client_id).await
+    }
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        T::create_oauth_token(self, token).await
+    }
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        T::get_oauth_token(self, token_hash).await
+    }
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        T::delete_oauth_token(self, token_hash).await
+    }
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        T::delete_oauth_grant(self, grant_hash).await
+    }
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        T::delete_user_oauth_tokens(self, user_id).await
+    }
+    async fn create_role(&self, role: &str) -> Result<()> {
+        T::create_role(self, role).await
+    }
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        T::delete_role(self, role).await
+    }
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        T::get_roles(self).await
+    }
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        T::grant_permission(self, role, permission).await
+    }
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        T::revoke_permission(self, role, permission).await
+    }
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        T::get_role_permissions(self, role).await
+    }
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        T::assign_role(self, user_id, role).await
+    }
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        T::unassign_role(self, user_id, role).await
+    }
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        T::create_organization(self, name, slug).await
+    }
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        T::get_organization_by_id(self, organization_id).await
+    }
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        T::get_organization_by_slug(self, slug).await
+    }
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        T::delete_organization(self, organization_id).await
+    }
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        T::set_membership(self, membership).await
+    }
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        T::get_membership(self, organization_id, user_id).await
+    }
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        T::get_memberships_by_organization(self, organization_id).await
+    }
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        T::get_memberships_by_user(self, user_id).await
+    }
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        T::delete_membership(self, organization_id, user_id).await
+    }
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        T::set_invitation(self, invitation).await
+    }
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        T::get_invitations(self, email).await
+    }
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        T::get_invitation_by_token(self, token_hash).await
+    }
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        T::delete_invitation_by_token(self, token_hash).await
+    }
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        T::delete_invitation(self, organization_id, email).await
+    }
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        T::create_signup_invite(self, invite).await
+    }
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        T::get_signup_invite(self, token_hash).await
+    }
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        T::delete_signup_invite(self, token_hash).await
+    }
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        T::set_user_disabled_at(self, user_id, disabled_at).await
+    }
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        T::set_user_deleted_at(self, user_id, deleted_at).await
+    }
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        T::purge_deleted_users(self, deleted_before).await
+    }
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        T::get_user_by_username(self, username).await
+    }
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        T::list_users(self, filter, page).await
+    }
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        T::count_users(self, filter).await
+    }
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        T::create_auth_event(self, entry).await
+    }
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        T::get_auth_events(self, user_id, before, limit, offset).await
+    }
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        T::add_password_history(self, user_id, hash).await
+    }
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        T::get_password_history(self, user_id, limit).await
+    }
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        T::prune_password_history(self, user_id, keep).await
+    }
 }
 
 
@@ -53,8 +250,8 @@ impl<T: DBConnection> DBConnection for tokio::sync::Mutex<T> {
     async fn init(&self) -> Result<()> {
         self.init().await
     }
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<(), Error> {
-        self.lock().await.create_user(email, hash, is_admin).await
+    async fn create_user(&self, email: &str, hash: &str, is_admin: bool, username: Option<&str>, profile: Option<&str>) -> Result<(), Error> {
+        self.lock().await.create_user(email, hash, is_admin, username, profile).await
     }
     async fn update_user(&self, user: &User) -> Result<()> {
         self.lock().await.update_user(user).await
@@ -71,5 +268,146 @@ impl<T: DBConnection> DBConnection for tokio::sync::Mutex<T> {
     async fn get_user_by_email(&self, email: &str) -> Result<User> {
         self.lock().await.get_user_by_email(email).await
     }
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        self.lock().await.create_oauth_client(client).await
+    }
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        self.lock().await.get_oauth_client(client_id).await
+    }
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        self.lock().await.delete_oauth_client(client_id).await
+    }
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        self.lock().await.set_oauth_consent(user_id, client_id, scope).await
+    }
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        self.lock().await.get_oauth_consent(user_id, client_id).await
+    }
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        self.lock().await.delete_oauth_consent(user_id, client_id).await
+    }
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        self.lock().await.create_oauth_token(token).await
+    }
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        self.lock().await.get_oauth_token(token_hash).await
+    }
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        self.lock().await.delete_oauth_token(token_hash).await
+    }
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        self.lock().await.delete_oauth_grant(grant_hash).await
+    }
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        self.lock().await.delete_user_oauth_tokens(user_id).await
+    }
+    async fn create_role(&self, role: &str) -> Result<()> {
+        self.lock().await.create_role(role).await
+    }
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        self.lock().await.delete_role(role).await
+    }
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        self.lock().await.get_roles().await
+    }
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        self.lock().await.grant_permission(role, permission).await
+    }
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        self.lock().await.revoke_permission(role, permission).await
+    }
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        self.lock().await.get_role_permissions(role).await
+    }
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        self.lock().await.assign_role(user_id, role).await
+    }
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        self.lock().await.unassign_role(user_id, role).await
+    }
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        self.lock().await.create_organization(name, slug).await
+    }
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        self.lock().await.get_organization_by_id(organization_id).await
+    }
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        self.lock().await.get_organization_by_slug(slug).await
+    }
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        self.lock().await.delete_organization(organization_id).await
+    }
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        self.lock().await.set_membership(membership).await
+    }
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        self.lock().await.get_membership(organization_id, user_id).await
+    }
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        self.lock().await.get_memberships_by_organization(organization_id).await
+    }
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        self.lock().await.get_memberships_by_user(user_id).await
+    }
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        self.lock().await.delete_membership(organization_id, user_id).await
+    }
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        self.lock().await.set_invitation(invitation).await
+    }
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        self.lock().await.get_invitations(email).await
+    }
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        self.lock().await.get_invitation_by_token(token_hash).await
+    }
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        self.lock().await.delete_invitation_by_token(token_hash).await
+    }
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        self.lock().await.delete_invitation(organization_id, email).await
+    }
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        self.lock().await.create_signup_invite(invite).await
+    }
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        self.lock().await.get_signup_invite(token_hash).await
+    }
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        self.lock().await.delete_signup_invite(token_hash).await
+    }
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        self.lock().await.set_user_disabled_at(user_id, disabled_at).await
+    }
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        self.lock().await.set_user_deleted_at(user_id, deleted_at).await
+    }
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        self.lock().await.purge_deleted_users(deleted_before).await
+    }
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        self.lock().await.get_user_by_username(username).await
+    }
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        self.lock().await.list_users(filter, page).await
+    }
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        self.lock().await.count_users(filter).await
+    }
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        self.lock().await.create_auth_event(entry).await
+    }
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        self.lock().await.get_auth_events(user_id, before, limit, offset).await
+    }
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        self.lock().await.add_password_history(user_id, hash).await
+    }
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        self.lock().await.get_password_history(user_id, limit).await
+    }
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        self.lock().await.prune_password_history(user_id, keep).await
+    }
 }
 
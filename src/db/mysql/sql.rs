@@ -1,32 +1,58 @@
+/// Selects users along with their comma separated roles and permissions.
+macro_rules! select_user {
+    ($condition:literal) => {
+        concat!(
+            "
+SELECT users.*,
+    (SELECT GROUP_CONCAT(roles.name) FROM user_roles
+        JOIN roles ON roles.id = user_roles.role_id
+        WHERE user_roles.user_id = users.id) AS roles,
+    (SELECT GROUP_CONCAT(DISTINCT role_permissions.permission) FROM user_roles
+        JOIN role_permissions ON role_permissions.role_id = user_roles.role_id
+        WHERE user_roles.user_id = users.id) AS permissions
+FROM users WHERE ",
+            $condition
+        )
+    };
+}
+
 pub(crate) const CREATE_TABLE: &str = "
 CREATE TABLE IF NOT EXISTS users (
     id INT PRIMARY KEY AUTO_INCREMENT,
     email VARCHAR (254) UNIQUE NOT NULL,
 	password VARCHAR ( 255 ) NOT NULL,
-    is_admin BOOLEAN DEFAULT FALSE
+    is_admin BOOLEAN DEFAULT FALSE,
+    disabled_at BIGINT,
+    deleted_at BIGINT,
+    profile TEXT,
+    -- the default collation is case insensitive
+    username VARCHAR (64) UNIQUE,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    password_changed_at BIGINT,
+    must_change_password BOOLEAN NOT NULL DEFAULT FALSE
 );
 ";
 
 pub(crate) const INSERT_USER: &str = "
-INSERT INTO users (email, password, is_admin) VALUES (?, ?, ?);
+INSERT INTO users (email, password, is_admin, username, created_at, password_changed_at, profile) VALUES (?, ?, ?, ?, ?, ?, ?);
 ";
 
 pub(crate) const UPDATE_USER: &str = "
 UPDATE users SET 
     email = ?,
     password = ?,
-    is_admin = ?
+    is_admin = ?,
+    profile = ?,
+    username = ?,
+    password_changed_at = ?,
+    must_change_password = ?
 WHERE
     id = ?
 ";
 
-pub(crate) const SELECT_BY_ID: &str = "
-SELECT * FROM users WHERE id = ?;
-";
+pub(crate) const SELECT_BY_ID: &str = select_user!("id = ?;");
 
-pub(crate) const SELECT_BY_EMAIL: &str = "
-SELECT * FROM users WHERE email = ?;
-";
+pub(crate) const SELECT_BY_EMAIL: &str = select_user!("email = ?;");
 
 pub(crate) const REMOVE_BY_ID: &str = "
 DELETE FROM users WHERE id = ?;
@@ -34,3 +60,342 @@ DELETE FROM users WHERE id = ?;
 pub(crate) const REMOVE_BY_EMAIL: &str = "
 DELETE FROM users WHERE email = ?;
 ";
+
+pub(crate) const SELECT_USER_COLUMNS: &str = "
+SELECT CAST(column_name AS CHAR) FROM information_schema.columns
+WHERE table_schema = DATABASE() AND table_name = 'users';
+";
+
+/// The columns added to `users` after its first version, along with the statement that adds
+/// each of them to tables created before. MySQL can't add them conditionally, so they are
+/// only added if they are missing from `SELECT_USER_COLUMNS`.
+pub(crate) const ADD_USER_COLUMNS: [(&str, &str); 7] = [
+    ("disabled_at", "ALTER TABLE users ADD COLUMN disabled_at BIGINT;"),
+    ("deleted_at", "ALTER TABLE users ADD COLUMN deleted_at BIGINT;"),
+    ("profile", "ALTER TABLE users ADD COLUMN profile TEXT;"),
+    ("username", "ALTER TABLE users ADD COLUMN username VARCHAR (64) UNIQUE;"),
+    ("created_at", "ALTER TABLE users ADD COLUMN created_at BIGINT NOT NULL DEFAULT 0;"),
+    ("password_changed_at", "ALTER TABLE users ADD COLUMN password_changed_at BIGINT;"),
+    ("must_change_password", "ALTER TABLE users ADD COLUMN must_change_password BOOLEAN NOT NULL DEFAULT FALSE;"),
+];
+
+pub(crate) const CREATE_OAUTH_TABLES: [&str; 3] = [
+    "
+CREATE TABLE IF NOT EXISTS oauth_clients (
+    client_id VARCHAR (64) PRIMARY KEY,
+    name VARCHAR (255) NOT NULL,
+    secret_hash VARCHAR (64),
+    redirect_uris TEXT NOT NULL,
+    scopes TEXT NOT NULL
+);",
+    "
+CREATE TABLE IF NOT EXISTS oauth_consents (
+    user_id INT NOT NULL,
+    client_id VARCHAR (64) NOT NULL,
+    scope TEXT NOT NULL,
+    PRIMARY KEY (user_id, client_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE
+);",
+    "
+CREATE TABLE IF NOT EXISTS oauth_tokens (
+    token_hash VARCHAR (64) PRIMARY KEY,
+    kind VARCHAR (16) NOT NULL,
+    client_id VARCHAR (64) NOT NULL,
+    user_id INT,
+    scope TEXT NOT NULL,
+    redirect_uri TEXT,
+    code_challenge VARCHAR (128),
+    code_challenge_method VARCHAR (8),
+    expires_at BIGINT NOT NULL,
+    grant_hash VARCHAR (64),
+    FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);",
+];
+
+pub(crate) const INSERT_OAUTH_CLIENT: &str = "
+INSERT INTO oauth_clients (client_id, name, secret_hash, redirect_uris, scopes) VALUES (?, ?, ?, ?, ?);
+";
+
+pub(crate) const SELECT_OAUTH_CLIENT: &str = "
+SELECT * FROM oauth_clients WHERE client_id = ?;
+";
+
+pub(crate) const REMOVE_OAUTH_CLIENT: &str = "
+DELETE FROM oauth_clients WHERE client_id = ?;
+";
+
+pub(crate) const UPSERT_OAUTH_CONSENT: &str = "
+INSERT INTO oauth_consents (user_id, client_id, scope) VALUES (?, ?, ?)
+ON DUPLICATE KEY UPDATE scope = VALUES(scope);
+";
+
+pub(crate) const SELECT_OAUTH_CONSENT: &str = "
+SELECT scope FROM oauth_consents WHERE user_id = ? AND client_id = ?;
+";
+
+pub(crate) const REMOVE_OAUTH_CONSENT: &str = "
+DELETE FROM oauth_consents WHERE user_id = ? AND client_id = ?;
+";
+
+pub(crate) const INSERT_OAUTH_TOKEN: &str = "
+INSERT INTO oauth_tokens (
+    token_hash, kind, client_id, user_id, scope,
+    redirect_uri, code_challenge, code_challenge_method, expires_at, grant_hash
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
+";
+
+pub(crate) const SELECT_OAUTH_TOKEN: &str = "
+SELECT * FROM oauth_tokens WHERE token_hash = ?;
+";
+
+pub(crate) const REMOVE_OAUTH_TOKEN: &str = "
+DELETE FROM oauth_tokens WHERE token_hash = ?;
+";
+
+pub(crate) const REMOVE_OAUTH_GRANT: &str = "
+DELETE FROM oauth_tokens WHERE grant_hash = ?;
+";
+
+pub(crate) const REMOVE_USER_OAUTH_TOKENS: &str = "
+DELETE FROM oauth_tokens WHERE user_id = ?;
+";
+
+pub(crate) const CREATE_ROLE_TABLES: [&str; 3] = [
+    "
+CREATE TABLE IF NOT EXISTS roles (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR (255) UNIQUE NOT NULL
+);",
+    "
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id INT NOT NULL,
+    role_id INT NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);",
+    "
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id INT NOT NULL,
+    permission VARCHAR (255) NOT NULL,
+    PRIMARY KEY (role_id, permission),
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);",
+];
+
+pub(crate) const INSERT_ROLE: &str = "
+INSERT INTO roles (name) VALUES (?);
+";
+
+pub(crate) const REMOVE_ROLE: &str = "
+DELETE FROM roles WHERE name = ?;
+";
+
+pub(crate) const SELECT_ROLES: &str = "
+SELECT name FROM roles ORDER BY name;
+";
+
+pub(crate) const INSERT_USER_ROLE: &str = "
+INSERT IGNORE INTO user_roles (user_id, role_id)
+SELECT ?, id FROM roles WHERE name = ?;
+";
+
+pub(crate) const REMOVE_USER_ROLE: &str = "
+DELETE FROM user_roles
+WHERE user_id = ? AND role_id IN (SELECT id FROM roles WHERE name = ?);
+";
+
+pub(crate) const INSERT_ROLE_PERMISSION: &str = "
+INSERT IGNORE INTO role_permissions (role_id, permission)
+SELECT id, ? FROM roles WHERE name = ?;
+";
+
+pub(crate) const REMOVE_ROLE_PERMISSION: &str = "
+DELETE FROM role_permissions
+WHERE permission = ? AND role_id IN (SELECT id FROM roles WHERE name = ?);
+";
+
+pub(crate) const SELECT_ROLE_PERMISSIONS: &str = "
+SELECT permission FROM role_permissions
+JOIN roles ON roles.id = role_permissions.role_id
+WHERE roles.name = ?
+ORDER BY permission;
+";
+
+pub(crate) const CREATE_ORGANIZATION_TABLES: [&str; 3] = [
+    "
+CREATE TABLE IF NOT EXISTS organizations (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR (255) NOT NULL,
+    slug VARCHAR (64) UNIQUE NOT NULL
+);",
+    "
+CREATE TABLE IF NOT EXISTS memberships (
+    organization_id INT NOT NULL,
+    user_id INT NOT NULL,
+    role VARCHAR (64) NOT NULL,
+    PRIMARY KEY (organization_id, user_id),
+    FOREIGN KEY (organization_id) REFERENCES organizations(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);",
+    "
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    organization_id INT NOT NULL,
+    email VARCHAR (254) NOT NULL,
+    role VARCHAR (64) NOT NULL,
+    token_hash VARCHAR (64) UNIQUE NOT NULL,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY (organization_id, email),
+    FOREIGN KEY (organization_id) REFERENCES organizations(id) ON DELETE CASCADE
+);",
+];
+
+pub(crate) const INSERT_ORGANIZATION: &str = "
+INSERT INTO organizations (name, slug) VALUES (?, ?);
+";
+
+pub(crate) const SELECT_ORGANIZATION_BY_ID: &str = "
+SELECT * FROM organizations WHERE id = ?;
+";
+
+pub(crate) const SELECT_ORGANIZATION_BY_SLUG: &str = "
+SELECT * FROM organizations WHERE slug = ?;
+";
+
+pub(crate) const REMOVE_ORGANIZATION: &str = "
+DELETE FROM organizations WHERE id = ?;
+";
+
+pub(crate) const UPSERT_MEMBERSHIP: &str = "
+INSERT INTO memberships (organization_id, user_id, role) VALUES (?, ?, ?)
+ON DUPLICATE KEY UPDATE role = VALUES(role);
+";
+
+pub(crate) const SELECT_MEMBERSHIP: &str = "
+SELECT * FROM memberships WHERE organization_id = ? AND user_id = ?;
+";
+
+pub(crate) const SELECT_MEMBERSHIPS_BY_ORGANIZATION: &str = "
+SELECT * FROM memberships WHERE organization_id = ? ORDER BY user_id;
+";
+
+pub(crate) const SELECT_MEMBERSHIPS_BY_USER: &str = "
+SELECT * FROM memberships WHERE user_id = ? ORDER BY organization_id;
+";
+
+pub(crate) const REMOVE_MEMBERSHIP: &str = "
+DELETE FROM memberships WHERE organization_id = ? AND user_id = ?;
+";
+
+pub(crate) const UPSERT_INVITATION: &str = "
+INSERT INTO organization_invitations (organization_id, email, role, token_hash, expires_at)
+VALUES (?, ?, ?, ?, ?)
+ON DUPLICATE KEY UPDATE role = VALUES(role), token_hash = VALUES(token_hash), expires_at = VALUES(expires_at);
+";
+
+pub(crate) const SELECT_INVITATIONS_BY_EMAIL: &str = "
+SELECT * FROM organization_invitations WHERE email = ? ORDER BY organization_id;
+";
+
+pub(crate) const SELECT_INVITATION_BY_TOKEN: &str = "
+SELECT * FROM organization_invitations WHERE token_hash = ?;
+";
+
+pub(crate) const REMOVE_INVITATION_BY_TOKEN: &str = "
+DELETE FROM organization_invitations WHERE token_hash = ?;
+";
+
+pub(crate) const REMOVE_INVITATION: &str = "
+DELETE FROM organization_invitations WHERE organization_id = ? AND email = ?;
+";
+
+pub(crate) const CREATE_SIGNUP_INVITES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS signup_invites (
+    token_hash VARCHAR (64) PRIMARY KEY,
+    email VARCHAR (254) NOT NULL,
+    role VARCHAR (255),
+    expires_at BIGINT NOT NULL
+);
+";
+
+pub(crate) const INSERT_SIGNUP_INVITE: &str = "
+INSERT INTO signup_invites (token_hash, email, role, expires_at) VALUES (?, ?, ?, ?);
+";
+
+pub(crate) const SELECT_SIGNUP_INVITE: &str = "
+SELECT * FROM signup_invites WHERE token_hash = ?;
+";
+
+pub(crate) const REMOVE_SIGNUP_INVITE: &str = "
+DELETE FROM signup_invites WHERE token_hash = ?;
+";
+
+pub(crate) const UPDATE_DISABLED_AT: &str = "
+UPDATE users SET disabled_at = ? WHERE id = ?;
+";
+
+pub(crate) const UPDATE_DELETED_AT: &str = "
+UPDATE users SET deleted_at = ? WHERE id = ?;
+";
+
+pub(crate) const PURGE_DELETED_USERS: &str = "
+DELETE FROM users WHERE deleted_at IS NOT NULL AND deleted_at <= ?;
+";
+
+pub(crate) const SELECT_BY_USERNAME: &str = select_user!("username = ?;");
+
+pub(crate) const CREATE_AUDIT_TABLES: [&str; 1] = ["
+CREATE TABLE IF NOT EXISTS auth_events (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    user_id INT,
+    event VARCHAR (32) NOT NULL,
+    ip VARCHAR (45),
+    user_agent TEXT,
+    created_at BIGINT NOT NULL,
+    INDEX (user_id)
+);"];
+
+pub(crate) const INSERT_AUTH_EVENT: &str = "
+INSERT INTO auth_events (user_id, event, ip, user_agent, created_at) VALUES (?, ?, ?, ?, ?);
+";
+
+pub(crate) const SELECT_AUTH_EVENTS: &str = "
+SELECT * FROM auth_events WHERE user_id = ? AND id < ? ORDER BY id DESC LIMIT ? OFFSET ?;
+";
+
+pub(crate) const CREATE_PASSWORD_HISTORY_TABLES: [&str; 1] = ["
+CREATE TABLE IF NOT EXISTS password_history (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    user_id INT NOT NULL,
+    password VARCHAR (255) NOT NULL,
+    created_at BIGINT NOT NULL,
+    INDEX (user_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);"];
+
+pub(crate) const INSERT_PASSWORD_HISTORY: &str = "
+INSERT INTO password_history (user_id, password, created_at) VALUES (?, ?, ?);
+";
+
+pub(crate) const SELECT_PASSWORD_HISTORY: &str = "
+SELECT password FROM password_history WHERE user_id = ? ORDER BY id DESC LIMIT ?;
+";
+
+/// Deletes every entry older than the newest ones. MySQL doesn't allow `LIMIT` in subqueries
+/// of the table being deleted from, so the newest entries are selected through a derived table.
+pub(crate) const PRUNE_PASSWORD_HISTORY: &str = "
+DELETE FROM password_history WHERE user_id = ? AND id <= (
+    SELECT id FROM (
+        SELECT id FROM password_history WHERE user_id = ? ORDER BY id DESC LIMIT 1 OFFSET ?
+    ) AS oldest
+);
+";
+
+/// Matches every user, so the filters of [`Users::list`](crate::Users::list) can be appended to it.
+pub(crate) const SELECT_USERS: &str = select_user!("1 = 1");
+
+pub(crate) fn placeholder(_n: usize) -> String {
+    "?".into()
+}
@@ -1,6 +1,11 @@
+use crate::audit::AuditEntry;
+use crate::oauth::{OAuthClient, OAuthToken};
+use crate::invites::SignupInvite;
+use crate::organizations::{Invitation, Membership, Organization};
 use crate::prelude::{Result, *};
 mod sql;
 use sql::*;
+use super::list::{self, bind_params, Param};
 
 use sqlx::postgres::PgPool;
 
@@ -10,13 +15,43 @@ use sqlx::*;
 impl DBConnection for PgPool {
     async fn init(&self) -> Result<()> {
         query(CREATE_TABLE).execute(self).await?;
+        for column in ADD_USER_COLUMNS {
+            query(column).execute(self).await?;
+        }
+        query(CREATE_USERNAME_INDEX).execute(self).await?;
+        for table in CREATE_OAUTH_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_ROLE_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_ORGANIZATION_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_AUDIT_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_PASSWORD_HISTORY_TABLES {
+            query(table).execute(self).await?;
+        }
+        query(CREATE_SIGNUP_INVITES_TABLE).execute(self).await?;
         Ok(())
     }
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<()> {
+    async fn create_user(
+        &self,
+        email: &str,
+        hash: &str,
+        is_admin: bool,
+        username: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<()> {
         query(INSERT_USER)
             .bind(email)
             .bind(hash)
             .bind(is_admin)
+            .bind(username)
+            .bind(now())
+            .bind(profile)
             .execute(self)
             .await?;
         Ok(())
@@ -27,6 +62,10 @@ impl DBConnection for PgPool {
             .bind(&user.email)
             .bind(&user.password)
             .bind(user.is_admin)
+            .bind(&user.profile)
+            .bind(&user.username)
+            .bind(user.password_changed_at)
+            .bind(user.must_change_password)
             .execute(self)
             .await?;
 
@@ -52,4 +91,379 @@ impl DBConnection for PgPool {
             .await?;
         Ok(user)
     }
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        query(INSERT_OAUTH_CLIENT)
+            .bind(&client.client_id)
+            .bind(&client.name)
+            .bind(&client.secret_hash)
+            .bind(&client.redirect_uris)
+            .bind(&client.scopes)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        let client = query_as(SELECT_OAUTH_CLIENT)
+            .bind(client_id)
+            .fetch_one(self)
+            .await?;
+        Ok(client)
+    }
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        query(REMOVE_OAUTH_CLIENT)
+            .bind(client_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        query(UPSERT_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .bind(scope)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        let scope = query_scalar(SELECT_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .fetch_optional(self)
+            .await?;
+        Ok(scope)
+    }
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        query(REMOVE_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        query(INSERT_OAUTH_TOKEN)
+            .bind(&token.token_hash)
+            .bind(&token.kind)
+            .bind(&token.client_id)
+            .bind(token.user_id)
+            .bind(&token.scope)
+            .bind(&token.redirect_uri)
+            .bind(&token.code_challenge)
+            .bind(&token.code_challenge_method)
+            .bind(token.expires_at)
+            .bind(&token.grant_hash)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        let token = query_as(SELECT_OAUTH_TOKEN)
+            .bind(token_hash)
+            .fetch_one(self)
+            .await?;
+        Ok(token)
+    }
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        let result = query(REMOVE_OAUTH_TOKEN)
+            .bind(token_hash)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        query(REMOVE_OAUTH_GRANT)
+            .bind(grant_hash)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        query(REMOVE_USER_OAUTH_TOKENS)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_role(&self, role: &str) -> Result<()> {
+        query(INSERT_ROLE)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        query(REMOVE_ROLE)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        let names = query_scalar(SELECT_ROLES)
+            .fetch_all(self)
+            .await?;
+        Ok(names)
+    }
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        query(INSERT_ROLE_PERMISSION)
+            .bind(role)
+            .bind(permission)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        query(REMOVE_ROLE_PERMISSION)
+            .bind(role)
+            .bind(permission)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        let names = query_scalar(SELECT_ROLE_PERMISSIONS)
+            .bind(role)
+            .fetch_all(self)
+            .await?;
+        Ok(names)
+    }
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        query(INSERT_USER_ROLE)
+            .bind(user_id)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        query(REMOVE_USER_ROLE)
+            .bind(user_id)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        query(INSERT_ORGANIZATION)
+            .bind(name)
+            .bind(slug)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        let result = query_as(SELECT_ORGANIZATION_BY_ID)
+            .bind(organization_id)
+            .fetch_one(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        let result = query_as(SELECT_ORGANIZATION_BY_SLUG)
+            .bind(slug)
+            .fetch_one(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        query(REMOVE_ORGANIZATION)
+            .bind(organization_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        query(UPSERT_MEMBERSHIP)
+            .bind(membership.organization_id)
+            .bind(membership.user_id)
+            .bind(&membership.role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        let result = query_as(SELECT_MEMBERSHIP)
+            .bind(organization_id)
+            .bind(user_id)
+            .fetch_optional(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        let result = query_as(SELECT_MEMBERSHIPS_BY_ORGANIZATION)
+            .bind(organization_id)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        let result = query_as(SELECT_MEMBERSHIPS_BY_USER)
+            .bind(user_id)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        query(REMOVE_MEMBERSHIP)
+            .bind(organization_id)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        query(UPSERT_INVITATION)
+            .bind(invitation.organization_id)
+            .bind(&invitation.email)
+            .bind(&invitation.role)
+            .bind(&invitation.token_hash)
+            .bind(invitation.expires_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        let result = query_as(SELECT_INVITATIONS_BY_EMAIL)
+            .bind(email)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        let result = query_as(SELECT_INVITATION_BY_TOKEN)
+            .bind(token_hash)
+            .fetch_optional(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        let result = query(REMOVE_INVITATION_BY_TOKEN)
+            .bind(token_hash)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        query(REMOVE_INVITATION)
+            .bind(organization_id)
+            .bind(email)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        query(INSERT_SIGNUP_INVITE)
+            .bind(&invite.token_hash)
+            .bind(&invite.email)
+            .bind(&invite.role)
+            .bind(invite.expires_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        let result = query_as(SELECT_SIGNUP_INVITE)
+            .bind(token_hash)
+            .fetch_optional(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        let result = query(REMOVE_SIGNUP_INVITE)
+            .bind(token_hash)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        query(UPDATE_DISABLED_AT)
+            .bind(disabled_at)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        query(UPDATE_DELETED_AT)
+            .bind(deleted_at)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        let result = query(PURGE_DELETED_USERS)
+            .bind(deleted_before)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected())
+    }
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        let result = query_as(SELECT_BY_USERNAME)
+            .bind(username)
+            .fetch_one(self)
+            .await?;
+        Ok(result)
+    }
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        let list = list::list_users(SELECT_USERS, filter, page, placeholder);
+        let users = bind_params!(query_as(&list.sql), &list.params)
+            .fetch_all(self)
+            .await?;
+        Ok(users)
+    }
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        let count = list::count_users(filter, placeholder);
+        let total: i64 = bind_params!(query_scalar(&count.sql), &count.params)
+            .fetch_one(self)
+            .await?;
+        Ok(total as u64)
+    }
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        query(INSERT_AUTH_EVENT)
+            .bind(entry.user_id)
+            .bind(entry.event.as_str())
+            .bind(&entry.ip)
+            .bind(&entry.user_agent)
+            .bind(entry.created_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        let result = query_as(SELECT_AUTH_EVENTS)
+            .bind(user_id)
+            .bind(before)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        query(INSERT_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(hash)
+            .bind(now())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        let result = query_scalar(SELECT_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        query(PRUNE_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(keep)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
 }
@@ -1,8 +1,15 @@
 mod sql;
 
+use crate::audit::AuditEntry;
+use crate::oauth::{OAuthClient, OAuthToken};
+use crate::invites::SignupInvite;
+use crate::organizations::{Invitation, Membership, Organization};
 use crate::prelude::{Result, *};
 use rocket::async_trait;
 use sql::*;
+use super::list::{self, Param};
+#[cfg(feature = "sqlx-sqlite")]
+use super::list::bind_params;
 use tokio::sync::Mutex;
 
 #[cfg(feature = "rusqlite")]
@@ -23,6 +30,114 @@ impl<'a> TryFrom<&rusqlite::Row<'a>> for crate::User {
             email: row.get(1)?,
             password: row.get(2)?,
             is_admin: row.get(3)?,
+            disabled_at: row.get("disabled_at")?,
+            deleted_at: row.get("deleted_at")?,
+            password_changed_at: row.get("password_changed_at")?,
+            must_change_password: row.get("must_change_password")?,
+            profile: row.get("profile")?,
+            username: row.get("username")?,
+            created_at: row.get("created_at")?,
+            roles: row.get::<_, Option<String>>("roles").ok().flatten().into(),
+            permissions: row.get::<_, Option<String>>("permissions").ok().flatten().into(),
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for OAuthClient {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<OAuthClient, rusqlite::Error> {
+        Ok(OAuthClient {
+            client_id: row.get("client_id")?,
+            name: row.get("name")?,
+            secret_hash: row.get("secret_hash")?,
+            redirect_uris: row.get("redirect_uris")?,
+            scopes: row.get("scopes")?,
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for OAuthToken {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<OAuthToken, rusqlite::Error> {
+        Ok(OAuthToken {
+            token_hash: row.get("token_hash")?,
+            kind: row.get("kind")?,
+            client_id: row.get("client_id")?,
+            user_id: row.get("user_id")?,
+            scope: row.get("scope")?,
+            redirect_uri: row.get("redirect_uri")?,
+            code_challenge: row.get("code_challenge")?,
+            code_challenge_method: row.get("code_challenge_method")?,
+            expires_at: row.get("expires_at")?,
+            grant_hash: row.get("grant_hash")?,
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for Organization {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<Organization, rusqlite::Error> {
+        Ok(Organization {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            slug: row.get("slug")?,
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for Membership {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<Membership, rusqlite::Error> {
+        Ok(Membership {
+            organization_id: row.get("organization_id")?,
+            user_id: row.get("user_id")?,
+            role: row.get("role")?,
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for Invitation {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<Invitation, rusqlite::Error> {
+        Ok(Invitation {
+            organization_id: row.get("organization_id")?,
+            email: row.get("email")?,
+            role: row.get("role")?,
+            token_hash: row.get("token_hash")?,
+            expires_at: row.get("expires_at")?,
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for SignupInvite {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<SignupInvite, rusqlite::Error> {
+        Ok(SignupInvite {
+            token_hash: row.get("token_hash")?,
+            email: row.get("email")?,
+            role: row.get("role")?,
+            expires_at: row.get("expires_at")?,
+        })
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl<'a> TryFrom<&rusqlite::Row<'a>> for AuditEntry {
+    type Error = rusqlite::Error;
+    fn try_from(row: &Row) -> Result<AuditEntry, rusqlite::Error> {
+        Ok(AuditEntry {
+            id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            event: row.get("event")?,
+            ip: row.get("ip")?,
+            user_agent: row.get("user_agent")?,
+            created_at: row.get("created_at")?,
         })
     }
 }
@@ -32,13 +147,49 @@ impl<'a> TryFrom<&rusqlite::Row<'a>> for crate::User {
 impl DBConnection for Mutex<rusqlite::Connection> {
     async fn init(&self) -> Result<()> {
         let conn = self.lock().await;
+        // sqlx enables foreign keys by default, but rusqlite doesn't.
+        block_in_place(|| conn.execute_batch(ENABLE_FOREIGN_KEYS))?;
         block_in_place(|| conn.execute(CREATE_TABLE, []))?;
+        let columns = block_in_place(|| {
+            conn.prepare(SELECT_USER_COLUMNS)?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })?;
+        for (column, add_column) in ADD_USER_COLUMNS {
+            if !columns.iter().any(|name| name == column) {
+                block_in_place(|| conn.execute(add_column, []))?;
+            }
+        }
+        block_in_place(|| conn.execute(CREATE_USERNAME_INDEX, []))?;
+        for table in CREATE_OAUTH_TABLES {
+            block_in_place(|| conn.execute(table, []))?;
+        }
+        for table in CREATE_ROLE_TABLES {
+            block_in_place(|| conn.execute(table, []))?;
+        }
+        for table in CREATE_ORGANIZATION_TABLES {
+            block_in_place(|| conn.execute(table, []))?;
+        }
+        for table in CREATE_AUDIT_TABLES {
+            block_in_place(|| conn.execute(table, []))?;
+        }
+        for table in CREATE_PASSWORD_HISTORY_TABLES {
+            block_in_place(|| conn.execute(table, []))?;
+        }
+        block_in_place(|| conn.execute(CREATE_SIGNUP_INVITES_TABLE, []))?;
         Ok(())
     }
 
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<()> {
+    async fn create_user(
+        &self,
+        email: &str,
+        hash: &str,
+        is_admin: bool,
+        username: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<()> {
         let conn = self.lock().await;
-        block_in_place(|| conn.execute(INSERT_USER, params![email, hash, is_admin]))?;
+        block_in_place(|| conn.execute(INSERT_USER, params![email, hash, is_admin, username, now(), profile]))?;
 
         Ok(())
     }
@@ -48,7 +199,16 @@ impl DBConnection for Mutex<rusqlite::Connection> {
         block_in_place(|| {
             conn.execute(
                 UPDATE_USER,
-                params![user.id, user.email, user.password, user.is_admin],
+                params![
+                    user.id,
+                    user.email,
+                    user.password,
+                    user.is_admin,
+                    user.profile,
+                    user.username,
+                    user.password_changed_at,
+                    user.must_change_password
+                ],
             )
         })?;
         Ok(())
@@ -89,125 +249,1424 @@ impl DBConnection for Mutex<rusqlite::Connection> {
         })?;
         Ok(user)
     }
-}
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| {
+            conn.execute(
+                INSERT_OAUTH_CLIENT,
+                params![
+                    client.client_id,
+                    client.name,
+                    client.secret_hash,
+                    client.redirect_uris,
+                    client.scopes
+                ],
+            )
+        })?;
+        Ok(())
+    }
 
-#[cfg(feature = "sqlx-sqlite")]
-use sqlx::{sqlite::SqliteConnection, *};
-#[cfg(feature = "sqlx-sqlite")]
-#[async_trait]
-impl DBConnection for Mutex<SqliteConnection> {
-    async fn init(&self) -> Result<()> {
-        let mut db = self.lock().await;
-        query(CREATE_TABLE).execute(&mut *db).await?;
-        println!("table created");
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        let conn = self.lock().await;
+        let client = block_in_place(|| {
+            conn.query_row(
+                SELECT_OAUTH_CLIENT, //
+                params![client_id],
+                |row| row.try_into(),
+            )
+        })?;
+        Ok(client)
+    }
+
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_OAUTH_CLIENT, params![client_id]))?;
         Ok(())
     }
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<()> {
-        let mut db = self.lock().await;
-        query(INSERT_USER)
-            .bind(email)
-            .bind(hash)
-            .bind(is_admin)
-            .execute(&mut *db)
-            .await?;
+
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(UPSERT_OAUTH_CONSENT, params![user_id, client_id, scope]))?;
         Ok(())
     }
-    async fn update_user(&self, user: &User) -> Result<()> {
-        let mut db = self.lock().await;
-        query(UPDATE_USER)
-            .bind(user.id)
-            .bind(&user.email)
-            .bind(&user.password)
-            .bind(user.is_admin)
-            .execute(&mut *db)
-            .await?;
+
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        let conn = self.lock().await;
+        let scope = block_in_place(|| {
+            conn.query_row(
+                SELECT_OAUTH_CONSENT, //
+                params![user_id, client_id],
+                |row| row.get(0),
+            )
+            .optional()
+        })?;
+        Ok(scope)
+    }
+
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_OAUTH_CONSENT, params![user_id, client_id]))?;
         Ok(())
     }
-    async fn delete_user_by_id(&self, user_id: i32) -> Result<()> {
-        query(REMOVE_BY_ID)
-            .bind(user_id)
-            .execute(&mut *self.lock().await)
-            .await?;
+
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| {
+            conn.execute(
+                INSERT_OAUTH_TOKEN,
+                params![
+                    token.token_hash,
+                    token.kind,
+                    token.client_id,
+                    token.user_id,
+                    token.scope,
+                    token.redirect_uri,
+                    token.code_challenge,
+                    token.code_challenge_method,
+                    token.expires_at,
+                    token.grant_hash
+                ],
+            )
+        })?;
         Ok(())
     }
-    async fn delete_user_by_email(&self, email: &str) -> Result<()> {
-        query(REMOVE_BY_EMAIL)
-            .bind(email)
-            .execute(&mut *self.lock().await)
-            .await?;
+
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        let conn = self.lock().await;
+        let token = block_in_place(|| {
+            conn.query_row(
+                SELECT_OAUTH_TOKEN, //
+                params![token_hash],
+                |row| row.try_into(),
+            )
+        })?;
+        Ok(token)
+    }
+
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        let conn = self.lock().await;
+        let deleted = block_in_place(|| conn.execute(REMOVE_OAUTH_TOKEN, params![token_hash]))?;
+        Ok(deleted == 1)
+    }
+
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_OAUTH_GRANT, params![grant_hash]))?;
         Ok(())
     }
-    async fn get_user_by_id(&self, user_id: i32) -> Result<User> {
-        let mut db = self.lock().await;
 
-        let user = query_as(SELECT_BY_ID)
-            .bind(user_id)
-            .fetch_one(&mut *db)
-            .await?;
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_USER_OAUTH_TOKENS, params![user_id]))?;
+        Ok(())
+    }
 
-        Ok(user)
+    async fn create_role(&self, role: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_ROLE, params![role]))?;
+        Ok(())
     }
-    async fn get_user_by_email(&self, email: &str) -> Result<User> {
-        let mut db = self.lock().await;
-        let user = query_as(SELECT_BY_EMAIL)
-            .bind(email)
-            .fetch_one(&mut *db)
-            .await?;
-        Ok(user)
+
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_ROLE, params![role]))?;
+        Ok(())
     }
-}
-#[cfg(feature = "sqlx-sqlite")]
-#[rocket::async_trait]
-impl DBConnection for SqlitePool {
-    async fn init(&self) -> Result<()> {
-        query(CREATE_TABLE) //
-            .execute(self)
-            .await?;
+
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        let conn = self.lock().await;
+        let names = block_in_place(|| {
+            conn.prepare(SELECT_ROLES)?
+                .query_map(params![], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })?;
+        Ok(names)
+    }
+
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_ROLE_PERMISSION, params![role, permission]))?;
         Ok(())
     }
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<()> {
-        query(INSERT_USER)
-            .bind(email)
-            .bind(hash)
-            .bind(is_admin)
-            .execute(self)
-            .await?;
+
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_ROLE_PERMISSION, params![role, permission]))?;
         Ok(())
     }
-    async fn update_user(&self, user: &User) -> Result<()> {
-        query(UPDATE_USER)
-            .bind(user.id)
-            .bind(&user.email)
-            .bind(&user.password)
-            .bind(user.is_admin)
-            .execute(self)
-            .await?;
+
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        let conn = self.lock().await;
+        let names = block_in_place(|| {
+            conn.prepare(SELECT_ROLE_PERMISSIONS)?
+                .query_map(params![role], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })?;
+        Ok(names)
+    }
+
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_USER_ROLE, params![user_id, role]))?;
         Ok(())
     }
-    async fn delete_user_by_id(&self, user_id: i32) -> Result<()> {
-        query(REMOVE_BY_ID) //
-            .bind(user_id)
-            .execute(self)
-            .await?;
+
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_USER_ROLE, params![user_id, role]))?;
         Ok(())
     }
-    async fn delete_user_by_email(&self, email: &str) -> Result<()> {
-        query(REMOVE_BY_EMAIL) //
-            .bind(email)
-            .execute(self)
-            .await?;
+
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_ORGANIZATION, params![name, slug]))?;
         Ok(())
     }
-    async fn get_user_by_id(&self, user_id: i32) -> Result<User> {
-        let user = query_as(SELECT_BY_ID) //
-            .bind(user_id)
-            .fetch_one(self)
-            .await?;
-        Ok(user)
+
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.query_row(
+                SELECT_ORGANIZATION_BY_ID, //
+                params![organization_id],
+                |row| row.try_into(),
+            )
+        })?;
+        Ok(result)
     }
-    async fn get_user_by_email(&self, email: &str) -> Result<User> {
-        let user = query_as(SELECT_BY_EMAIL).bind(email).fetch_one(self).await;
-        println!("user: {:?}", user);
-        Ok(user?)
+
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.query_row(
+                SELECT_ORGANIZATION_BY_SLUG, //
+                params![slug],
+                |row| row.try_into(),
+            )
+        })?;
+        Ok(result)
+    }
+
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_ORGANIZATION, params![organization_id]))?;
+        Ok(())
+    }
+
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(UPSERT_MEMBERSHIP, params![membership.organization_id, membership.user_id, membership.role]))?;
+        Ok(())
+    }
+
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.query_row(
+                SELECT_MEMBERSHIP, //
+                params![organization_id, user_id],
+                |row| row.try_into(),
+            )
+            .optional()
+        })?;
+        Ok(result)
+    }
+
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.prepare(SELECT_MEMBERSHIPS_BY_ORGANIZATION)?
+                .query_map(params![organization_id], |row| row.try_into())?
+                .collect::<rusqlite::Result<_>>()
+        })?;
+        Ok(result)
+    }
+
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.prepare(SELECT_MEMBERSHIPS_BY_USER)?
+                .query_map(params![user_id], |row| row.try_into())?
+                .collect::<rusqlite::Result<_>>()
+        })?;
+        Ok(result)
+    }
+
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_MEMBERSHIP, params![organization_id, user_id]))?;
+        Ok(())
+    }
+
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| {
+            conn.execute(
+                UPSERT_INVITATION,
+                params![
+                    invitation.organization_id,
+                    invitation.email,
+                    invitation.role,
+                    invitation.token_hash,
+                    invitation.expires_at
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.prepare(SELECT_INVITATIONS_BY_EMAIL)?
+                .query_map(params![email], |row| row.try_into())?
+                .collect::<rusqlite::Result<_>>()
+        })?;
+        Ok(result)
+    }
+
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.query_row(
+                SELECT_INVITATION_BY_TOKEN, //
+                params![token_hash],
+                |row| row.try_into(),
+            )
+            .optional()
+        })?;
+        Ok(result)
+    }
+
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        let conn = self.lock().await;
+        let deleted = block_in_place(|| conn.execute(REMOVE_INVITATION_BY_TOKEN, params![token_hash]))?;
+        Ok(deleted == 1)
+    }
+
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(REMOVE_INVITATION, params![organization_id, email]))?;
+        Ok(())
+    }
+
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_SIGNUP_INVITE, params![invite.token_hash, invite.email, invite.role, invite.expires_at]))?;
+        Ok(())
+    }
+
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.query_row(
+                SELECT_SIGNUP_INVITE, //
+                params![token_hash],
+                |row| row.try_into(),
+            )
+            .optional()
+        })?;
+        Ok(result)
+    }
+
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        let conn = self.lock().await;
+        let deleted = block_in_place(|| conn.execute(REMOVE_SIGNUP_INVITE, params![token_hash]))?;
+        Ok(deleted == 1)
+    }
+
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(UPDATE_DISABLED_AT, params![disabled_at, user_id]))?;
+        Ok(())
+    }
+
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(UPDATE_DELETED_AT, params![deleted_at, user_id]))?;
+        Ok(())
+    }
+
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        let conn = self.lock().await;
+        let count = block_in_place(|| conn.execute(PURGE_DELETED_USERS, params![deleted_before]))?;
+        Ok(count as u64)
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.query_row(
+                SELECT_BY_USERNAME, //
+                params![username],
+                |row| row.try_into(),
+            )
+        })?;
+        Ok(result)
+    }
+
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        let conn = self.lock().await;
+        let list = list::list_users(SELECT_USERS, filter, page, placeholder);
+        let users = block_in_place(|| {
+            conn.prepare(&list.sql)?
+                .query_map(params_from_iter(to_sql(&list.params)), |row| row.try_into())?
+                .collect::<rusqlite::Result<_>>()
+        })?;
+        Ok(users)
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        let conn = self.lock().await;
+        let count = list::count_users(filter, placeholder);
+        let total: i64 = block_in_place(|| {
+            conn.query_row(&count.sql, params_from_iter(to_sql(&count.params)), |row| row.get(0))
+        })?;
+        Ok(total as u64)
+    }
+
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_AUTH_EVENT, params![entry.user_id, entry.event.as_str(), entry.ip, entry.user_agent, entry.created_at]))?;
+        Ok(())
+    }
+
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.prepare(SELECT_AUTH_EVENTS)?
+                .query_map(params![user_id, before, limit, offset], |row| row.try_into())?
+                .collect::<rusqlite::Result<_>>()
+        })?;
+        Ok(result)
+    }
+
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(INSERT_PASSWORD_HISTORY, params![user_id, hash, now()]))?;
+        Ok(())
+    }
+
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        let conn = self.lock().await;
+        let result = block_in_place(|| {
+            conn.prepare(SELECT_PASSWORD_HISTORY)?
+                .query_map(params![user_id, limit], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()
+        })?;
+        Ok(result)
+    }
+
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        let conn = self.lock().await;
+        block_in_place(|| conn.execute(PRUNE_PASSWORD_HISTORY, params![user_id, keep]))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+fn to_sql(params: &[Param]) -> Vec<&dyn ToSql> {
+    params
+        .iter()
+        .map(|param| match param {
+            Param::Text(text) => text as &dyn ToSql,
+            Param::Int(int) => int,
+            Param::BigInt(int) => int,
+            Param::Bool(boolean) => boolean,
+        })
+        .collect()
+}
+
+#[cfg(feature = "sqlx-sqlite")]
+use sqlx::{sqlite::SqliteConnection, *};
+#[cfg(feature = "sqlx-sqlite")]
+#[async_trait]
+impl DBConnection for Mutex<SqliteConnection> {
+    async fn init(&self) -> Result<()> {
+        let mut db = self.lock().await;
+        query(CREATE_TABLE).execute(&mut *db).await?;
+        let columns: Vec<String> = query_scalar(SELECT_USER_COLUMNS)
+            .fetch_all(&mut *db)
+            .await?;
+        for (column, add_column) in ADD_USER_COLUMNS {
+            if !columns.iter().any(|name| name == column) {
+                query(add_column).execute(&mut *db).await?;
+            }
+        }
+        query(CREATE_USERNAME_INDEX).execute(&mut *db).await?;
+        for table in CREATE_OAUTH_TABLES {
+            query(table).execute(&mut *db).await?;
+        }
+        for table in CREATE_ROLE_TABLES {
+            query(table).execute(&mut *db).await?;
+        }
+        for table in CREATE_ORGANIZATION_TABLES {
+            query(table).execute(&mut *db).await?;
+        }
+        for table in CREATE_AUDIT_TABLES {
+            query(table).execute(&mut *db).await?;
+        }
+        for table in CREATE_PASSWORD_HISTORY_TABLES {
+            query(table).execute(&mut *db).await?;
+        }
+        query(CREATE_SIGNUP_INVITES_TABLE).execute(&mut *db).await?;
+        println!("table created");
+        Ok(())
+    }
+    async fn create_user(
+        &self,
+        email: &str,
+        hash: &str,
+        is_admin: bool,
+        username: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_USER)
+            .bind(email)
+            .bind(hash)
+            .bind(is_admin)
+            .bind(username)
+            .bind(now())
+            .bind(profile)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn update_user(&self, user: &User) -> Result<()> {
+        let mut db = self.lock().await;
+        query(UPDATE_USER)
+            .bind(user.id)
+            .bind(&user.email)
+            .bind(&user.password)
+            .bind(user.is_admin)
+            .bind(&user.profile)
+            .bind(&user.username)
+            .bind(user.password_changed_at)
+            .bind(user.must_change_password)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_by_id(&self, user_id: i32) -> Result<()> {
+        query(REMOVE_BY_ID)
+            .bind(user_id)
+            .execute(&mut *self.lock().await)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_by_email(&self, email: &str) -> Result<()> {
+        query(REMOVE_BY_EMAIL)
+            .bind(email)
+            .execute(&mut *self.lock().await)
+            .await?;
+        Ok(())
+    }
+    async fn get_user_by_id(&self, user_id: i32) -> Result<User> {
+        let mut db = self.lock().await;
+
+        let user = query_as(SELECT_BY_ID)
+            .bind(user_id)
+            .fetch_one(&mut *db)
+            .await?;
+
+        Ok(user)
+    }
+    async fn get_user_by_email(&self, email: &str) -> Result<User> {
+        let mut db = self.lock().await;
+        let user = query_as(SELECT_BY_EMAIL)
+            .bind(email)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(user)
+    }
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_OAUTH_CLIENT)
+            .bind(&client.client_id)
+            .bind(&client.name)
+            .bind(&client.secret_hash)
+            .bind(&client.redirect_uris)
+            .bind(&client.scopes)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        let mut db = self.lock().await;
+        let client = query_as(SELECT_OAUTH_CLIENT)
+            .bind(client_id)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(client)
+    }
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_OAUTH_CLIENT)
+            .bind(client_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(UPSERT_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .bind(scope)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        let mut db = self.lock().await;
+        let scope = query_scalar(SELECT_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .fetch_optional(&mut *db)
+            .await?;
+        Ok(scope)
+    }
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_OAUTH_TOKEN)
+            .bind(&token.token_hash)
+            .bind(&token.kind)
+            .bind(&token.client_id)
+            .bind(token.user_id)
+            .bind(&token.scope)
+            .bind(&token.redirect_uri)
+            .bind(&token.code_challenge)
+            .bind(&token.code_challenge_method)
+            .bind(token.expires_at)
+            .bind(&token.grant_hash)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        let mut db = self.lock().await;
+        let token = query_as(SELECT_OAUTH_TOKEN)
+            .bind(token_hash)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(token)
+    }
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        let mut db = self.lock().await;
+        let result = query(REMOVE_OAUTH_TOKEN)
+            .bind(token_hash)
+            .execute(&mut *db)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_OAUTH_GRANT)
+            .bind(grant_hash)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_USER_OAUTH_TOKENS)
+            .bind(user_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn create_role(&self, role: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_ROLE)
+            .bind(role)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_ROLE)
+            .bind(role)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        let mut db = self.lock().await;
+        let names = query_scalar(SELECT_ROLES)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(names)
+    }
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_ROLE_PERMISSION)
+            .bind(role)
+            .bind(permission)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_ROLE_PERMISSION)
+            .bind(role)
+            .bind(permission)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        let mut db = self.lock().await;
+        let names = query_scalar(SELECT_ROLE_PERMISSIONS)
+            .bind(role)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(names)
+    }
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_USER_ROLE)
+            .bind(user_id)
+            .bind(role)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_USER_ROLE)
+            .bind(user_id)
+            .bind(role)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_ORGANIZATION)
+            .bind(name)
+            .bind(slug)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_ORGANIZATION_BY_ID)
+            .bind(organization_id)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_ORGANIZATION_BY_SLUG)
+            .bind(slug)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_ORGANIZATION)
+            .bind(organization_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        let mut db = self.lock().await;
+        query(UPSERT_MEMBERSHIP)
+            .bind(membership.organization_id)
+            .bind(membership.user_id)
+            .bind(&membership.role)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_MEMBERSHIP)
+            .bind(organization_id)
+            .bind(user_id)
+            .fetch_optional(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_MEMBERSHIPS_BY_ORGANIZATION)
+            .bind(organization_id)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_MEMBERSHIPS_BY_USER)
+            .bind(user_id)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_MEMBERSHIP)
+            .bind(organization_id)
+            .bind(user_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        let mut db = self.lock().await;
+        query(UPSERT_INVITATION)
+            .bind(invitation.organization_id)
+            .bind(&invitation.email)
+            .bind(&invitation.role)
+            .bind(&invitation.token_hash)
+            .bind(invitation.expires_at)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_INVITATIONS_BY_EMAIL)
+            .bind(email)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_INVITATION_BY_TOKEN)
+            .bind(token_hash)
+            .fetch_optional(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        let mut db = self.lock().await;
+        let result = query(REMOVE_INVITATION_BY_TOKEN)
+            .bind(token_hash)
+            .execute(&mut *db)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(REMOVE_INVITATION)
+            .bind(organization_id)
+            .bind(email)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_SIGNUP_INVITE)
+            .bind(&invite.token_hash)
+            .bind(&invite.email)
+            .bind(&invite.role)
+            .bind(invite.expires_at)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_SIGNUP_INVITE)
+            .bind(token_hash)
+            .fetch_optional(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        let mut db = self.lock().await;
+        let result = query(REMOVE_SIGNUP_INVITE)
+            .bind(token_hash)
+            .execute(&mut *db)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        let mut db = self.lock().await;
+        query(UPDATE_DISABLED_AT)
+            .bind(disabled_at)
+            .bind(user_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        let mut db = self.lock().await;
+        query(UPDATE_DELETED_AT)
+            .bind(deleted_at)
+            .bind(user_id)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        let mut db = self.lock().await;
+        let result = query(PURGE_DELETED_USERS)
+            .bind(deleted_before)
+            .execute(&mut *db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_BY_USERNAME)
+            .bind(username)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        let mut db = self.lock().await;
+        let list = list::list_users(SELECT_USERS, filter, page, placeholder);
+        let users = bind_params!(query_as(&list.sql), &list.params)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(users)
+    }
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        let mut db = self.lock().await;
+        let count = list::count_users(filter, placeholder);
+        let total: i64 = bind_params!(query_scalar(&count.sql), &count.params)
+            .fetch_one(&mut *db)
+            .await?;
+        Ok(total as u64)
+    }
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_AUTH_EVENT)
+            .bind(entry.user_id)
+            .bind(entry.event.as_str())
+            .bind(&entry.ip)
+            .bind(&entry.user_agent)
+            .bind(entry.created_at)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        let mut db = self.lock().await;
+        let result = query_as(SELECT_AUTH_EVENTS)
+            .bind(user_id)
+            .bind(before)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        let mut db = self.lock().await;
+        query(INSERT_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(hash)
+            .bind(now())
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        let mut db = self.lock().await;
+        let result = query_scalar(SELECT_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(&mut *db)
+            .await?;
+        Ok(result)
+    }
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        let mut db = self.lock().await;
+        query(PRUNE_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(keep)
+            .execute(&mut *db)
+            .await?;
+        Ok(())
+    }
+}
+#[cfg(feature = "sqlx-sqlite")]
+#[rocket::async_trait]
+impl DBConnection for SqlitePool {
+    async fn init(&self) -> Result<()> {
+        query(CREATE_TABLE) //
+            .execute(self)
+            .await?;
+        let columns: Vec<String> = query_scalar(SELECT_USER_COLUMNS).fetch_all(self).await?;
+        for (column, add_column) in ADD_USER_COLUMNS {
+            if !columns.iter().any(|name| name == column) {
+                query(add_column).execute(self).await?;
+            }
+        }
+        query(CREATE_USERNAME_INDEX).execute(self).await?;
+        for table in CREATE_OAUTH_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_ROLE_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_ORGANIZATION_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_AUDIT_TABLES {
+            query(table).execute(self).await?;
+        }
+        for table in CREATE_PASSWORD_HISTORY_TABLES {
+            query(table).execute(self).await?;
+        }
+        query(CREATE_SIGNUP_INVITES_TABLE).execute(self).await?;
+        Ok(())
+    }
+    async fn create_user(
+        &self,
+        email: &str,
+        hash: &str,
+        is_admin: bool,
+        username: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<()> {
+        query(INSERT_USER)
+            .bind(email)
+            .bind(hash)
+            .bind(is_admin)
+            .bind(username)
+            .bind(now())
+            .bind(profile)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn update_user(&self, user: &User) -> Result<()> {
+        query(UPDATE_USER)
+            .bind(user.id)
+            .bind(&user.email)
+            .bind(&user.password)
+            .bind(user.is_admin)
+            .bind(&user.profile)
+            .bind(&user.username)
+            .bind(user.password_changed_at)
+            .bind(user.must_change_password)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_by_id(&self, user_id: i32) -> Result<()> {
+        query(REMOVE_BY_ID) //
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_by_email(&self, email: &str) -> Result<()> {
+        query(REMOVE_BY_EMAIL) //
+            .bind(email)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_user_by_id(&self, user_id: i32) -> Result<User> {
+        let user = query_as(SELECT_BY_ID) //
+            .bind(user_id)
+            .fetch_one(self)
+            .await?;
+        Ok(user)
+    }
+    async fn get_user_by_email(&self, email: &str) -> Result<User> {
+        let user = query_as(SELECT_BY_EMAIL).bind(email).fetch_one(self).await;
+        println!("user: {:?}", user);
+        Ok(user?)
+    }
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        query(INSERT_OAUTH_CLIENT)
+            .bind(&client.client_id)
+            .bind(&client.name)
+            .bind(&client.secret_hash)
+            .bind(&client.redirect_uris)
+            .bind(&client.scopes)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        let client = query_as(SELECT_OAUTH_CLIENT)
+            .bind(client_id)
+            .fetch_one(self)
+            .await?;
+        Ok(client)
+    }
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        query(REMOVE_OAUTH_CLIENT)
+            .bind(client_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        query(UPSERT_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .bind(scope)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        let scope = query_scalar(SELECT_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .fetch_optional(self)
+            .await?;
+        Ok(scope)
+    }
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        query(REMOVE_OAUTH_CONSENT)
+            .bind(user_id)
+            .bind(client_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        query(INSERT_OAUTH_TOKEN)
+            .bind(&token.token_hash)
+            .bind(&token.kind)
+            .bind(&token.client_id)
+            .bind(token.user_id)
+            .bind(&token.scope)
+            .bind(&token.redirect_uri)
+            .bind(&token.code_challenge)
+            .bind(&token.code_challenge_method)
+            .bind(token.expires_at)
+            .bind(&token.grant_hash)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        let token = query_as(SELECT_OAUTH_TOKEN)
+            .bind(token_hash)
+            .fetch_one(self)
+            .await?;
+        Ok(token)
+    }
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        let result = query(REMOVE_OAUTH_TOKEN)
+            .bind(token_hash)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        query(REMOVE_OAUTH_GRANT)
+            .bind(grant_hash)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        query(REMOVE_USER_OAUTH_TOKENS)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_role(&self, role: &str) -> Result<()> {
+        query(INSERT_ROLE)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        query(REMOVE_ROLE)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        let names = query_scalar(SELECT_ROLES)
+            .fetch_all(self)
+            .await?;
+        Ok(names)
+    }
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        query(INSERT_ROLE_PERMISSION)
+            .bind(role)
+            .bind(permission)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        query(REMOVE_ROLE_PERMISSION)
+            .bind(role)
+            .bind(permission)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        let names = query_scalar(SELECT_ROLE_PERMISSIONS)
+            .bind(role)
+            .fetch_all(self)
+            .await?;
+        Ok(names)
+    }
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        query(INSERT_USER_ROLE)
+            .bind(user_id)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        query(REMOVE_USER_ROLE)
+            .bind(user_id)
+            .bind(role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        query(INSERT_ORGANIZATION)
+            .bind(name)
+            .bind(slug)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        let result = query_as(SELECT_ORGANIZATION_BY_ID)
+            .bind(organization_id)
+            .fetch_one(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        let result = query_as(SELECT_ORGANIZATION_BY_SLUG)
+            .bind(slug)
+            .fetch_one(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        query(REMOVE_ORGANIZATION)
+            .bind(organization_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        query(UPSERT_MEMBERSHIP)
+            .bind(membership.organization_id)
+            .bind(membership.user_id)
+            .bind(&membership.role)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        let result = query_as(SELECT_MEMBERSHIP)
+            .bind(organization_id)
+            .bind(user_id)
+            .fetch_optional(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        let result = query_as(SELECT_MEMBERSHIPS_BY_ORGANIZATION)
+            .bind(organization_id)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        let result = query_as(SELECT_MEMBERSHIPS_BY_USER)
+            .bind(user_id)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        query(REMOVE_MEMBERSHIP)
+            .bind(organization_id)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        query(UPSERT_INVITATION)
+            .bind(invitation.organization_id)
+            .bind(&invitation.email)
+            .bind(&invitation.role)
+            .bind(&invitation.token_hash)
+            .bind(invitation.expires_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        let result = query_as(SELECT_INVITATIONS_BY_EMAIL)
+            .bind(email)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        let result = query_as(SELECT_INVITATION_BY_TOKEN)
+            .bind(token_hash)
+            .fetch_optional(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        let result = query(REMOVE_INVITATION_BY_TOKEN)
+            .bind(token_hash)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        query(REMOVE_INVITATION)
+            .bind(organization_id)
+            .bind(email)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        query(INSERT_SIGNUP_INVITE)
+            .bind(&invite.token_hash)
+            .bind(&invite.email)
+            .bind(&invite.role)
+            .bind(invite.expires_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        let result = query_as(SELECT_SIGNUP_INVITE)
+            .bind(token_hash)
+            .fetch_optional(self)
+            .await?;
+        Ok(result)
+    }
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        let result = query(REMOVE_SIGNUP_INVITE)
+            .bind(token_hash)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        query(UPDATE_DISABLED_AT)
+            .bind(disabled_at)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        query(UPDATE_DELETED_AT)
+            .bind(deleted_at)
+            .bind(user_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        let result = query(PURGE_DELETED_USERS)
+            .bind(deleted_before)
+            .execute(self)
+            .await?;
+        Ok(result.rows_affected())
+    }
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        let result = query_as(SELECT_BY_USERNAME)
+            .bind(username)
+            .fetch_one(self)
+            .await?;
+        Ok(result)
+    }
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        let list = list::list_users(SELECT_USERS, filter, page, placeholder);
+        let users = bind_params!(query_as(&list.sql), &list.params)
+            .fetch_all(self)
+            .await?;
+        Ok(users)
+    }
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        let count = list::count_users(filter, placeholder);
+        let total: i64 = bind_params!(query_scalar(&count.sql), &count.params)
+            .fetch_one(self)
+            .await?;
+        Ok(total as u64)
+    }
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        query(INSERT_AUTH_EVENT)
+            .bind(entry.user_id)
+            .bind(entry.event.as_str())
+            .bind(&entry.ip)
+            .bind(&entry.user_agent)
+            .bind(entry.created_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        let result = query_as(SELECT_AUTH_EVENTS)
+            .bind(user_id)
+            .bind(before)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        query(INSERT_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(hash)
+            .bind(now())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        let result = query_scalar(SELECT_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(self)
+            .await?;
+        Ok(result)
+    }
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        query(PRUNE_PASSWORD_HISTORY)
+            .bind(user_id)
+            .bind(keep)
+            .execute(self)
+            .await?;
+        Ok(())
     }
 }
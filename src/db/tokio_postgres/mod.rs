@@ -1,22 +1,64 @@
+use crate::audit::AuditEntry;
+use crate::oauth::{OAuthClient, OAuthToken};
+use crate::invites::SignupInvite;
+use crate::organizations::{Invitation, Membership, Organization};
 use crate::prelude::*;
 mod sql;
+use super::list::{self, Param};
 use std::convert::{TryFrom, TryInto};
+use tokio_postgres::types::ToSql;
 use tokio_postgres::Client;
 #[rocket::async_trait]
 impl DBConnection for Client {
     async fn init(&self) -> Result<()> {
         self.execute(sql::CREATE_TABLE, &[]).await?;
+        for column in sql::ADD_USER_COLUMNS {
+            self.execute(column, &[]).await?;
+        }
+        self.execute(sql::CREATE_USERNAME_INDEX, &[]).await?;
+        for table in sql::CREATE_OAUTH_TABLES {
+            self.execute(table, &[]).await?;
+        }
+        for table in sql::CREATE_ROLE_TABLES {
+            self.execute(table, &[]).await?;
+        }
+        for table in sql::CREATE_ORGANIZATION_TABLES {
+            self.execute(table, &[]).await?;
+        }
+        for table in sql::CREATE_AUDIT_TABLES {
+            self.execute(table, &[]).await?;
+        }
+        for table in sql::CREATE_PASSWORD_HISTORY_TABLES {
+            self.execute(table, &[]).await?;
+        }
+        self.execute(sql::CREATE_SIGNUP_INVITES_TABLE, &[]).await?;
         Ok(())
     }
-    async fn create_user(&self, email: &str, hash: &str, is_admin: bool) -> Result<(), Error> {
-        self.execute(sql::INSERT_USER, &[&email, &hash, &is_admin])
+    async fn create_user(
+        &self,
+        email: &str,
+        hash: &str,
+        is_admin: bool,
+        username: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<(), Error> {
+        self.execute(sql::INSERT_USER, &[&email, &hash, &is_admin, &username, &now(), &profile])
             .await?;
         Ok(())
     }
     async fn update_user(&self, user: &User) -> Result<()> {
         self.execute(
             sql::UPDATE_USER,
-            &[&user.email, &user.password, &user.is_admin],
+            &[
+                &user.id,
+                &user.email,
+                &user.password,
+                &user.is_admin,
+                &user.profile,
+                &user.username,
+                &user.password_changed_at,
+                &user.must_change_password,
+            ],
         )
         .await?;
         Ok(())
@@ -38,6 +80,256 @@ impl DBConnection for Client {
         let user = self.query_one(sql::SELECT_BY_EMAIL, &[&email]).await?;
         user.try_into()
     }
+
+    async fn create_oauth_client(&self, client: &OAuthClient) -> Result<()> {
+        self.execute(
+            sql::INSERT_OAUTH_CLIENT,
+            &[
+                &client.client_id,
+                &client.name,
+                &client.secret_hash,
+                &client.redirect_uris,
+                &client.scopes,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+    async fn get_oauth_client(&self, client_id: &str) -> Result<OAuthClient> {
+        let client = self
+            .query_one(sql::SELECT_OAUTH_CLIENT, &[&client_id])
+            .await?;
+        client.try_into()
+    }
+    async fn delete_oauth_client(&self, client_id: &str) -> Result<()> {
+        self.execute(sql::REMOVE_OAUTH_CLIENT, &[&client_id])
+            .await?;
+        Ok(())
+    }
+    async fn set_oauth_consent(&self, user_id: i32, client_id: &str, scope: &str) -> Result<()> {
+        self.execute(sql::UPSERT_OAUTH_CONSENT, &[&user_id, &client_id, &scope])
+            .await?;
+        Ok(())
+    }
+    async fn get_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<Option<String>> {
+        let row = self
+            .query_opt(sql::SELECT_OAUTH_CONSENT, &[&user_id, &client_id])
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+    async fn delete_oauth_consent(&self, user_id: i32, client_id: &str) -> Result<()> {
+        self.execute(sql::REMOVE_OAUTH_CONSENT, &[&user_id, &client_id])
+            .await?;
+        Ok(())
+    }
+    async fn create_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        self.execute(
+            sql::INSERT_OAUTH_TOKEN,
+            &[
+                &token.token_hash,
+                &token.kind,
+                &token.client_id,
+                &token.user_id,
+                &token.scope,
+                &token.redirect_uri,
+                &token.code_challenge,
+                &token.code_challenge_method,
+                &token.expires_at,
+                &token.grant_hash,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+    async fn get_oauth_token(&self, token_hash: &str) -> Result<OAuthToken> {
+        let token = self
+            .query_one(sql::SELECT_OAUTH_TOKEN, &[&token_hash])
+            .await?;
+        token.try_into()
+    }
+    async fn delete_oauth_token(&self, token_hash: &str) -> Result<bool> {
+        let deleted = self
+            .execute(sql::REMOVE_OAUTH_TOKEN, &[&token_hash])
+            .await?;
+        Ok(deleted == 1)
+    }
+    async fn delete_oauth_grant(&self, grant_hash: &str) -> Result<()> {
+        self.execute(sql::REMOVE_OAUTH_GRANT, &[&grant_hash])
+            .await?;
+        Ok(())
+    }
+    async fn delete_user_oauth_tokens(&self, user_id: i32) -> Result<()> {
+        self.execute(sql::REMOVE_USER_OAUTH_TOKENS, &[&user_id]).await?;
+        Ok(())
+    }
+    async fn create_role(&self, role: &str) -> Result<()> {
+        self.execute(sql::INSERT_ROLE, &[&role]).await?;
+        Ok(())
+    }
+    async fn delete_role(&self, role: &str) -> Result<()> {
+        self.execute(sql::REMOVE_ROLE, &[&role]).await?;
+        Ok(())
+    }
+    async fn get_roles(&self) -> Result<Vec<String>> {
+        let rows = self.query(sql::SELECT_ROLES, &[]).await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+    async fn grant_permission(&self, role: &str, permission: &str) -> Result<()> {
+        self.execute(sql::INSERT_ROLE_PERMISSION, &[&role, &permission]).await?;
+        Ok(())
+    }
+    async fn revoke_permission(&self, role: &str, permission: &str) -> Result<()> {
+        self.execute(sql::REMOVE_ROLE_PERMISSION, &[&role, &permission]).await?;
+        Ok(())
+    }
+    async fn get_role_permissions(&self, role: &str) -> Result<Vec<String>> {
+        let rows = self.query(sql::SELECT_ROLE_PERMISSIONS, &[&role]).await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+    async fn assign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        self.execute(sql::INSERT_USER_ROLE, &[&user_id, &role]).await?;
+        Ok(())
+    }
+    async fn unassign_role(&self, user_id: i32, role: &str) -> Result<()> {
+        self.execute(sql::REMOVE_USER_ROLE, &[&user_id, &role]).await?;
+        Ok(())
+    }
+    async fn create_organization(&self, name: &str, slug: &str) -> Result<()> {
+        self.execute(sql::INSERT_ORGANIZATION, &[&name, &slug]).await?;
+        Ok(())
+    }
+    async fn get_organization_by_id(&self, organization_id: i32) -> Result<Organization> {
+        let row = self.query_one(sql::SELECT_ORGANIZATION_BY_ID, &[&organization_id]).await?;
+        row.try_into()
+    }
+    async fn get_organization_by_slug(&self, slug: &str) -> Result<Organization> {
+        let row = self.query_one(sql::SELECT_ORGANIZATION_BY_SLUG, &[&slug]).await?;
+        row.try_into()
+    }
+    async fn delete_organization(&self, organization_id: i32) -> Result<()> {
+        self.execute(sql::REMOVE_ORGANIZATION, &[&organization_id]).await?;
+        Ok(())
+    }
+    async fn set_membership(&self, membership: &Membership) -> Result<()> {
+        self.execute(sql::UPSERT_MEMBERSHIP, &[&membership.organization_id, &membership.user_id, &membership.role]).await?;
+        Ok(())
+    }
+    async fn get_membership(&self, organization_id: i32, user_id: i32) -> Result<Option<Membership>> {
+        let row = self.query_opt(sql::SELECT_MEMBERSHIP, &[&organization_id, &user_id]).await?;
+        row.map(TryInto::try_into).transpose()
+    }
+    async fn get_memberships_by_organization(&self, organization_id: i32) -> Result<Vec<Membership>> {
+        let rows = self.query(sql::SELECT_MEMBERSHIPS_BY_ORGANIZATION, &[&organization_id]).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn get_memberships_by_user(&self, user_id: i32) -> Result<Vec<Membership>> {
+        let rows = self.query(sql::SELECT_MEMBERSHIPS_BY_USER, &[&user_id]).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn delete_membership(&self, organization_id: i32, user_id: i32) -> Result<()> {
+        self.execute(sql::REMOVE_MEMBERSHIP, &[&organization_id, &user_id]).await?;
+        Ok(())
+    }
+    async fn set_invitation(&self, invitation: &Invitation) -> Result<()> {
+        self.execute(
+            sql::UPSERT_INVITATION,
+            &[
+                &invitation.organization_id,
+                &invitation.email,
+                &invitation.role,
+                &invitation.token_hash,
+                &invitation.expires_at,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+    async fn get_invitations(&self, email: &str) -> Result<Vec<Invitation>> {
+        let rows = self.query(sql::SELECT_INVITATIONS_BY_EMAIL, &[&email]).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>> {
+        let row = self.query_opt(sql::SELECT_INVITATION_BY_TOKEN, &[&token_hash]).await?;
+        row.map(TryInto::try_into).transpose()
+    }
+    async fn delete_invitation_by_token(&self, token_hash: &str) -> Result<bool> {
+        let deleted = self.execute(sql::REMOVE_INVITATION_BY_TOKEN, &[&token_hash]).await?;
+        Ok(deleted == 1)
+    }
+    async fn delete_invitation(&self, organization_id: i32, email: &str) -> Result<()> {
+        self.execute(sql::REMOVE_INVITATION, &[&organization_id, &email]).await?;
+        Ok(())
+    }
+    async fn create_signup_invite(&self, invite: &SignupInvite) -> Result<()> {
+        self.execute(sql::INSERT_SIGNUP_INVITE, &[&invite.token_hash, &invite.email, &invite.role, &invite.expires_at]).await?;
+        Ok(())
+    }
+    async fn get_signup_invite(&self, token_hash: &str) -> Result<Option<SignupInvite>> {
+        let row = self.query_opt(sql::SELECT_SIGNUP_INVITE, &[&token_hash]).await?;
+        row.map(TryInto::try_into).transpose()
+    }
+    async fn delete_signup_invite(&self, token_hash: &str) -> Result<bool> {
+        let deleted = self.execute(sql::REMOVE_SIGNUP_INVITE, &[&token_hash]).await?;
+        Ok(deleted == 1)
+    }
+    async fn set_user_disabled_at(&self, user_id: i32, disabled_at: Option<i64>) -> Result<()> {
+        self.execute(sql::UPDATE_DISABLED_AT, &[&disabled_at, &user_id]).await?;
+        Ok(())
+    }
+    async fn set_user_deleted_at(&self, user_id: i32, deleted_at: Option<i64>) -> Result<()> {
+        self.execute(sql::UPDATE_DELETED_AT, &[&deleted_at, &user_id]).await?;
+        Ok(())
+    }
+    async fn purge_deleted_users(&self, deleted_before: i64) -> Result<u64> {
+        let count = self.execute(sql::PURGE_DELETED_USERS, &[&deleted_before]).await?;
+        Ok(count)
+    }
+    async fn get_user_by_username(&self, username: &str) -> Result<User> {
+        let row = self.query_one(sql::SELECT_BY_USERNAME, &[&username]).await?;
+        row.try_into()
+    }
+    async fn list_users(&self, filter: &UserFilter, page: &Page) -> Result<Vec<User>> {
+        let list = list::list_users(sql::SELECT_USERS, filter, page, sql::placeholder);
+        let rows = self.query(&list.sql, &to_sql(&list.params)).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64> {
+        let count = list::count_users(filter, sql::placeholder);
+        let row = self.query_one(&count.sql, &to_sql(&count.params)).await?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+    async fn create_auth_event(&self, entry: &AuditEntry) -> Result<()> {
+        self.execute(sql::INSERT_AUTH_EVENT, &[&entry.user_id, &entry.event.as_str(), &entry.ip, &entry.user_agent, &entry.created_at]).await?;
+        Ok(())
+    }
+    async fn get_auth_events(&self, user_id: i32, before: i32, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+        let rows = self.query(sql::SELECT_AUTH_EVENTS, &[&user_id, &before, &limit, &offset]).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn add_password_history(&self, user_id: i32, hash: &str) -> Result<()> {
+        self.execute(sql::INSERT_PASSWORD_HISTORY, &[&user_id, &hash, &now()]).await?;
+        Ok(())
+    }
+    async fn get_password_history(&self, user_id: i32, limit: i64) -> Result<Vec<String>> {
+        let rows = self.query(sql::SELECT_PASSWORD_HISTORY, &[&user_id, &limit]).await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+    async fn prune_password_history(&self, user_id: i32, keep: i64) -> Result<()> {
+        self.execute(sql::PRUNE_PASSWORD_HISTORY, &[&user_id, &keep]).await?;
+        Ok(())
+    }
+}
+
+fn to_sql(params: &[Param]) -> Vec<&(dyn ToSql + Sync)> {
+    params
+        .iter()
+        .map(|param| match param {
+            Param::Text(text) => text as &(dyn ToSql + Sync),
+            Param::Int(int) => int,
+            Param::BigInt(int) => int,
+            Param::Bool(boolean) => boolean,
+        })
+        .collect()
 }
 
 impl TryFrom<tokio_postgres::Row> for User {
@@ -48,6 +340,107 @@ impl TryFrom<tokio_postgres::Row> for User {
             email: row.get(1),
             password: row.get(2),
             is_admin: row.get(3),
+            disabled_at: row.get("disabled_at"),
+            deleted_at: row.get("deleted_at"),
+            password_changed_at: row.get("password_changed_at"),
+            must_change_password: row.get("must_change_password"),
+            profile: row.get("profile"),
+            username: row.get("username"),
+            created_at: row.get("created_at"),
+            roles: row.try_get::<_, Option<String>>("roles").ok().flatten().into(),
+            permissions: row.try_get::<_, Option<String>>("permissions").ok().flatten().into(),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for OAuthClient {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<OAuthClient> {
+        Ok(OAuthClient {
+            client_id: row.get("client_id"),
+            name: row.get("name"),
+            secret_hash: row.get("secret_hash"),
+            redirect_uris: row.get("redirect_uris"),
+            scopes: row.get("scopes"),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for OAuthToken {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<OAuthToken> {
+        Ok(OAuthToken {
+            token_hash: row.get("token_hash"),
+            kind: row.get("kind"),
+            client_id: row.get("client_id"),
+            user_id: row.get("user_id"),
+            scope: row.get("scope"),
+            redirect_uri: row.get("redirect_uri"),
+            code_challenge: row.get("code_challenge"),
+            code_challenge_method: row.get("code_challenge_method"),
+            expires_at: row.get("expires_at"),
+            grant_hash: row.get("grant_hash"),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for Organization {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<Organization> {
+        Ok(Organization {
+            id: row.get("id"),
+            name: row.get("name"),
+            slug: row.get("slug"),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for Membership {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<Membership> {
+        Ok(Membership {
+            organization_id: row.get("organization_id"),
+            user_id: row.get("user_id"),
+            role: row.get("role"),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for Invitation {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<Invitation> {
+        Ok(Invitation {
+            organization_id: row.get("organization_id"),
+            email: row.get("email"),
+            role: row.get("role"),
+            token_hash: row.get("token_hash"),
+            expires_at: row.get("expires_at"),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for SignupInvite {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<SignupInvite> {
+        Ok(SignupInvite {
+            token_hash: row.get("token_hash"),
+            email: row.get("email"),
+            role: row.get("role"),
+            expires_at: row.get("expires_at"),
+        })
+    }
+}
+
+impl TryFrom<tokio_postgres::Row> for AuditEntry {
+    type Error = Error;
+    fn try_from(row: tokio_postgres::Row) -> Result<AuditEntry> {
+        Ok(AuditEntry {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            event: row.get("event"),
+            ip: row.get("ip"),
+            user_agent: row.get("user_agent"),
+            created_at: row.get("created_at"),
         })
     }
 }
@@ -1,32 +1,57 @@
+/// Selects users along with their comma separated roles and permissions.
+macro_rules! select_user {
+    ($condition:literal) => {
+        concat!(
+            "
+SELECT users.*,
+    (SELECT string_agg(roles.name, ',') FROM user_roles
+        JOIN roles ON roles.id = user_roles.role_id
+        WHERE user_roles.user_id = users.id) AS roles,
+    (SELECT string_agg(DISTINCT role_permissions.permission, ',') FROM user_roles
+        JOIN role_permissions ON role_permissions.role_id = user_roles.role_id
+        WHERE user_roles.user_id = users.id) AS permissions
+FROM users WHERE ",
+            $condition
+        )
+    };
+}
+
 pub(crate) const CREATE_TABLE: &str = "
 CREATE TABLE IF NOT EXISTS users (
     id SERIAL PRIMARY KEY,
     email VARCHAR (254) UNIQUE NOT NULL,
 	password VARCHAR ( 255 ) NOT NULL,
-    is_admin BOOL DEFAULT FALSE
+    is_admin BOOL DEFAULT FALSE,
+    disabled_at BIGINT,
+    deleted_at BIGINT,
+    profile TEXT,
+    username VARCHAR (64),
+    created_at BIGINT NOT NULL DEFAULT 0,
+    password_changed_at BIGINT,
+    must_change_password BOOL NOT NULL DEFAULT FALSE
 );
 ";
 
 pub(crate) const INSERT_USER: &str = "
-INSERT INTO users (email, password, is_admin) VALUES ($1, $2, $3);
+INSERT INTO users (email, password, is_admin, username, created_at, password_changed_at, profile) VALUES ($1, $2, $3, $4, $5, $5, $6);
 ";
 
 pub(crate) const UPDATE_USER: &str = "
-UPDATE table SET 
+UPDATE users SET
     email = $2,
     password = $3,
     is_admin = $4,
+    profile = $5,
+    username = $6,
+    password_changed_at = $7,
+    must_change_password = $8
 WHERE
     id = $1
 ";
 
-pub(crate) const SELECT_BY_ID: &str = "
-SELECT * FROM users WHERE id = $1;
-";
+pub(crate) const SELECT_BY_ID: &str = select_user!("id = $1;");
 
-pub(crate) const SELECT_BY_EMAIL: &str = "
-SELECT * FROM users WHERE email = $1;
-";
+pub(crate) const SELECT_BY_EMAIL: &str = select_user!("email = $1;");
 
 pub(crate) const REMOVE_BY_ID: &str = "
 DELETE FROM users WHERE id =$1;
@@ -34,3 +59,334 @@ DELETE FROM users WHERE id =$1;
 pub(crate) const REMOVE_BY_EMAIL: &str = "
 DELETE FROM users WHERE email =$1;
 ";
+
+/// Adds the columns added to `users` after its first version to tables created before.
+pub(crate) const ADD_USER_COLUMNS: [&str; 7] = [
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled_at BIGINT;",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS deleted_at BIGINT;",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS profile TEXT;",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS username VARCHAR (64);",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS created_at BIGINT NOT NULL DEFAULT 0;",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS password_changed_at BIGINT;",
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS must_change_password BOOL NOT NULL DEFAULT FALSE;",
+];
+
+pub(crate) const CREATE_OAUTH_TABLES: [&str; 3] = [
+    "
+CREATE TABLE IF NOT EXISTS oauth_clients (
+    client_id VARCHAR (64) PRIMARY KEY,
+    name VARCHAR (255) NOT NULL,
+    secret_hash VARCHAR (64),
+    redirect_uris TEXT NOT NULL,
+    scopes TEXT NOT NULL
+);",
+    "
+CREATE TABLE IF NOT EXISTS oauth_consents (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    client_id VARCHAR (64) NOT NULL REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    scope TEXT NOT NULL,
+    PRIMARY KEY (user_id, client_id)
+);",
+    "
+CREATE TABLE IF NOT EXISTS oauth_tokens (
+    token_hash VARCHAR (64) PRIMARY KEY,
+    kind VARCHAR (16) NOT NULL,
+    client_id VARCHAR (64) NOT NULL REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    scope TEXT NOT NULL,
+    redirect_uri TEXT,
+    code_challenge VARCHAR (128),
+    code_challenge_method VARCHAR (8),
+    expires_at BIGINT NOT NULL,
+    grant_hash VARCHAR (64)
+);",
+];
+
+pub(crate) const INSERT_OAUTH_CLIENT: &str = "
+INSERT INTO oauth_clients (client_id, name, secret_hash, redirect_uris, scopes) VALUES ($1, $2, $3, $4, $5);
+";
+
+pub(crate) const SELECT_OAUTH_CLIENT: &str = "
+SELECT * FROM oauth_clients WHERE client_id = $1;
+";
+
+pub(crate) const REMOVE_OAUTH_CLIENT: &str = "
+DELETE FROM oauth_clients WHERE client_id = $1;
+";
+
+pub(crate) const UPSERT_OAUTH_CONSENT: &str = "
+INSERT INTO oauth_consents (user_id, client_id, scope) VALUES ($1, $2, $3)
+ON CONFLICT (user_id, client_id) DO UPDATE SET scope = excluded.scope;
+";
+
+pub(crate) const SELECT_OAUTH_CONSENT: &str = "
+SELECT scope FROM oauth_consents WHERE user_id = $1 AND client_id = $2;
+";
+
+pub(crate) const REMOVE_OAUTH_CONSENT: &str = "
+DELETE FROM oauth_consents WHERE user_id = $1 AND client_id = $2;
+";
+
+pub(crate) const INSERT_OAUTH_TOKEN: &str = "
+INSERT INTO oauth_tokens (
+    token_hash, kind, client_id, user_id, scope,
+    redirect_uri, code_challenge, code_challenge_method, expires_at, grant_hash
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+";
+
+pub(crate) const SELECT_OAUTH_TOKEN: &str = "
+SELECT * FROM oauth_tokens WHERE token_hash = $1;
+";
+
+pub(crate) const REMOVE_OAUTH_TOKEN: &str = "
+DELETE FROM oauth_tokens WHERE token_hash = $1;
+";
+
+pub(crate) const REMOVE_OAUTH_GRANT: &str = "
+DELETE FROM oauth_tokens WHERE grant_hash = $1;
+";
+
+pub(crate) const REMOVE_USER_OAUTH_TOKENS: &str = "
+DELETE FROM oauth_tokens WHERE user_id = $1;
+";
+
+pub(crate) const CREATE_ROLE_TABLES: [&str; 3] = [
+    "
+CREATE TABLE IF NOT EXISTS roles (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR (255) UNIQUE NOT NULL
+);",
+    "
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, role_id)
+);",
+    "
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    permission VARCHAR (255) NOT NULL,
+    PRIMARY KEY (role_id, permission)
+);",
+];
+
+pub(crate) const INSERT_ROLE: &str = "
+INSERT INTO roles (name) VALUES ($1);
+";
+
+pub(crate) const REMOVE_ROLE: &str = "
+DELETE FROM roles WHERE name = $1;
+";
+
+pub(crate) const SELECT_ROLES: &str = "
+SELECT name FROM roles ORDER BY name;
+";
+
+pub(crate) const INSERT_USER_ROLE: &str = "
+INSERT INTO user_roles (user_id, role_id)
+SELECT $1, id FROM roles WHERE name = $2
+ON CONFLICT DO NOTHING;
+";
+
+pub(crate) const REMOVE_USER_ROLE: &str = "
+DELETE FROM user_roles
+WHERE user_id = $1 AND role_id IN (SELECT id FROM roles WHERE name = $2);
+";
+
+pub(crate) const INSERT_ROLE_PERMISSION: &str = "
+INSERT INTO role_permissions (role_id, permission)
+SELECT id, $2 FROM roles WHERE name = $1
+ON CONFLICT DO NOTHING;
+";
+
+pub(crate) const REMOVE_ROLE_PERMISSION: &str = "
+DELETE FROM role_permissions
+WHERE permission = $2 AND role_id IN (SELECT id FROM roles WHERE name = $1);
+";
+
+pub(crate) const SELECT_ROLE_PERMISSIONS: &str = "
+SELECT permission FROM role_permissions
+JOIN roles ON roles.id = role_permissions.role_id
+WHERE roles.name = $1
+ORDER BY permission;
+";
+
+pub(crate) const CREATE_ORGANIZATION_TABLES: [&str; 3] = [
+    "
+CREATE TABLE IF NOT EXISTS organizations (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR (255) NOT NULL,
+    slug VARCHAR (64) UNIQUE NOT NULL
+);",
+    "
+CREATE TABLE IF NOT EXISTS memberships (
+    organization_id INTEGER NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR (64) NOT NULL,
+    PRIMARY KEY (organization_id, user_id)
+);",
+    "
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    organization_id INTEGER NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    email VARCHAR (254) NOT NULL,
+    role VARCHAR (64) NOT NULL,
+    token_hash VARCHAR (64) UNIQUE NOT NULL,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY (organization_id, email)
+);",
+];
+
+pub(crate) const INSERT_ORGANIZATION: &str = "
+INSERT INTO organizations (name, slug) VALUES ($1, $2);
+";
+
+pub(crate) const SELECT_ORGANIZATION_BY_ID: &str = "
+SELECT * FROM organizations WHERE id = $1;
+";
+
+pub(crate) const SELECT_ORGANIZATION_BY_SLUG: &str = "
+SELECT * FROM organizations WHERE slug = $1;
+";
+
+pub(crate) const REMOVE_ORGANIZATION: &str = "
+DELETE FROM organizations WHERE id = $1;
+";
+
+pub(crate) const UPSERT_MEMBERSHIP: &str = "
+INSERT INTO memberships (organization_id, user_id, role) VALUES ($1, $2, $3)
+ON CONFLICT (organization_id, user_id) DO UPDATE SET role = excluded.role;
+";
+
+pub(crate) const SELECT_MEMBERSHIP: &str = "
+SELECT * FROM memberships WHERE organization_id = $1 AND user_id = $2;
+";
+
+pub(crate) const SELECT_MEMBERSHIPS_BY_ORGANIZATION: &str = "
+SELECT * FROM memberships WHERE organization_id = $1 ORDER BY user_id;
+";
+
+pub(crate) const SELECT_MEMBERSHIPS_BY_USER: &str = "
+SELECT * FROM memberships WHERE user_id = $1 ORDER BY organization_id;
+";
+
+pub(crate) const REMOVE_MEMBERSHIP: &str = "
+DELETE FROM memberships WHERE organization_id = $1 AND user_id = $2;
+";
+
+pub(crate) const UPSERT_INVITATION: &str = "
+INSERT INTO organization_invitations (organization_id, email, role, token_hash, expires_at)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (organization_id, email)
+DO UPDATE SET role = excluded.role, token_hash = excluded.token_hash, expires_at = excluded.expires_at;
+";
+
+pub(crate) const SELECT_INVITATIONS_BY_EMAIL: &str = "
+SELECT * FROM organization_invitations WHERE email = $1 ORDER BY organization_id;
+";
+
+pub(crate) const SELECT_INVITATION_BY_TOKEN: &str = "
+SELECT * FROM organization_invitations WHERE token_hash = $1;
+";
+
+pub(crate) const REMOVE_INVITATION_BY_TOKEN: &str = "
+DELETE FROM organization_invitations WHERE token_hash = $1;
+";
+
+pub(crate) const REMOVE_INVITATION: &str = "
+DELETE FROM organization_invitations WHERE organization_id = $1 AND email = $2;
+";
+
+pub(crate) const CREATE_SIGNUP_INVITES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS signup_invites (
+    token_hash VARCHAR (64) PRIMARY KEY,
+    email VARCHAR (254) NOT NULL,
+    role VARCHAR (255),
+    expires_at BIGINT NOT NULL
+);
+";
+
+pub(crate) const INSERT_SIGNUP_INVITE: &str = "
+INSERT INTO signup_invites (token_hash, email, role, expires_at) VALUES ($1, $2, $3, $4);
+";
+
+pub(crate) const SELECT_SIGNUP_INVITE: &str = "
+SELECT * FROM signup_invites WHERE token_hash = $1;
+";
+
+pub(crate) const REMOVE_SIGNUP_INVITE: &str = "
+DELETE FROM signup_invites WHERE token_hash = $1;
+";
+
+pub(crate) const UPDATE_DISABLED_AT: &str = "
+UPDATE users SET disabled_at = $1 WHERE id = $2;
+";
+
+pub(crate) const UPDATE_DELETED_AT: &str = "
+UPDATE users SET deleted_at = $1 WHERE id = $2;
+";
+
+pub(crate) const PURGE_DELETED_USERS: &str = "
+DELETE FROM users WHERE deleted_at IS NOT NULL AND deleted_at <= $1;
+";
+
+pub(crate) const CREATE_USERNAME_INDEX: &str = "
+CREATE UNIQUE INDEX IF NOT EXISTS users_username_key ON users (lower(username));
+";
+
+pub(crate) const SELECT_BY_USERNAME: &str = select_user!("lower(username) = lower($1);");
+
+pub(crate) const CREATE_AUDIT_TABLES: [&str; 2] = [
+    "
+CREATE TABLE IF NOT EXISTS auth_events (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER,
+    event VARCHAR (32) NOT NULL,
+    ip VARCHAR (45),
+    user_agent TEXT,
+    created_at BIGINT NOT NULL
+);",
+    "
+CREATE INDEX IF NOT EXISTS auth_events_user_id ON auth_events (user_id);",
+];
+
+pub(crate) const INSERT_AUTH_EVENT: &str = "
+INSERT INTO auth_events (user_id, event, ip, user_agent, created_at) VALUES ($1, $2, $3, $4, $5);
+";
+
+pub(crate) const SELECT_AUTH_EVENTS: &str = "
+SELECT * FROM auth_events WHERE user_id = $1 AND id < $2 ORDER BY id DESC LIMIT $3 OFFSET $4;
+";
+
+pub(crate) const CREATE_PASSWORD_HISTORY_TABLES: [&str; 2] = [
+    "
+CREATE TABLE IF NOT EXISTS password_history (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    password VARCHAR (255) NOT NULL,
+    created_at BIGINT NOT NULL
+);",
+    "
+CREATE INDEX IF NOT EXISTS password_history_user_id ON password_history (user_id);",
+];
+
+pub(crate) const INSERT_PASSWORD_HISTORY: &str = "
+INSERT INTO password_history (user_id, password, created_at) VALUES ($1, $2, $3);
+";
+
+pub(crate) const SELECT_PASSWORD_HISTORY: &str = "
+SELECT password FROM password_history WHERE user_id = $1 ORDER BY id DESC LIMIT $2;
+";
+
+/// Deletes every entry older than the newest `$2` ones.
+pub(crate) const PRUNE_PASSWORD_HISTORY: &str = "
+DELETE FROM password_history WHERE user_id = $1 AND id <= (
+    SELECT id FROM password_history WHERE user_id = $1 ORDER BY id DESC LIMIT 1 OFFSET $2
+);
+";
+
+/// Matches every user, so the filters of [`Users::list`](crate::Users::list) can be appended to it.
+pub(crate) const SELECT_USERS: &str = select_user!("1 = 1");
+
+pub(crate) fn placeholder(n: usize) -> String {
+    format!("${}", n)
+}
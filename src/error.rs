@@ -7,6 +7,63 @@ pub enum Error {
     #[error("That is not a valid email address.")]
     InvalidEmailAddressError,
 
+    /// This error occurs when registering an OAuth2 client with a redirect uri that is not an absolute uri.
+    #[error("That is not a valid redirect uri.")]
+    InvalidRedirectUriError,
+
+    /// This error occurs when configuring [`ProxyAuth`](crate::ProxyAuth) with an invalid ip address or range.
+    #[error("That is not a valid ip range: {0}")]
+    InvalidIpRangeError(String),
+
+    /// This error occurs when creating a role or granting a permission with an empty name or a name containing commas.
+    #[error("Role and permission names must not be empty nor contain commas.")]
+    InvalidRoleNameError,
+
+    /// Thrown when the requested role does not exist.
+    #[error("The role \"{0}\" does not exist.")]
+    RoleNotFoundError(String),
+
+    /// This error occurs when creating an organization with a slug that is empty or not url safe.
+    #[error("Organization slugs may only contain letters, digits, dashes and underscores.")]
+    InvalidSlugError,
+
+    /// Thrown when the requested organization does not exist.
+    #[error("Could not find the requested organization.")]
+    OrganizationNotFoundError,
+
+    /// This error occurs when the user is not a member of the organization.
+    #[error("You are not a member of this organization.")]
+    NotAMemberError,
+
+    /// Thrown when accepting an invitation that does not exist.
+    #[error("Could not find an invitation to this organization.")]
+    InvitationNotFoundError,
+
+    /// This error occurs when signing up while open signup is disabled with [`Users::set_open_signup`](crate::Users::set_open_signup).
+    #[error("Signup is disabled. You need an invitation to create an account.")]
+    SignupDisabled,
+
+    /// This error occurs when signing up with an invitation that doesn't exist, has expired, or was sent to another email.
+    #[error("This invitation is invalid or has expired.")]
+    InvalidInviteError,
+
+    /// This error occurs when a disabled or deleted user tries to log in.
+    #[error("This account has been disabled.")]
+    AccountDisabledError,
+
+    /// This error occurs when an admin tries to disable, delete or demote their own account through the admin routes.
+    #[error("You can't disable, delete or demote your own account.")]
+    SelfModificationError,
+
+    /// This error occurs when a user guard rejects the session of a user who has to change their password.
+    /// See [`LoginStatus::PasswordChangeRequired`](crate::LoginStatus::PasswordChangeRequired).
+    #[error("You have to change your password before continuing.")]
+    PasswordChangeRequired,
+
+    /// This error is thrown when a user tries to sign up with a username that is already taken.
+    #[error("That username is already taken.")]
+    UsernameAlreadyExists,
+
     /// This error only occurs if the application panics while holding a locked mutex.
     #[cfg(feature = "sqlx-sqlite")]
     #[error("The mutex guarding the Sqlite connection was poisoned.")]
@@ -45,6 +102,27 @@ pub enum Error {
     #[cfg(any(feature = "sqlx"))]
     #[error("SqlxError: {0}")]
     SqlxError(#[from] sqlx::Error),
+    /// This error occurs when verifying a password hash produced by an unknown algorithm.
+    #[error("The password hash uses an unsupported algorithm.")]
+    UnsupportedHashError,
+
+    /// This error occurs when verifying a peppered hash whose pepper version was not added to [`Users`](crate::Users).
+    #[error("The password hash uses pepper version {0}, which is not configured.")]
+    UnknownPepperError(u32),
+
+    /// This error occurs when deriving a pepper from a Rocket configuration without a secret key.
+    #[error("The Rocket configuration has no secret key.")]
+    MissingSecretKeyError,
+
+    /// This error occurs when bcrypt or scrypt fail to hash or parse a password hash.
+    #[error("HashingError: {0}")]
+    HashingError(String),
+
+    /// This error occurs when loading a file that was not built with
+    /// [`BreachedPasswords::build_bloom_filter`](crate::BreachedPasswords::build_bloom_filter).
+    #[error("The file is not a valid breached password Bloom filter.")]
+    InvalidBloomFilterError,
+
     /// A wrapper around [`argon2::Error`].
     #[error("Argon2ParsingError: {0}")]
     Argon2ParsingError(#[from] argon2::Error),
@@ -59,15 +137,28 @@ pub enum Error {
     #[error("RedisError")]
     RedisError(#[from] redis::RedisError),
 
+    /// This error occurs when a [`Mailer`](crate::mail::Mailer) fails to deliver a message.
+    #[error("MailError: {0}")]
+    MailError(String),
+
+    /// A wrapper around [`lettre::transport::smtp::Error`].
+    #[cfg(feature = "smtp")]
+    #[error("SmtpError: {0}")]
+    SmtpError(#[from] lettre::transport::smtp::Error),
+
     /// A wrapper around [`serde_json::Error`].
     #[error("SerdeError: {0}")]
     SerdeError(#[from] serde_json::Error),
 
     /// A wrapper around [`std::io::Error`].
-    #[cfg(feature = "sqlx-postgres")]
     #[error("IOError: {0}")]
     IOError(#[from] std::io::Error),
 
+    /// A wrapper around [`ldap3::LdapError`].
+    #[cfg(feature = "ldap")]
+    #[error("LdapError: {0}")]
+    LdapError(#[from] ldap3::LdapError),
+
     /// A wrapper around [`tokio_postgres::Error`].
     #[cfg(feature = "tokio-postgres")]
     #[error("TokioPostgresError: {0}")]
@@ -89,6 +180,19 @@ impl Error {
     fn message(&self) -> String {
         match self {
             InvalidEmailAddressError
+            | InvalidRedirectUriError
+            | InvalidRoleNameError
+            | RoleNotFoundError(_)
+            | InvalidSlugError
+            | OrganizationNotFoundError
+            | NotAMemberError
+            | InvitationNotFoundError
+            | SignupDisabled
+            | InvalidInviteError
+            | AccountDisabledError
+            | SelfModificationError
+            | PasswordChangeRequired
+            | UsernameAlreadyExists
             | EmailAlreadyExists
             | UnauthorizedError
             | UserNotFoundError => format!("{}", self),
@@ -100,7 +204,7 @@ impl Error {
                     .map(IntoIterator::into_iter)
                     .map(|errs| {
                         errs //
-                            .map(|err| &err.code)
+                            .map(|err| err.message.as_ref().unwrap_or(&err.code))
                             .fold(String::new(), |a, b| a + b)
                     })
                     .fold(String::new(), |a, b| a + &b)
@@ -111,6 +215,51 @@ impl Error {
             _ => "undefined".into(),
         }
     }
+
+    /// Maps the violation of a unique constraint of the users table, as when two users sign up
+    /// with the same email or username at the same time, to [`EmailAlreadyExists`] or [`UsernameAlreadyExists`].
+    pub(crate) fn into_user_conflict(self) -> Error {
+        match self.unique_violation() {
+            Some(constraint) if constraint.contains("username") => UsernameAlreadyExists,
+            Some(_) => EmailAlreadyExists,
+            None => self,
+        }
+    }
+
+    /// Returns the name of the violated constraint, or the message naming it, if the error is a unique violation.
+    fn unique_violation(&self) -> Option<&str> {
+        match self {
+            #[cfg(feature = "sqlx")]
+            SqlxError(sqlx::Error::Database(error)) => {
+                // postgres reports `23505` and mysql `23000`, while sqlite reports the
+                // extended codes of unique and primary key violations.
+                let code = error.code()?;
+                if !matches!(&*code, "23505" | "23000" | "2067" | "1555") {
+                    return None;
+                }
+                // mysql names the key after the duplicate value, which may contain anything.
+                let message = error.constraint().unwrap_or_else(|| error.message());
+                message.rsplit(" for key ").next()
+            }
+            #[cfg(feature = "rusqlite")]
+            RusqliteError(rusqlite::Error::SqliteFailure(error, message))
+                if matches!(
+                    error.extended_code,
+                    rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+                        | rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+                ) =>
+            {
+                message.as_deref()
+            }
+            #[cfg(feature = "tokio-postgres")]
+            TokioPostgresError(error)
+                if error.code() == Some(&tokio_postgres::error::SqlState::UNIQUE_VIOLATION) =>
+            {
+                error.as_db_error()?.constraint()
+            }
+            _ => None,
+        }
+    }
 }
 
 use rocket::http::ContentType;
@@ -121,11 +270,15 @@ use std::io::Cursor;
 
 impl<'r> Responder<'r, 'static> for Error {
     fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
-        let payload = to_string(&json!({
+        let mut payload = json!({
             "status": "error",
             "message": self.message(),
-        }))
-        .unwrap();
+        });
+        // the params of form errors, such as the score of a password that is too weak.
+        if let FormValidationErrors(errors) = &self {
+            payload["errors"] = to_value(errors).unwrap();
+        }
+        let payload = to_string(&payload).unwrap();
         Response::build()
             .sized_body(payload.len(), Cursor::new(payload))
             .header(ContentType::new("application", "json"))
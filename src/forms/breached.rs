@@ -0,0 +1,298 @@
+use crate::prelude::*;
+use sha1::{Digest, Sha1};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 8] = b"RABLOOM\x01";
+
+/// Passwords known to have appeared in data breaches, such as the ones in the
+/// [Pwned Passwords](https://haveibeenpwned.com/Passwords) dataset. They are checked locally, without network access,
+/// against either:
+/// * A directory of k-anonymity range files, as downloaded by the
+///   [PwnedPasswordsDownloader](https://github.com/HaveIBeenPwned/PwnedPasswordsDownloader).
+///   Each file is named after the first 5 characters of the SHA-1 hashes in it, like `21BD1.txt`,
+///   and has a `SUFFIX:COUNT` line for each hash. Only the file of the password being checked is read.
+/// * A Bloom filter built with [`BreachedPasswords::build_bloom_filter`], which is kept in memory.
+///   It is faster, but a small fraction of the passwords that were never breached are rejected as well.
+///
+/// Once loaded with [`Users::load_breached_passwords`], breached passwords are rejected when signing up
+/// or changing passwords, with a validation error for the `password` field whose code is [`BreachedPasswords::ERROR_CODE`]
+/// and whose message is [`BreachedPasswords::ERROR_MESSAGE`].
+/// ```rust,no_run
+/// # use rocket_auth::{Users, Error};
+/// # fn func(mut users: Users) -> Result<(), Error> {
+/// users.load_breached_passwords("/var/lib/pwned-passwords")?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct BreachedPasswords {
+    source: Source,
+}
+
+#[derive(Clone)]
+enum Source {
+    Ranges(PathBuf),
+    Bloom(Arc<BloomFilter>),
+}
+
+impl Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            Source::Ranges(dir) => write!(f, "BreachedPasswords {{ ranges: {:?} }}", dir),
+            Source::Bloom(filter) => write!(f, "BreachedPasswords {{ bloom_filter_bits: {} }}", filter.len),
+        }
+    }
+}
+
+impl BreachedPasswords {
+    /// The code of the validation error of breached passwords.
+    pub const ERROR_CODE: &'static str = "breached_password";
+    /// The message of the validation error of breached passwords, which is shown to the client.
+    pub const ERROR_MESSAGE: &'static str =
+        "This password has appeared in a data breach, so it can't be used. Please choose a different one.\n";
+
+    /// Loads a directory of range files, or a Bloom filter file.
+    #[throws(Error)]
+    pub fn load(path: impl AsRef<Path>) -> BreachedPasswords {
+        let path = path.as_ref();
+        if path.is_dir() {
+            BreachedPasswords::ranges(path)
+        } else {
+            BreachedPasswords::bloom_filter(path)?
+        }
+    }
+
+    /// Uses a directory of range files. They are read as passwords are checked, so the directory must stay in place.
+    pub fn ranges(dir: impl Into<PathBuf>) -> BreachedPasswords {
+        BreachedPasswords {
+            source: Source::Ranges(dir.into()),
+        }
+    }
+
+    /// Reads a Bloom filter built with [`BreachedPasswords::build_bloom_filter`] into memory.
+    #[throws(Error)]
+    pub fn bloom_filter(path: impl AsRef<Path>) -> BreachedPasswords {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        BreachedPasswords {
+            source: Source::Bloom(Arc::new(BloomFilter::read(BufReader::new(file), size)?)),
+        }
+    }
+
+    /// Builds a Bloom filter from the SHA-1 hashes of breached passwords, and writes it to `output`.
+    /// The hashes are read from either a directory of range files or a single file with a `HASH:COUNT` line for each hash.
+    /// With a `false_positive_rate` of 0.001, the filter takes about 1.8 bytes per hash.
+    /// ```rust,no_run
+    /// # use rocket_auth::{BreachedPasswords, Error};
+    /// # fn func() -> Result<(), Error> {
+    /// BreachedPasswords::build_bloom_filter("pwnedpasswords.txt", "pwnedpasswords.bloom", 0.001)?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub fn build_bloom_filter(hashes: impl AsRef<Path>, output: impl AsRef<Path>, false_positive_rate: f64) {
+        let hashes = hashes.as_ref();
+        let mut count = 0;
+        for_each_hash(hashes, |_| count += 1)?;
+        let mut filter = BloomFilter::new(count, false_positive_rate);
+        for_each_hash(hashes, |hash| filter.insert(&hash))?;
+        let mut output = BufWriter::new(File::create(output)?);
+        filter.write(&mut output)?;
+        output.flush()?;
+    }
+
+    /// Checks whether a password has been breached. Range files are read from disk, so it blocks the current thread.
+    /// ```rust
+    /// # use rocket_auth::{BreachedPasswords, Error};
+    /// # fn func() -> Result<(), Error> {
+    /// let breached = BreachedPasswords::ranges("/var/lib/pwned-passwords");
+    /// assert!(breached.contains("password123")?);
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub fn contains(&self, password: &str) -> bool {
+        let hash: [u8; 20] = Sha1::digest(password.as_bytes()).into();
+        match &self.source {
+            Source::Bloom(filter) => filter.contains(&hash),
+            Source::Ranges(dir) => in_range_file(dir, &hash)?,
+        }
+    }
+}
+
+/// Looks for the hash in its range file. Padding lines, with a count of 0, don't count as breached.
+#[throws(Error)]
+fn in_range_file(dir: &Path, hash: &[u8; 20]) -> bool {
+    let hex = to_hex(hash);
+    let (prefix, suffix) = hex.split_at(5);
+    let mut path = dir.join(format!("{}.txt", prefix));
+    if !path.exists() {
+        path = dir.join(prefix);
+    }
+    let ranges = match fs::read_to_string(path) {
+        Ok(ranges) => ranges,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return false,
+        Err(error) => throw!(error),
+    };
+    ranges.lines().any(|line| match line.trim().split_once(':') {
+        Some((found, count)) => found.eq_ignore_ascii_case(suffix) && count.trim() != "0",
+        None => line.trim().eq_ignore_ascii_case(suffix),
+    })
+}
+
+/// Calls `function` with every hash in a range directory or a hash file.
+#[throws(Error)]
+fn for_each_hash(path: &Path, mut function: impl FnMut([u8; 20])) {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            let prefix = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            if prefix.len() != 5 {
+                continue;
+            }
+            let prefix = prefix.to_string();
+            for line in BufReader::new(File::open(&path)?).lines() {
+                if let Some(hash) = parse_line(&prefix, &line?) {
+                    function(hash);
+                }
+            }
+        }
+    } else {
+        for line in BufReader::new(File::open(path)?).lines() {
+            if let Some(hash) = parse_line("", &line?) {
+                function(hash);
+            }
+        }
+    }
+}
+
+fn parse_line(prefix: &str, line: &str) -> Option<[u8; 20]> {
+    let (hex, count) = line.trim().split_once(':').unwrap_or((line.trim(), ""));
+    if count.trim() == "0" {
+        return None;
+    }
+    from_hex(&format!("{}{}", prefix, hex))
+}
+
+fn to_hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// A Bloom filter over SHA-1 hashes. Since the hashes are already uniformly distributed,
+/// its bit positions are derived from the hashes themselves with double hashing.
+struct BloomFilter {
+    hashes: u32,
+    len: u64,
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new(count: u64, false_positive_rate: f64) -> BloomFilter {
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let count = count.max(1) as f64;
+        let len = (-count * rate.ln() / (2f64.ln() * 2f64.ln())).ceil().max(64.0) as u64;
+        let hashes = (len as f64 / count * 2f64.ln()).round().max(1.0) as u32;
+        BloomFilter {
+            hashes,
+            len,
+            bits: vec![0; len.div_ceil(64) as usize],
+        }
+    }
+
+    fn positions<'a>(&'a self, hash: &[u8; 20]) -> impl Iterator<Item = u64> + 'a {
+        let mut first = [0; 8];
+        let mut second = [0; 8];
+        first.copy_from_slice(&hash[..8]);
+        second.copy_from_slice(&hash[8..16]);
+        let first = u64::from_le_bytes(first);
+        let second = u64::from_le_bytes(second) | 1;
+        (0..self.hashes as u64).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % self.len)
+    }
+
+    fn insert(&mut self, hash: &[u8; 20]) {
+        let positions: Vec<_> = self.positions(hash).collect();
+        for position in positions {
+            self.bits[(position / 64) as usize] |= 1 << (position % 64);
+        }
+    }
+
+    fn contains(&self, hash: &[u8; 20]) -> bool {
+        self.positions(hash)
+            .all(|position| self.bits[(position / 64) as usize] & (1 << (position % 64)) != 0)
+    }
+
+    #[throws(Error)]
+    fn write(&self, output: &mut impl Write) {
+        output.write_all(MAGIC)?;
+        output.write_all(&self.hashes.to_le_bytes())?;
+        output.write_all(&self.len.to_le_bytes())?;
+        for word in &self.bits {
+            output.write_all(&word.to_le_bytes())?;
+        }
+    }
+
+    /// Reads a filter from `input`, whose `size` in bytes must match the length in its header,
+    /// so a corrupt header can't make it allocate more memory than the file takes.
+    #[throws(Error)]
+    fn read(mut input: impl Read, size: u64) -> BloomFilter {
+        let mut magic = [0; 8];
+        let mut hashes = [0; 4];
+        let mut len = [0; 8];
+        input.read_exact(&mut magic)?;
+        input.read_exact(&mut hashes)?;
+        input.read_exact(&mut len)?;
+        let header = (MAGIC.len() + hashes.len() + len.len()) as u64;
+        let hashes = u32::from_le_bytes(hashes);
+        let len = u64::from_le_bytes(len);
+        let words = len.div_ceil(64);
+        if &magic != MAGIC || hashes == 0 || len == 0 || words.checked_mul(8) != size.checked_sub(header) {
+            throw!(Error::InvalidBloomFilterError);
+        }
+        let mut bits = Vec::with_capacity(words as usize);
+        let mut word = [0; 8];
+        for _ in 0..words {
+            input.read_exact(&mut word)?;
+            bits.push(u64::from_le_bytes(word));
+        }
+        BloomFilter { hashes, len, bits }
+    }
+}
+
+impl Users {
+    /// Loads the breached passwords to reject from a directory of range files, or from a Bloom filter file.
+    /// See [`BreachedPasswords`] for their formats.
+    #[throws(Error)]
+    pub fn load_breached_passwords(&mut self, path: impl AsRef<Path>) {
+        self.breached_passwords = Some(BreachedPasswords::load(path)?);
+    }
+
+    /// Sets the breached passwords to reject when signing up or changing passwords.
+    pub fn set_breached_passwords(&mut self, breached: BreachedPasswords) {
+        self.breached_passwords = Some(breached);
+    }
+
+    /// Checks whether a password has been breached, reading range files on the blocking thread pool.
+    #[throws(Error)]
+    pub(crate) async fn is_breached(&self, password: &str) -> bool {
+        match &self.breached_passwords {
+            None => false,
+            Some(breached) => {
+                let breached = breached.clone();
+                let password = password.to_string();
+                let task = tokio::task::spawn_blocking(move || breached.contains(&password));
+                task.await.map_err(io::Error::other)??
+            }
+        }
+    }
+}
@@ -1,10 +1,22 @@
-use crate::prelude::*;
+mod breached;
+mod policy;
+mod strength;
+
+pub use breached::BreachedPasswords;
+pub use policy::PasswordPolicy;
+pub(crate) use policy::password_error;
+pub use strength::PasswordStrength;
 
+use crate::prelude::*;
 
 /// The `Login` form is used along with the [`Auth`] guard to authenticate users.
+/// Users that signed up with a username can log in with it instead of their email,
+/// either in the `email` field or in a `username` field.
 #[derive(FromForm, Deserialize, Clone, Hash, PartialEq, Eq, Validate)]
 pub struct Login {
-    #[validate(email)]
+    #[field(name = "email")]
+    #[field(name = "username")]
+    #[serde(alias = "username")]
     pub email: String,
     pub(crate) password: String,
 }
@@ -14,14 +26,66 @@ pub struct Login {
 pub struct Signup {
     #[validate(email)]
     pub email: String,
-    #[validate(
-        custom = "is_long",
-        custom = "has_number",
-        custom = "has_lowercase",
-        custom = "has_uppercase"
-    )]
+    /// An optional username the user can log in with. It is unique regardless of case.
+    #[validate(custom = "is_username")]
+    #[serde(default)]
+    pub username: Option<String>,
+    /// It is checked against the [`PasswordPolicy`] and the [`BreachedPasswords`] of [`Users`] when signing up.
     pub(crate) password: String,
 }
+
+/// The `SignupWith` form extends [`Signup`] with application defined profile data,
+/// which is stored along with the user. See [`Auth::signup_with_profile`](crate::Auth::signup_with_profile).
+/// In html forms the fields of the profile are prefixed with `profile.`, such as `profile.display_name`.
+/// ```rust
+/// # use rocket::FromForm;
+/// # use rocket_auth::SignupWith;
+/// # use serde::{Serialize, Deserialize};
+/// #[derive(FromForm, Serialize, Deserialize)]
+/// struct Profile {
+///     display_name: String,
+/// }
+///
+/// type ProfileSignup = SignupWith<Profile>;
+/// ```
+#[derive(FromForm, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct SignupWith<T> {
+    pub email: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    pub(crate) password: String,
+    pub profile: T,
+}
+
+impl<T> Debug for SignupWith<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SignupWith {{ email: {:?}, password: \"*****\" }}",
+            self.email
+        )
+    }
+}
+
+impl<T> From<&SignupWith<T>> for Signup {
+    fn from(form: &SignupWith<T>) -> Signup {
+        Signup {
+            email: form.email.clone(),
+            username: form.username.clone(),
+            password: form.password.clone(),
+        }
+    }
+}
+
+impl<T> From<&SignupWith<T>> for Login {
+    fn from(form: &SignupWith<T>) -> Login {
+        Login {
+            email: form.email.clone(),
+            password: form.password.clone(),
+        }
+    }
+}
+
 impl Debug for Signup {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -54,6 +118,7 @@ impl From<Login> for Signup {
     fn from(form: Login) -> Signup {
         Self {
             email: form.email,
+            username: None,
             password: form.password,
         }
     }
@@ -67,57 +132,15 @@ impl<T: Deref<Target = Signup>> From<T> for Login {
         }
     }
 }
-#[throws(ValidationError)]
-pub(crate) fn is_secure(password: &str) {
-    is_long(password)?;
-    has_uppercase(password)?;
-    has_lowercase(password)?;
-    has_number(password)?;
-}
 
+/// Usernames can't contain `@`, so they are never mistaken for emails.
 #[throws(ValidationError)]
-fn is_long(password: &str) {
-    if password.len() < 8 {
+pub(crate) fn is_username(username: &str) {
+    let is_valid = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '.';
+    let length = username.chars().count();
+    if !(3..=32).contains(&length) || !username.chars().all(is_valid) {
         throw!(ValidationError::new(
-            "The password must be at least 8 characters long.\n"
+            "The username must be between 3 and 32 characters long, and may only contain letters, digits, dots, dashes and underscores.\n"
         ));
     }
 }
-#[allow(unreachable_code)]
-#[throws(ValidationError)]
-fn has_uppercase(password: &str) {
-    for c in password.chars() {
-        if c.is_uppercase() {
-            return;
-        }
-    }
-    throw!(ValidationError::new(
-        "The password must include least one uppercase character.\n"
-    ));
-}
-#[allow(unreachable_code)]
-#[throws(ValidationError)]
-fn has_lowercase(password: &str) {
-    for c in password.chars() {
-        if c.is_lowercase() {
-            return;
-        }
-    }
-    // throw!(Error::UnsafePasswordHasNoLower)
-    throw!(ValidationError::new(
-        "The password must include least one uppercase character.\n"
-    ))
-}
-#[allow(unreachable_code)]
-#[throws(ValidationError)]
-fn has_number(password: &str) {
-    for c in password.chars() {
-        if c.is_numeric() {
-            return;
-        }
-    }
-    throw!(ValidationError::new(
-        "The password has to contain at least one digit.\n"
-    ))
-    // throw!(Error::UnsafePasswordHasNoDigit)
-}
@@ -0,0 +1,230 @@
+use super::strength::email_inputs;
+use crate::prelude::*;
+use std::sync::Arc;
+use validator::ValidationErrors;
+
+type Rule = Arc<dyn Fn(&str, &str) -> Result<(), ValidationError> + Send + Sync>;
+
+/// The requirements passwords must meet when users sign up or change their password.
+/// It is set with [`Users::set_password_policy`], and the default policy requires at least 8 characters,
+/// with at least one uppercase letter, one lowercase letter and one digit.
+/// ```rust
+/// # use rocket_auth::{Users, PasswordPolicy};
+/// # use validator::ValidationError;
+/// # fn func(mut users: Users) {
+/// let mut policy = PasswordPolicy::default();
+/// policy.min_length = 12;
+/// policy.require_uppercase = false;
+/// policy.require_digit = false;
+/// policy.forbid_email = true;
+/// policy.forbidden_substrings = vec!["acme".into()];
+/// policy.add_rule(|password, _email| {
+///     if password.chars().all(|c| c.is_ascii_lowercase()) {
+///         return Err(ValidationError::new("The password can't consist of lowercase letters only.\n"));
+///     }
+///     Ok(())
+/// });
+/// users.set_password_policy(policy);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    /// The minimum number of characters.
+    pub min_length: usize,
+    /// The maximum number of characters. It defaults to 1024, so huge passwords can't be used to exhaust the server.
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    /// Requires a character that is neither a letter, a digit nor whitespace.
+    pub require_symbol: bool,
+    /// Forbids passwords containing the part of the email before the `@`, if it is at least 3 characters long.
+    pub forbid_email: bool,
+    /// Forbids passwords containing any of these, regardless of case.
+    pub forbidden_substrings: Vec<String>,
+    /// Requires a [`PasswordStrength`] score of at least this much, from 0 to 4.
+    /// Passwords are scored by how guessable they are, taking the email of the user into account.
+    /// A score of 3 is a good choice. It is disabled by default. The score, warning and suggestions
+    /// of a rejected password are sent in the `errors` field of the json error response.
+    pub min_strength: Option<u8>,
+    /// The maximum age of a password. Once it is older, the user must change it after logging in,
+    /// as described in [`LoginStatus::PasswordChangeRequired`](crate::LoginStatus::PasswordChangeRequired).
+    /// Passwords set before the age of passwords was tracked never expire, and neither do the ones checked by a
+    /// [`CredentialVerifier`](crate::credentials::CredentialVerifier) other than the default one, such as LDAP,
+    /// nor the ones of users authenticated by a [`ProxyAuth`](crate::ProxyAuth) gateway. It is disabled by default.
+    pub max_age: Option<Duration>,
+    rules: Vec<Rule>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 1024,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: false,
+            forbid_email: false,
+            forbidden_substrings: vec![],
+            min_strength: None,
+            max_age: None,
+            rules: vec![],
+        }
+    }
+}
+
+impl Debug for PasswordPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasswordPolicy")
+            .field("min_length", &self.min_length)
+            .field("max_length", &self.max_length)
+            .field("require_uppercase", &self.require_uppercase)
+            .field("require_lowercase", &self.require_lowercase)
+            .field("require_digit", &self.require_digit)
+            .field("require_symbol", &self.require_symbol)
+            .field("forbid_email", &self.forbid_email)
+            .field("forbidden_substrings", &self.forbidden_substrings)
+            .field("min_strength", &self.min_strength)
+            .field("max_age", &self.max_age)
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+impl PasswordPolicy {
+    /// Adds a custom rule, which receives the password and the email of the user.
+    /// The code of the returned error is used as its message.
+    pub fn add_rule(
+        &mut self,
+        rule: impl Fn(&str, &str) -> Result<(), ValidationError> + Send + Sync + 'static,
+    ) {
+        self.rules.push(Arc::new(rule));
+    }
+
+    /// Checks a password against every requirement, returning all of the unmet ones
+    /// as [`Error::FormValidationErrors`] for the `password` field.
+    /// ```rust
+    /// # use rocket_auth::PasswordPolicy;
+    /// let policy = PasswordPolicy::default();
+    /// assert!(policy.check("Password123", "bob@acme.com").is_ok());
+    /// assert!(policy.check("password", "bob@acme.com").is_err());
+    /// ```
+    pub fn check(&self, password: &str, email: &str) -> Result<()> {
+        password_errors(self.errors(password, email))
+    }
+
+    fn errors(&self, password: &str, email: &str) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        let length = password.chars().count();
+        if length < self.min_length {
+            errors.push(message(format!(
+                "The password must be at least {} characters long.\n",
+                self.min_length
+            )));
+        }
+        if length > self.max_length {
+            errors.push(message(format!(
+                "The password must be at most {} characters long.\n",
+                self.max_length
+            )));
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            errors.push(message("The password must include at least one uppercase character.\n"));
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            errors.push(message("The password must include at least one lowercase character.\n"));
+        }
+        if self.require_digit && !password.chars().any(char::is_numeric) {
+            errors.push(message("The password has to contain at least one digit.\n"));
+        }
+        let is_symbol = |c: char| !c.is_alphanumeric() && !c.is_whitespace();
+        if self.require_symbol && !password.chars().any(is_symbol) {
+            errors.push(message("The password has to contain at least one symbol.\n"));
+        }
+        let lowercase = password.to_lowercase();
+        let local_part = email.split('@').next().unwrap_or_default().to_lowercase();
+        if self.forbid_email && local_part.chars().count() >= 3 && lowercase.contains(&local_part) {
+            errors.push(message("The password must not contain your email address.\n"));
+        }
+        for forbidden in &self.forbidden_substrings {
+            if lowercase.contains(&forbidden.to_lowercase()) {
+                errors.push(message(format!("The password must not contain \"{}\".\n", forbidden)));
+            }
+        }
+        if let Some(min_strength) = self.min_strength {
+            let strength = PasswordStrength::estimate(password, &email_inputs(email));
+            if strength.score < min_strength {
+                errors.push(too_weak(&strength));
+            }
+        }
+        for rule in &self.rules {
+            if let Err(error) = rule(password, email) {
+                errors.push(error);
+            }
+        }
+        errors
+    }
+}
+
+/// Fails with the errors of the `password` field, if there are any.
+fn password_errors(errors: Vec<ValidationError>) -> Result<()> {
+    let mut result = ValidationErrors::new();
+    for error in errors {
+        result.add("password", error);
+    }
+    if result.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FormValidationErrors(result))
+    }
+}
+
+/// An error for the `password` field with a single message.
+pub(crate) fn password_error(text: impl Into<String>) -> Error {
+    let mut errors = ValidationErrors::new();
+    errors.add("password", message(text));
+    Error::FormValidationErrors(errors)
+}
+
+/// A validation error whose code is the message shown to the client, like the rest of the form errors.
+fn message(text: impl Into<String>) -> ValidationError {
+    let mut error = ValidationError::new("");
+    error.code = text.into().into();
+    error
+}
+
+/// Includes the score, warning and suggestions of a password in its error, both in its message and as params.
+fn too_weak(strength: &PasswordStrength) -> ValidationError {
+    let mut text = String::from("The password is too easy to guess.");
+    for feedback in strength.warning.iter().chain(&strength.suggestions) {
+        text.push(' ');
+        text.push_str(feedback);
+    }
+    text.push('\n');
+    let mut error = message(text);
+    error.add_param("score".into(), &strength.score);
+    error.add_param("warning".into(), &strength.warning);
+    error.add_param("suggestions".into(), &strength.suggestions);
+    error
+}
+
+impl Users {
+    /// Sets the requirements passwords must meet when signing up, or when changing
+    /// them with [`Auth::change_password`] and [`Users::set_password`].
+    pub fn set_password_policy(&mut self, policy: PasswordPolicy) {
+        self.password_policy = policy;
+    }
+
+    /// Checks a password against the [`PasswordPolicy`], and against the [`BreachedPasswords`] if they were loaded.
+    #[throws(Error)]
+    pub(crate) async fn check_password(&self, password: &str, email: &str) {
+        let mut errors = self.password_policy.errors(password, email);
+        if errors.is_empty() && self.is_breached(password).await? {
+            let mut error = ValidationError::new(BreachedPasswords::ERROR_CODE);
+            error.message = Some(BreachedPasswords::ERROR_MESSAGE.into());
+            errors.push(error);
+        }
+        password_errors(errors)?
+    }
+}
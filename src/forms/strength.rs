@@ -0,0 +1,414 @@
+//! Estimates how many guesses an attacker would need to find a password, in the style of zxcvbn.
+//! The password is split into the sequence of patterns that is cheapest to guess: words from the
+//! bundled dictionaries or the user's own details, sequences, repeats, recent years, and any
+//! remaining characters, which are brute forced.
+use crate::prelude::*;
+use chrono::Datelike;
+use std::collections::HashMap;
+
+lazy_static::lazy_static! {
+    static ref PASSWORDS: HashMap<&'static str, usize> = ranked(include_str!("dictionaries/passwords.txt"));
+    static ref WORDS: HashMap<&'static str, usize> = ranked(include_str!("dictionaries/words.txt"));
+}
+
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+const LEET: [(char, char); 9] = [
+    ('4', 'a'),
+    ('@', 'a'),
+    ('3', 'e'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('0', 'o'),
+    ('$', 's'),
+    ('5', 's'),
+    ('7', 't'),
+];
+
+/// The strength of a password, along with feedback for the user on how to improve it.
+/// ```rust
+/// # use rocket_auth::PasswordStrength;
+/// let weak = PasswordStrength::estimate("Password1", &[]);
+/// assert_eq!(weak.score, 0);
+/// assert!(weak.warning.is_some());
+///
+/// let strong = PasswordStrength::estimate("correct horse battery staple", &[]);
+/// assert!(strong.score >= 3);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PasswordStrength {
+    /// From 0, too guessable, to 4, very unguessable.
+    pub score: u8,
+    /// The base 10 logarithm of the estimated number of guesses.
+    pub guesses_log10: f64,
+    /// Explains what makes the password weak, if anything.
+    pub warning: Option<String>,
+    /// Tips on how to choose a stronger password.
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Pattern {
+    Dictionary {
+        common_password: bool,
+        user_input: bool,
+        uppercase: bool,
+        leet: bool,
+    },
+    Sequence,
+    KeyboardRow,
+    Repeat { single_char: bool },
+    Year,
+}
+
+#[derive(Clone, Debug)]
+struct Match {
+    start: usize,
+    end: usize,
+    guesses: f64,
+    pattern: Pattern,
+}
+
+impl PasswordStrength {
+    /// Estimates the strength of a password. `user_inputs` are words an attacker
+    /// could know about the user, such as their name, username or email.
+    /// Only the first 100 characters are taken into account.
+    pub fn estimate(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+        let chars: Vec<char> = password.chars().take(100).collect();
+        let user_inputs = ranked_inputs(user_inputs);
+        let (guesses_log10, sequence) = minimum_guesses(&chars, &user_inputs, true);
+        let score = match guesses_log10 {
+            x if x < 3.0 => 0,
+            x if x < 6.0 => 1,
+            x if x < 8.0 => 2,
+            x if x < 10.0 => 3,
+            _ => 4,
+        };
+        let mut strength = PasswordStrength {
+            score,
+            guesses_log10,
+            warning: None,
+            suggestions: vec![],
+        };
+        if score < 3 {
+            strength.add_feedback(&chars, &sequence);
+        }
+        strength
+    }
+
+    fn add_feedback(&mut self, chars: &[char], sequence: &[Match]) {
+        let mut suggestions = vec![];
+        if chars.is_empty() {
+            suggestions.push("Use a few words, avoid common phrases.");
+        } else {
+            suggestions.push("Add another word or two. Uncommon words are better.");
+        }
+        let longest = sequence.iter().max_by_key(|m| m.end - m.start);
+        let warning = match longest.map(|m| (m, &m.pattern)) {
+            Some((m, Pattern::Dictionary { common_password, user_input, uppercase, leet })) => {
+                if *uppercase {
+                    suggestions.push("Capitalization doesn't help very much.");
+                }
+                if *leet {
+                    suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much.");
+                }
+                let whole = m.end - m.start == chars.len();
+                if *user_input {
+                    Some("Passwords containing your email address are easy to guess.")
+                } else if *common_password && whole && m.guesses <= 10.0 {
+                    Some("This is a top-10 common password.")
+                } else if *common_password && whole && m.guesses <= 100.0 {
+                    Some("This is a top-100 common password.")
+                } else if *common_password && whole {
+                    Some("This is a very common password.")
+                } else if *common_password {
+                    Some("This is similar to a commonly used password.")
+                } else if whole {
+                    Some("A word by itself is easy to guess.")
+                } else {
+                    None
+                }
+            }
+            Some((_, Pattern::Sequence)) => {
+                suggestions.push("Avoid sequences.");
+                Some("Sequences like abc or 6543 are easy to guess.")
+            }
+            Some((_, Pattern::KeyboardRow)) => {
+                suggestions.push("Use a longer keyboard pattern with more turns.");
+                Some("Straight rows of keys are easy to guess.")
+            }
+            Some((_, Pattern::Repeat { single_char })) => {
+                suggestions.push("Avoid repeated words and characters.");
+                match single_char {
+                    true => Some("Repeats like \"aaa\" are easy to guess."),
+                    false => Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"."),
+                }
+            }
+            Some((_, Pattern::Year)) => {
+                suggestions.push("Avoid recent years and years that are associated with you.");
+                Some("Recent years are easy to guess.")
+            }
+            None => None,
+        };
+        self.warning = warning.map(String::from);
+        self.suggestions = suggestions.into_iter().map(String::from).collect();
+    }
+}
+
+/// The words of the user inputs, ranked in the order they were given.
+fn ranked_inputs(user_inputs: &[&str]) -> HashMap<String, usize> {
+    let mut ranked = HashMap::new();
+    for input in user_inputs {
+        let input = input.to_lowercase();
+        let words = input.split(|c: char| !c.is_alphanumeric());
+        for word in std::iter::once(&input[..]).chain(words) {
+            if word.chars().count() >= 3 {
+                let rank = ranked.len() + 1;
+                ranked.entry(word.to_string()).or_insert(rank);
+            }
+        }
+    }
+    ranked
+}
+
+fn ranked(list: &'static str) -> HashMap<&'static str, usize> {
+    let mut ranked = HashMap::new();
+    for (rank, word) in list.lines().enumerate() {
+        ranked.entry(word).or_insert(rank + 1);
+    }
+    ranked
+}
+
+/// Finds the cheapest way to guess `chars`, returning the logarithm of its guesses and its patterns.
+/// Repeats are only looked for if `repeats` is set, which is not the case when guessing the repeated part itself.
+fn minimum_guesses(
+    chars: &[char],
+    user_inputs: &HashMap<String, usize>,
+    repeats: bool,
+) -> (f64, Vec<Match>) {
+    let mut matches = dictionary_matches(chars, user_inputs);
+    matches.extend(sequence_matches(chars));
+    matches.extend(year_matches(chars));
+    if repeats {
+        matches.extend(repeat_matches(chars, user_inputs));
+    }
+    // cheapest[k] is the cost of guessing the first k characters, and the match ending the cheapest way.
+    let mut cheapest: Vec<(f64, Option<&Match>)> = vec![(0.0, None)];
+    for k in 1..=chars.len() {
+        let brute_force = (cheapest[k - 1].0 + 1.0, None);
+        let best = matches
+            .iter()
+            .filter(|m| m.end == k)
+            .map(|m| {
+                let guesses = if m.end - m.start < chars.len() {
+                    m.guesses.max(50.0)
+                } else {
+                    m.guesses
+                };
+                (cheapest[m.start].0 + guesses.log10(), Some(m))
+            })
+            .fold(brute_force, |best, candidate| {
+                if candidate.0 < best.0 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        cheapest.push(best);
+    }
+    let mut sequence = vec![];
+    let mut k = chars.len();
+    while k > 0 {
+        match cheapest[k].1 {
+            Some(m) => {
+                sequence.push(m.clone());
+                k = m.start;
+            }
+            None => k -= 1,
+        }
+    }
+    sequence.reverse();
+    (cheapest[chars.len()].0, sequence)
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &HashMap<String, usize>) -> Vec<Match> {
+    let lowercase: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let unleet: Vec<char> = lowercase.iter().map(|c| unleet_char(*c)).collect();
+    let mut matches = vec![];
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len().min(start + 32) {
+            let original = &chars[start..end];
+            for (candidate, leet) in [(&lowercase[start..end], false), (&unleet[start..end], true)] {
+                if leet && candidate == &lowercase[start..end] {
+                    continue;
+                }
+                let word: String = candidate.iter().collect();
+                let (rank, common_password, user_input) = if let Some(rank) = user_inputs.get(&word) {
+                    (*rank, false, true)
+                } else if let Some(rank) = PASSWORDS.get(&word[..]) {
+                    (*rank, true, false)
+                } else if let Some(rank) = WORDS.get(&word[..]) {
+                    (*rank, false, false)
+                } else {
+                    continue;
+                };
+                let uppercase = uppercase_variations(original);
+                let leet_variations = if leet { 2f64.powi(substitutions(original) as i32) } else { 1.0 };
+                matches.push(Match {
+                    start,
+                    end,
+                    guesses: rank as f64 * uppercase * leet_variations,
+                    pattern: Pattern::Dictionary {
+                        common_password,
+                        user_input,
+                        uppercase: uppercase > 1.0,
+                        leet,
+                    },
+                });
+            }
+        }
+    }
+    matches
+}
+
+fn unleet_char(c: char) -> char {
+    LEET.iter().find(|(leet, _)| *leet == c).map_or(c, |(_, letter)| *letter)
+}
+
+fn substitutions(chars: &[char]) -> usize {
+    chars.iter().filter(|c| unleet_char(**c) != **c).count()
+}
+
+fn uppercase_variations(chars: &[char]) -> f64 {
+    let upper = chars.iter().filter(|c| c.is_uppercase()).count();
+    let lower = chars.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        1.0
+    } else if lower == 0 || (upper == 1 && chars[0].is_uppercase()) {
+        2.0
+    } else {
+        2f64.powi(upper.min(lower) as i32).max(2.0)
+    }
+}
+
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let lowercase: String = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let lowercase: Vec<char> = lowercase.chars().collect();
+    let mut matches = vec![];
+    // runs of characters whose codes keep increasing or decreasing by one, such as abc or 6543
+    let mut start = 0;
+    while start + 2 < lowercase.len() {
+        let delta = lowercase[start + 1] as i64 - lowercase[start] as i64;
+        let mut end = start + 1;
+        while (delta == 1 || delta == -1)
+            && end < lowercase.len()
+            && lowercase[end] as i64 - lowercase[end - 1] as i64 == delta
+            && lowercase[end].is_ascii_alphanumeric()
+        {
+            end += 1;
+        }
+        if end - start >= 3 && lowercase[start].is_ascii_alphanumeric() {
+            let first = lowercase[start];
+            let base = match first {
+                'a' | 'z' | '0' | '1' | '9' => 4.0,
+                c if c.is_ascii_digit() => 10.0,
+                _ => 26.0,
+            };
+            matches.push(Match {
+                start,
+                end,
+                guesses: base * (end - start) as f64,
+                pattern: Pattern::Sequence,
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    // straight rows of the keyboard, in either direction
+    let text: String = lowercase.iter().collect();
+    for row in KEYBOARD_ROWS.iter() {
+        let reversed: String = row.chars().rev().collect();
+        for row in [row.to_string(), reversed] {
+            for length in 4..=row.len() {
+                for offset in 0..=row.len() - length {
+                    let pattern = &row[offset..offset + length];
+                    for (byte_start, _) in text.match_indices(pattern) {
+                        let start = text[..byte_start].chars().count();
+                        matches.push(Match {
+                            start,
+                            end: start + length,
+                            guesses: 50.0 * length as f64,
+                            pattern: Pattern::KeyboardRow,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn repeat_matches(chars: &[char], user_inputs: &HashMap<String, usize>) -> Vec<Match> {
+    let mut matches = vec![];
+    let mut base_guesses = HashMap::new();
+    for start in 0..chars.len() {
+        for base_length in 1..=(chars.len() - start) / 2 {
+            let base = &chars[start..start + base_length];
+            // only the longest repeat of each base is considered
+            if start >= base_length && &chars[start - base_length..start] == base {
+                continue;
+            }
+            let mut end = start + base_length;
+            while end + base_length <= chars.len() && &chars[end..end + base_length] == base {
+                end += base_length;
+            }
+            let count = (end - start) / base_length;
+            if count < 2 || end - start < 3 {
+                continue;
+            }
+            let base_guesses = *base_guesses
+                .entry(base)
+                .or_insert_with(|| minimum_guesses(base, user_inputs, false).0);
+            matches.push(Match {
+                start,
+                end,
+                guesses: 10f64.powf(base_guesses) * count as f64,
+                pattern: Pattern::Repeat {
+                    single_char: base_length == 1,
+                },
+            });
+        }
+    }
+    matches
+}
+
+fn year_matches(chars: &[char]) -> Vec<Match> {
+    let current_year = chrono::Utc::now().year();
+    let mut matches = vec![];
+    for start in 0..chars.len().saturating_sub(3) {
+        let digits: String = chars[start..start + 4].iter().collect();
+        match digits.parse::<i32>() {
+            Ok(year) if (1900..=2099).contains(&year) && digits.chars().all(|c| c.is_ascii_digit()) => {
+                matches.push(Match {
+                    start,
+                    end: start + 4,
+                    guesses: ((year - current_year).abs().max(20)) as f64,
+                    pattern: Pattern::Year,
+                });
+            }
+            _ => (),
+        }
+    }
+    matches
+}
+
+/// The words of an email address an attacker could guess, such as `john`, `smith` and `acme`
+/// for `john.smith@acme.com`.
+pub(crate) fn email_inputs(email: &str) -> Vec<&str> {
+    let (local_part, domain) = email.split_once('@').unwrap_or((email, ""));
+    let mut inputs = vec![email, local_part];
+    let labels: Vec<&str> = domain.split('.').collect();
+    inputs.extend(&labels[..labels.len().saturating_sub(1)]);
+    inputs
+}
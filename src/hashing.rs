@@ -0,0 +1,400 @@
+//! Password hashing. New passwords are hashed with the [`PasswordHasher`] set with
+//! [`Users::set_password_hasher`], while existing hashes are verified with the algorithm
+//! named by their prefix, so databases imported from other systems keep working:
+//! * `$argon2i$`, `$argon2d$` and `$argon2id$` hashes are verified with argon2.
+//! * `$2a$`, `$2b$`, `$2x$` and `$2y$` hashes are verified with bcrypt.
+//! * `$scrypt$` hashes are verified with scrypt.
+//! * `pbkdf2_sha256$`, `bcrypt$` and `bcrypt_sha256$` hashes, as stored by Django, are verified
+//!   with PBKDF2 and bcrypt.
+//!
+//! When a user logs in with a password whose hash was produced by another algorithm or
+//! with other parameters, it is rehashed with the current [`PasswordHasher`] and saved,
+//! so raising the cost of the hasher upgrades existing hashes as users log in.
+//! ```rust
+//! # use rocket_auth::{Users, hashing::PasswordHasher};
+//! # fn func(mut users: Users) {
+//! users.set_password_hasher(PasswordHasher::argon2id(64 * 1024, 3, 4));
+//! # }
+//! ```
+//!
+//! ## Pepper
+//! A secret pepper can be mixed into argon2 hashes through their secret key parameter, so passwords
+//! can't be cracked from a dump of the database alone. Peppers are identified by a version, which is
+//! stored as a `pepper<version>$` prefix of the hash, so they can be rotated: hashes made with a previous
+//! pepper keep working as long as it is added with [`Users::add_pepper`], and are rehashed with the current
+//! one as users log in. Peppers only apply to argon2, and are ignored when hashing with bcrypt or scrypt.
+//! ```rust
+//! # use rocket_auth::{Users, Error};
+//! # fn func(mut users: Users) -> Result<(), Error> {
+//! users.add_pepper(1, std::env::var("OLD_PEPPER").unwrap());
+//! users.set_pepper(2, std::env::var("PEPPER").unwrap());
+//! # Ok(()) }
+//! ```
+//!
+//! ## Concurrency
+//! Hashing and verification run on tokio's blocking thread pool, so they don't stall other requests.
+//! At most as many passwords as there are CPUs are hashed at the same time, and further logins wait
+//! for their turn. This limit can be changed with [`Users::set_hashing_concurrency`].
+//! ```rust
+//! # use rocket_auth::Users;
+//! # fn func(mut users: Users) {
+//! users.set_hashing_concurrency(2);
+//! # }
+//! ```
+
+use crate::prelude::*;
+use crate::user::rand_token;
+use scrypt::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+pub use argon2::Variant as Argon2Variant;
+
+/// The algorithm and parameters used to hash new passwords.
+/// The default is Argon2id with 19 MiB of memory, 2 iterations and a parallelism of 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordHasher {
+    Argon2 {
+        variant: Argon2Variant,
+        /// The memory used, in KiB.
+        mem_cost: u32,
+        /// The number of iterations.
+        time_cost: u32,
+        /// The degree of parallelism.
+        lanes: u32,
+    },
+    Bcrypt {
+        cost: u32,
+    },
+    Scrypt {
+        /// The base two logarithm of the cost parameter `N`.
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+}
+
+impl Default for PasswordHasher {
+    fn default() -> PasswordHasher {
+        PasswordHasher::argon2id(19 * 1024, 2, 1)
+    }
+}
+
+impl PasswordHasher {
+    /// Argon2id with `mem_cost` KiB of memory, `time_cost` iterations and `lanes` degrees of parallelism.
+    pub fn argon2id(mem_cost: u32, time_cost: u32, lanes: u32) -> PasswordHasher {
+        PasswordHasher::Argon2 {
+            variant: Argon2Variant::Argon2id,
+            mem_cost,
+            time_cost,
+            lanes,
+        }
+    }
+
+    /// Hashes a password into a PHC string, with a random salt.
+    /// ```rust
+    /// # use rocket_auth::hashing::{self, PasswordHasher};
+    /// let hash = PasswordHasher::Bcrypt { cost: 4 }.hash("password").unwrap();
+    /// assert!(hashing::verify(&hash, "password").unwrap());
+    /// ```
+    #[throws(Error)]
+    pub fn hash(&self, password: &str) -> String {
+        self.hash_with_pepper(password, None)?
+    }
+
+    /// Like [`PasswordHasher::hash`], but mixing the versioned `pepper` into argon2 hashes.
+    #[throws(Error)]
+    fn hash_with_pepper(&self, password: &str, pepper: Option<(u32, &[u8])>) -> String {
+        let salt = rand_token(22);
+        match *self {
+            PasswordHasher::Argon2 {
+                variant,
+                mem_cost,
+                time_cost,
+                lanes,
+            } => {
+                let config = argon2::Config {
+                    variant,
+                    mem_cost,
+                    time_cost,
+                    lanes,
+                    secret: pepper.map_or(&[], |(_, secret)| secret),
+                    ..Default::default()
+                };
+                let hash = argon2::hash_encoded(password.as_bytes(), salt.as_bytes(), &config)?;
+                match pepper {
+                    Some((version, _)) => format!("pepper{}${}", version, hash),
+                    None => hash,
+                }
+            }
+            PasswordHasher::Bcrypt { cost } => bcrypt::hash(password, cost).map_err(hashing_error)?,
+            PasswordHasher::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN)
+                    .map_err(hashing_error)?;
+                let salt = SaltString::encode_b64(salt.as_bytes()).map_err(hashing_error)?;
+                scrypt::Scrypt
+                    .hash_password_customized(password.as_bytes(), None, None, params, &salt)
+                    .map_err(hashing_error)?
+                    .to_string()
+            }
+        }
+    }
+
+    /// Whether a hash was produced by another algorithm or with other parameters than this hasher,
+    /// in which case the password should be hashed again.
+    /// ```rust
+    /// # use rocket_auth::hashing::PasswordHasher;
+    /// let hash = PasswordHasher::Bcrypt { cost: 4 }.hash("password").unwrap();
+    /// assert!(!PasswordHasher::Bcrypt { cost: 4 }.needs_rehash(&hash));
+    /// assert!(PasswordHasher::Bcrypt { cost: 10 }.needs_rehash(&hash));
+    /// assert!(PasswordHasher::default().needs_rehash(&hash));
+    /// ```
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let prefix = match *self {
+            PasswordHasher::Argon2 {
+                variant,
+                mem_cost,
+                time_cost,
+                lanes,
+            } => format!(
+                "${}$v=19$m={},t={},p={}$",
+                variant.as_lowercase_str(),
+                mem_cost,
+                time_cost,
+                lanes
+            ),
+            PasswordHasher::Bcrypt { cost } => {
+                let version = algorithm(hash).filter(|version| is_bcrypt(version));
+                format!("${}${:02}$", version.unwrap_or("2b"), cost)
+            }
+            PasswordHasher::Scrypt { log_n, r, p } => format!("$scrypt$ln={},r={},p={}$", log_n, r, p),
+        };
+        !hash.starts_with(&prefix)
+    }
+}
+
+/// Compares a password against a hash produced by any of the supported algorithms.
+/// It fails with [`Error::UnsupportedHashError`] if the algorithm of the hash is unknown,
+/// and with [`Error::UnknownPepperError`] if the hash was peppered.
+#[throws(Error)]
+pub fn verify(hash: &str, password: &str) -> bool {
+    verify_with_peppers(hash, password, &Peppers::default())?
+}
+
+#[throws(Error)]
+fn verify_with_peppers(hash: &str, password: &str, peppers: &Peppers) -> bool {
+    if let Some(hash) = hash.strip_prefix("pepper") {
+        let (version, hash) = hash.split_once('$').ok_or(Error::UnsupportedHashError)?;
+        let version = version.parse().map_err(|_| Error::UnsupportedHashError)?;
+        let secret = peppers.get(version).ok_or(Error::UnknownPepperError(version))?;
+        return argon2::verify_encoded_ext(hash, password.as_bytes(), secret, &[])?;
+    }
+    if let Some(hash) = hash.strip_prefix("bcrypt_sha256$") {
+        let password = format!("{:x}", Sha256::digest(password.as_bytes()));
+        return verify(hash, &password)?;
+    }
+    if let Some(hash) = hash.strip_prefix("bcrypt$") {
+        return verify(hash, password)?;
+    }
+    if let Some(hash) = hash.strip_prefix("pbkdf2_sha256$") {
+        return verify_django_pbkdf2(hash, password)?;
+    }
+    match algorithm(hash) {
+        Some("argon2i" | "argon2d" | "argon2id") => argon2::verify_encoded(hash, password.as_bytes())?,
+        Some(version) if is_bcrypt(version) => bcrypt::verify(password, hash).map_err(hashing_error)?,
+        Some("scrypt") => {
+            let hash = PasswordHash::new(hash).map_err(hashing_error)?;
+            scrypt::Scrypt
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        }
+        _ => throw!(Error::UnsupportedHashError),
+    }
+}
+
+/// Verifies the `<iterations>$<salt>$<hash>` part of a Django PBKDF2 hash.
+#[throws(Error)]
+fn verify_django_pbkdf2(hash: &str, password: &str) -> bool {
+    let parts: Vec<&str> = hash.split('$').collect();
+    let (iterations, salt, expected) = match parts[..] {
+        [iterations, salt, expected] => (iterations, salt, expected),
+        _ => throw!(Error::UnsupportedHashError),
+    };
+    let iterations = iterations.parse().map_err(hashing_error)?;
+    let expected = base64::decode(expected).map_err(hashing_error)?;
+    let mut derived = vec![0; expected.len()];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), iterations, &mut derived);
+    constant_time_eq(&derived, &expected)
+}
+
+/// Compares two byte strings in a time that doesn't depend on where they differ.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The identifier between the first two `$` of a PHC string.
+fn algorithm(hash: &str) -> Option<&str> {
+    hash.strip_prefix('$')?.split('$').next()
+}
+
+fn is_bcrypt(version: &str) -> bool {
+    matches!(version, "2a" | "2b" | "2x" | "2y")
+}
+
+fn hashing_error(error: impl std::fmt::Display) -> Error {
+    Error::HashingError(error.to_string())
+}
+
+impl Users {
+    /// Sets the algorithm used to hash new passwords. Existing hashes keep working,
+    /// since they are verified according to their own algorithm.
+    pub fn set_password_hasher(&mut self, hasher: PasswordHasher) {
+        self.hasher = hasher;
+    }
+
+    /// Sets the maximum number of passwords hashed or verified at the same time.
+    /// It defaults to the number of CPUs. A value of `0` is treated as `1`, since no password could be hashed otherwise.
+    pub fn set_hashing_concurrency(&mut self, permits: usize) {
+        self.hashing_permits = Arc::new(Semaphore::new(permits.max(1)));
+    }
+
+    /// Sets the password of a user, checking it against the [`PasswordPolicy`], the breached passwords
+    /// and the password history configured in [`Users`], and hashing it with its hasher and pepper.
+    /// The user still needs to be saved with [`Users::modify`].
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let mut user = users.get_by_id(3).await?;
+    /// users.set_password(&mut user, "Password123").await?;
+    /// users.modify(&user).await?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn set_password(&self, user: &mut User, password: &str) {
+        self.check_password(password, &user.email).await?;
+        self.check_password_reuse(user, password).await?;
+        user.password = self.hash_password(password).await?;
+        user.password_changed();
+    }
+
+    /// Sets the pepper mixed into new argon2 hashes, identified by `version`.
+    /// Hashes made with previous peppers are rehashed with this one as users log in.
+    pub fn set_pepper(&mut self, version: u32, secret: impl AsRef<[u8]>) {
+        self.add_pepper(version, secret);
+        self.peppers.current = Some(version);
+    }
+
+    /// Adds a pepper that is only used to verify existing hashes, such as one that is being rotated out.
+    pub fn add_pepper(&mut self, version: u32, secret: impl AsRef<[u8]>) {
+        let secrets = &mut self.peppers.secrets;
+        secrets.retain(|(existing, _)| *existing != version);
+        secrets.push((version, secret.as_ref().to_vec()));
+    }
+
+    /// Sets a pepper derived from the `secret_key` of a Rocket configuration,
+    /// so no additional secret needs to be managed. Note that rotating the secret key
+    /// then requires keeping the previous one as a pepper with [`Users::add_pepper`].
+    /// ```rust,no_run
+    /// # use rocket_auth::{Users, Error};
+    /// # fn func(mut users: Users) -> Result<(), Error> {
+    /// users.set_pepper_from_secret_key(1, &rocket::Config::figment())?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub fn set_pepper_from_secret_key(&mut self, version: u32, figment: &rocket::figment::Figment) {
+        let secret_key: String = figment
+            .extract_inner("secret_key")
+            .map_err(|_| Error::MissingSecretKeyError)?;
+        let mut hasher = Sha256::new();
+        hasher.update(b"rocket_auth pepper");
+        hasher.update(secret_key.as_bytes());
+        self.set_pepper(version, hasher.finalize());
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn hash_password(&self, password: &str) -> String {
+        let hasher = self.hasher;
+        let pepper = self.peppers.current();
+        let password = password.to_string();
+        self.run_blocking(move || {
+            let pepper = pepper.as_ref().map(|(version, secret)| (*version, &secret[..]));
+            hasher.hash_with_pepper(&password, pepper)
+        })
+        .await?
+    }
+
+    /// Checks the password of a user, using the peppers added to [`Users`].
+    /// Verification runs on the blocking thread pool, like logins do.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let user = users.get_by_email("alice@example.com").await?;
+    /// if users.verify_password(&user, "Password123").await? {
+    ///     println!("The password is correct");
+    /// }
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn verify_password(&self, user: &User, password: &str) -> bool {
+        self.verify_hash(&user.password, password).await?
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn verify_hash(&self, hash: &str, password: &str) -> bool {
+        let peppers = self.peppers.clone();
+        let hash = hash.to_string();
+        let password = password.to_string();
+        self.run_blocking(move || verify_with_peppers(&hash, &password, &peppers))
+            .await?
+    }
+
+    /// Whether a hash doesn't match the current hasher or pepper.
+    pub(crate) fn needs_rehash(&self, hash: &str) -> bool {
+        match self.peppers.current {
+            Some(version) if matches!(self.hasher, PasswordHasher::Argon2 { .. }) => {
+                match hash.strip_prefix(&format!("pepper{}$", version)) {
+                    Some(hash) => self.hasher.needs_rehash(hash),
+                    None => true,
+                }
+            }
+            _ => self.hasher.needs_rehash(hash),
+        }
+    }
+
+    /// Runs `function` on the blocking thread pool once a hashing permit is available.
+    #[throws(Error)]
+    async fn run_blocking<T: Send + 'static>(&self, function: impl FnOnce() -> Result<T> + Send + 'static) -> T {
+        let permit = self.hashing_permits.clone().acquire_owned().await.map_err(hashing_error)?;
+        let task = tokio::task::spawn_blocking(move || {
+            let result = function();
+            drop(permit);
+            result
+        });
+        task.await.map_err(hashing_error)??
+    }
+}
+
+/// The peppers known to [`Users`], by version.
+#[derive(Clone, Default)]
+pub(crate) struct Peppers {
+    current: Option<u32>,
+    secrets: Vec<(u32, Vec<u8>)>,
+}
+
+impl Peppers {
+    fn get(&self, version: u32) -> Option<&[u8]> {
+        let (_, secret) = self.secrets.iter().find(|(existing, _)| *existing == version)?;
+        Some(secret)
+    }
+
+    fn current(&self) -> Option<(u32, Vec<u8>)> {
+        let version = self.current?;
+        Some((version, self.get(version)?.to_vec()))
+    }
+}
+
+/// The default number of passwords hashed at the same time.
+pub(crate) fn default_concurrency() -> usize {
+    std::thread::available_parallelism().map_or(4, |threads| threads.get())
+}
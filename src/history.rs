@@ -0,0 +1,58 @@
+use crate::forms::password_error;
+use crate::prelude::*;
+
+impl Users {
+    /// Forbids reusing any of the last `count` passwords of a user, including their current one,
+    /// when changing it with [`Auth::change_password`], the admin routes or [`Users::set_password`].
+    /// The hashes of replaced passwords are kept in the `password_history` table, which is created by [`Users::create_table`],
+    /// and only the ones that are still checked are kept. It is disabled by default, with a `count` of 0.
+    /// ```rust
+    /// # use rocket_auth::Users;
+    /// # fn func(mut users: Users) {
+    /// users.set_password_history(5);
+    /// # }
+    /// ```
+    pub fn set_password_history(&mut self, count: usize) {
+        self.password_history = count;
+    }
+
+    /// Fails if the password matches the current password of the user or one of the previous ones in their history.
+    #[throws(Error)]
+    pub(crate) async fn check_password_reuse(&self, user: &User, password: &str) {
+        if self.password_history == 0 {
+            return;
+        }
+        let mut hashes = vec![user.password.clone()];
+        let previous = self.previous_passwords();
+        if previous > 0 {
+            hashes.extend(self.conn.get_password_history(user.id, previous).await?);
+        }
+        for hash in hashes {
+            // hashes that can't be verified, such as the ones of LDAP users, never match.
+            if let Ok(true) = self.verify_hash(&hash, password).await {
+                throw!(password_error(match self.password_history {
+                    1 => "The password must be different from your current password.\n".into(),
+                    count => format!("The password must be different from your last {} passwords.\n", count),
+                }));
+            }
+        }
+    }
+
+    /// Adds the replaced hash of a user to their history, and drops the entries that are no longer checked.
+    #[throws(Error)]
+    pub(crate) async fn record_password_history(&self, user_id: i32, hash: &str) {
+        if self.password_history == 0 {
+            return;
+        }
+        let previous = self.previous_passwords();
+        if previous > 0 {
+            self.conn.add_password_history(user_id, hash).await?;
+        }
+        self.conn.prune_password_history(user_id, previous).await?;
+    }
+
+    /// The number of passwords checked besides the current one.
+    fn previous_passwords(&self) -> i64 {
+        self.password_history.saturating_sub(1) as i64
+    }
+}
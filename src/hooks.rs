@@ -0,0 +1,81 @@
+//! Callbacks run around the lifecycle of users, such as sending a welcome email after
+//! they sign up, or cleaning up application data after they are deleted.
+//! Hooks are registered with [`Users::add_hooks`], and run in the order they were added.
+//! ```rust
+//! # use rocket_auth::{Users, User, Error, hooks::UserHooks};
+//! struct Welcome;
+//!
+//! #[rocket::async_trait]
+//! impl UserHooks for Welcome {
+//!     async fn on_signup(&self, _users: &Users, user: &User) -> Result<(), Error> {
+//!         println!("sending a welcome email to {}", user.email());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn func(mut users: Users) {
+//! users.add_hooks(Welcome);
+//! # }
+//! ```
+//! The `before_*` hooks run before the operation takes place, and returning an error from
+//! them vetoes it. The `on_*` hooks run once the operation has taken place. Their errors
+//! are returned to the caller too, but the operation is not rolled back.
+
+use crate::prelude::*;
+
+/// Callbacks invoked by [`Auth`] and [`Users`]. Every method defaults to doing nothing.
+#[async_trait]
+pub trait UserHooks: Send + Sync {
+    /// Runs before a user is created with a [`Signup`] form.
+    async fn before_signup(&self, _users: &Users, _form: &Signup) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after a user signed up.
+    async fn on_signup(&self, _users: &Users, _user: &User) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after the credentials of a user are verified, but before their session is created.
+    async fn before_login(&self, _users: &Users, _user: &User) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after a user logged in.
+    async fn on_login(&self, _users: &Users, _user: &User) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after a user logged out.
+    async fn on_logout(&self, _users: &Users, _user_id: i32) -> Result<()> {
+        Ok(())
+    }
+    /// Runs before the password of a user is changed. `user` holds the new password hash.
+    async fn before_password_change(&self, _users: &Users, _user: &User) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after the password of a user changed.
+    async fn on_password_change(&self, _users: &Users, _user: &User) -> Result<()> {
+        Ok(())
+    }
+    /// Runs before the email of a user is changed. `user` holds the new email.
+    async fn before_email_change(&self, _users: &Users, _user: &User, _old_email: &str) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after the email of a user changed.
+    async fn on_email_change(&self, _users: &Users, _user: &User, _old_email: &str) -> Result<()> {
+        Ok(())
+    }
+    /// Runs before a user is deleted.
+    async fn before_delete(&self, _users: &Users, _user_id: i32) -> Result<()> {
+        Ok(())
+    }
+    /// Runs after a user was deleted, or soft deleted.
+    async fn on_delete(&self, _users: &Users, _user_id: i32) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Users {
+    /// Registers hooks to be invoked by [`Auth`] and [`Users`].
+    /// Several sets of hooks can be registered, and they run in the order they were added.
+    pub fn add_hooks(&mut self, hooks: impl UserHooks + 'static) {
+        self.hooks.push(Box::new(hooks));
+    }
+}
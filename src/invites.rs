@@ -0,0 +1,86 @@
+use crate::audit::Client;
+use crate::oauth::hash_token;
+use crate::prelude::*;
+use crate::user::rand_token;
+
+/// A single use invitation to sign up. Only the sha256 hash of the token is stored.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignupInvite {
+    pub(crate) token_hash: String,
+    pub(crate) email: String,
+    pub(crate) role: Option<String>,
+    pub(crate) expires_at: i64,
+}
+
+impl Users {
+    /// Creates an invitation for `email` to sign up with [`Auth::signup_with_invite`](crate::Auth::signup_with_invite),
+    /// even when open signup is disabled. The returned token can only be used once and expires after `ttl`.
+    /// If `role` is set, it is assigned to the user once they sign up.
+    /// If a mailer is set with [`Users::set_mailer`], the invitation is also sent to `email`.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # use std::time::Duration;
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let one_week = Duration::from_secs(7 * 24 * 60 * 60);
+    /// let token = users.create_invite("new.hire@acme.com", Some("editor"), one_week).await?;
+    /// println!("https://acme.com/signup?invite={}", token);
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn create_invite(&self, email: &str, role: Option<&str>, ttl: Duration) -> String {
+        if !validator::validate_email(email) {
+            throw!(Error::InvalidEmailAddressError)
+        }
+        if let Some(role) = role {
+            if !self.get_roles().await?.iter().any(|name| name == role) {
+                throw!(Error::RoleNotFoundError(role.into()))
+            }
+        }
+        let token = rand_token(32);
+        let invite = SignupInvite {
+            token_hash: hash_token(&token),
+            email: email.to_lowercase(),
+            role: role.map(String::from),
+            expires_at: now() + ttl.as_secs() as i64,
+        };
+        self.conn.create_signup_invite(&invite).await?;
+        let vars = [("email", email), ("token", &token), ("role", role.unwrap_or_default())];
+        self.notify(&self.templates.invitation, &invite.email, &vars).await?;
+        token
+    }
+
+    /// Revokes an invitation that has not been used yet.
+    #[throws(Error)]
+    pub async fn revoke_invite(&self, token: &str) {
+        self.conn.delete_signup_invite(&hash_token(token)).await?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn signup_with_invite(&self, form: &Signup, token: &str, client: &Client) {
+        let token_hash = hash_token(token);
+        let invite = self.conn.get_signup_invite(&token_hash).await?;
+        let invite = match invite {
+            Some(invite) if invite.expires_at > now() => invite,
+            _ => throw!(Error::InvalidInviteError),
+        };
+        if invite.email != form.email.to_lowercase() {
+            throw!(Error::InvalidInviteError)
+        }
+        // invites are single use, so only the request that removes it can sign up with it.
+        if !self.conn.delete_signup_invite(&token_hash).await? {
+            throw!(Error::InvalidInviteError)
+        }
+        let user = match self.signup(form, Some(client)).await {
+            Ok(user) => user,
+            Err(error) => {
+                // the invite can be used again if the signup is rejected, for example for a weak password.
+                self.conn.create_signup_invite(&invite).await?;
+                throw!(error)
+            }
+        };
+        if let Some(role) = invite.role {
+            self.assign_role(user.id, &role).await?;
+        }
+    }
+}
@@ -7,6 +7,7 @@
 //! * `redis`: for storing sessions on a redis server using `redis`.
 //! * `rusqlite`: for interacting with a SQLite database using `rusqlite`.
 //! * `tokio-postgres`: for interacting with a Postgresql database with `tokio-postgres`.
+//! * `ldap`: for verifying passwords against an LDAP directory using `ldap3`.
 //!
 //!
 //! `rocket_auth` uses private cookies to store session data.
@@ -61,8 +62,8 @@
 //! }
 //!
 //! #[get("/logout")]
-//! fn logout(auth: Auth<'_>) {
-//!     auth.logout();
+//! async fn logout(auth: Auth<'_>) {
+//!     auth.logout().await;
 //! }
 //! #[tokio::main]
 //! async fn main() -> Result<(), Error>{
@@ -76,6 +77,19 @@
 //! }
 //! ```
 //!
+//! The same endpoints are available ready-made, so a new service only needs to mount them:
+//! ```rust,no_run
+//! # use rocket_auth::{Users, Error};
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Error> {
+//! # let users = Users::open_sqlite("mydb.db").await?;
+//! rocket::build()
+//!     .mount("/auth", rocket_auth::routes())
+//!     .manage(users);
+//! # Ok(()) }
+//! ```
+//! See [`routes`] for the list of endpoints.
+//!
 //! ## Users struct
 //! The [`Users`] struct administers interactions with the database.
 //! It lets you query, create, modify and delete users.
@@ -97,6 +111,8 @@
 //! # fn main() {}
 //! ```
 //!
+//! Users can also be searched and paginated with [`Users::list`].
+//!
 //! A [`Users`] instance can be constructed by connecting it to the database with the methods [`open_sqlite`](Users::open_sqlite),
 //! [`open_postgres`](Users::open_postgres) or [`open_rusqlite`](Users::open_rusqlite). Furthermore, it can be constructed from a working connection.
 //!
@@ -133,13 +149,93 @@
 //!    format!("Hello {}.", user.email())
 //! }
 //! ```
+//!
+//! Ready-made JSON endpoints to list, disable, log out or delete users can be mounted with [`admin::routes`].
+//!
+//! ## Roles and permissions
+//! Finer grained authorization is possible with roles, created with [`Users::create_role`]
+//! and granted permissions with [`Users::grant_permission`].
+//! Routes can then require a role or a permission with the [`HasRole`] and [`Authorized`] guards.
+//! ```
+//! # use rocket::*;
+//! # use rocket_auth::{Authorized, Permission};
+//! struct CanViewReports;
+//! impl Permission for CanViewReports {
+//!     const NAME: &'static str = "reports:view";
+//! }
+//!
+//! #[get("/reports")]
+//! fn report(user: Authorized<CanViewReports>) -> String {
+//!    format!("Reports for {}.", user.email())
+//! }
+//! ```
+//!
+//! ## Organizations
+//! Multi-tenant applications can group users into organizations with per-organization roles.
+//! See the [`organizations`] module for more information.
+//!
+//! ## Machine clients
+//! Scripts that can only send HTTP Basic credentials can use the [`BasicAuthUser`] guard,
+//! which authenticates every request without creating a session. It must be used along with the
+//! [`BasicAuthChallenge`] fairing, which adds the `WWW-Authenticate` challenge to the requests it rejects.
+//!
+//! ## Authentication gateways
+//! Behind a gateway such as oauth2-proxy, the [`User`] and [`AdminUser`] guards can identify clients
+//! by the `X-Forwarded-Email` header instead of the session cookie. See [`ProxyAuth`] for more information.
+//!
+//! ## Password policy
+//! The requirements passwords must meet, such as their length, character classes or a minimum
+//! [`PasswordStrength`] score, are configured with a [`PasswordPolicy`] in [`Users::set_password_policy`].
+//! Passwords that appeared in data breaches can be rejected too, checking them against a local copy of the
+//! Pwned Passwords dataset loaded with [`Users::load_breached_passwords`].
+//! Reusing recent passwords is forbidden with [`Users::set_password_history`].
+//! Once a password is older than the [`max_age`](PasswordPolicy::max_age) of the policy, or after a change is required with
+//! [`Users::require_password_change`], [`Auth::login_with_status`] returns [`LoginStatus::PasswordChangeRequired`]
+//! and the [`User`] guards reject the session until the password is changed.
+//!
+//! ## Password hashing
+//! Passwords are hashed with Argon2id by default. The parameters, or bcrypt and scrypt for compatibility
+//! with legacy databases, can be chosen with [`Users::set_password_hasher`]. See the [`hashing`] module for more information.
+//!
+//! ## Audit log
+//! Logins, signups, password changes and other authentication events can be recorded
+//! for compliance purposes. See the [`audit`] module for more information.
+//!
+//! ## Lifecycle hooks
+//! Application code can run after users sign up, log in, change their email or password,
+//! or are deleted, and can veto those operations beforehand. See the [`hooks`] module for more information.
+//!
+//! ## Email
+//! Invitations can be delivered by email, with templated messages sent through SMTP,
+//! printed to stdout, or captured in memory for tests. See the [`mail`] module for more information.
+//!
+//! ## OAuth2 server
+//! The [`oauth`] module lets third party applications obtain tokens on behalf of your users.
+//! Clients are registered with [`Users::register_oauth_client`], and the endpoints are mounted with [`oauth::routes`].
 
 
+pub mod admin;
+pub mod audit;
+mod basic;
 mod cookies;
+pub mod credentials;
 mod db;
 mod error;
 mod forms;
+pub mod hashing;
+mod history;
+pub mod hooks;
+mod invites;
+mod listing;
+pub mod mail;
+pub mod oauth;
+pub mod organizations;
 pub mod prelude;
+mod proxy;
+mod roles;
+// rocket generates an unused `pub use` for the `uri!` macro of each route.
+#[allow(unused_imports)]
+mod routes;
 mod session;
 mod user;
 
@@ -151,11 +247,13 @@ use std::fmt::Debug;
 pub use prelude::*;
 
 // pub use language::Language;
-pub use crate::user::auth::Auth;
+pub use crate::user::auth::{Auth, LoginStatus};
 pub use cookies::Session;
 pub use error::Error;
+pub use routes::routes;
 
 /// The `User` guard can be used to restrict content, so that it can only be viewed by authenticated users.
+/// It rejects users who have to change their password, see [`LoginStatus::PasswordChangeRequired`].
 /// ```rust
 /// #
 /// # use rocket::{get};
@@ -174,6 +272,25 @@ pub struct User {
     pub is_admin: bool,
     #[serde(skip_serializing)]
     password: String,
+    #[serde(default)]
+    disabled_at: Option<i64>,
+    #[serde(default)]
+    deleted_at: Option<i64>,
+    #[serde(default)]
+    password_changed_at: Option<i64>,
+    #[serde(default)]
+    must_change_password: bool,
+    #[serde(default, with = "crate::user::profile_json")]
+    profile: Option<String>,
+    username: Option<String>,
+    #[serde(default)]
+    created_at: i64,
+    #[cfg_attr(feature = "sqlx", sqlx(default))]
+    #[serde(default)]
+    roles: NameList,
+    #[cfg_attr(feature = "sqlx", sqlx(default))]
+    #[serde(default)]
+    permissions: NameList,
 }
 
 /// The [`AdminUser`] guard can be used analogously to [`User`].
@@ -199,4 +316,18 @@ impl Debug for AdminUser {
 pub struct Users {
     conn: Box<dyn DBConnection>,
     sess: Box<dyn SessionManager>,
+    verifier: Box<dyn CredentialVerifier>,
+    proxy: Option<ProxyAuth>,
+    audit: Option<Box<dyn audit::AuditSink>>,
+    hooks: Vec<Box<dyn hooks::UserHooks>>,
+    mailer: Option<Box<dyn mail::Mailer>>,
+    templates: mail::Templates,
+    hasher: hashing::PasswordHasher,
+    hashing_permits: std::sync::Arc<tokio::sync::Semaphore>,
+    peppers: hashing::Peppers,
+    password_policy: PasswordPolicy,
+    breached_passwords: Option<BreachedPasswords>,
+    password_history: usize,
+    open_signup: bool,
+    soft_delete: bool,
 }
@@ -0,0 +1,120 @@
+use crate::prelude::*;
+use rocket::form::FromFormField;
+
+/// Narrows down the users returned by [`Users::list`]. Every field is optional,
+/// and the default filter matches every user. It can be parsed from query strings.
+/// ```rust
+/// # use rocket_auth::UserFilter;
+/// let filter = UserFilter {
+///     email: Some("@acme.com".into()),
+///     active: Some(true),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(FromForm, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct UserFilter {
+    /// Only match users whose email contains this text, regardless of case.
+    pub email: Option<String>,
+    /// Only match admins, or only match users that aren't admins.
+    pub is_admin: Option<bool>,
+    /// Only match users that signed up at or after this unix timestamp.
+    pub created_after: Option<i64>,
+    /// Only match users that signed up before this unix timestamp.
+    pub created_before: Option<i64>,
+    /// Only match users that are neither disabled nor deleted, or only match those that are.
+    pub active: Option<bool>,
+}
+
+/// The column users are sorted by. Ties are always broken by id.
+#[derive(FromFormField, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum UserOrder {
+    #[default]
+    #[field(value = "id")]
+    Id,
+    #[field(value = "email")]
+    Email,
+    #[field(value = "created_at")]
+    CreatedAt,
+}
+
+/// Selects a page of the users returned by [`Users::list`].
+/// Pages can be selected either by `offset`, or by passing the id of the last
+/// user of the previous page in `after`. The latter is faster on large tables and
+/// doesn't skip or repeat users when they are created or deleted between requests.
+/// ```rust
+/// # use rocket_auth::{Page, UserOrder};
+/// let page = Page {
+///     limit: 20,
+///     order: UserOrder::CreatedAt,
+///     descending: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct Page {
+    /// The maximum number of users in the page. It defaults to 50.
+    pub limit: u32,
+    /// The number of users to skip. It is ignored when `after` is set.
+    pub offset: u64,
+    /// The id of the last user of the previous page.
+    pub after: Option<i32>,
+    pub order: UserOrder,
+    pub descending: bool,
+}
+
+impl Default for Page {
+    fn default() -> Page {
+        Page {
+            limit: 50,
+            offset: 0,
+            after: None,
+            order: UserOrder::Id,
+            descending: false,
+        }
+    }
+}
+
+/// A page of users, along with the number of users matching the filter across every page.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UserList {
+    pub users: Vec<User>,
+    pub total: u64,
+}
+
+impl UserList {
+    /// The page following `page`, or `None` if this was the last one.
+    pub fn next_page(&self, page: &Page) -> Option<Page> {
+        if self.users.len() < page.limit as usize {
+            return None;
+        }
+        let last = self.users.last()?;
+        Some(Page {
+            after: Some(last.id),
+            offset: 0,
+            ..page.clone()
+        })
+    }
+}
+
+impl Users {
+    /// Lists the users matching `filter`, sorted and paginated according to `page`.
+    /// ```rust
+    /// # use rocket::{get, State};
+    /// # use rocket::serde::json::Json;
+    /// # use rocket_auth::{AdminUser, Error, Page, UserFilter, UserList, Users};
+    /// #[get("/users?<after>&<filter..>")]
+    /// async fn users(filter: UserFilter, after: Option<i32>, _admin: AdminUser, users: &State<Users>) -> Result<Json<UserList>, Error> {
+    ///     let page = Page { after, ..Default::default() };
+    ///     Ok(Json(users.list(&filter, &page).await?))
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub async fn list(&self, filter: &UserFilter, page: &Page) -> UserList {
+        let users = self.conn.list_users(filter, page).await?;
+        let total = self.conn.count_users(filter).await?;
+        UserList { users, total }
+    }
+}
@@ -0,0 +1,226 @@
+//! Outgoing email, used to notify users, for example by sending them their invitations.
+//! Nothing is sent until a mailer is set with [`Users::set_mailer`]:
+//! ```rust
+//! # use rocket_auth::{Users, mail::{StdoutMailer, Template, Templates}};
+//! # fn func(mut users: Users) {
+//! users.set_mailer(StdoutMailer);
+//! users.set_mail_templates(Templates {
+//!     invitation: Template::new(
+//!         "Join Acme",
+//!         "Hi {email}, sign up at https://acme.com/signup?invite={token}",
+//!     ),
+//!     ..Default::default()
+//! });
+//! # }
+//! ```
+//! The [`MemoryMailer`] keeps every message in memory, so tests can assert on what was sent.
+//! With the `smtp` feature enabled, [`SmtpMailer`] delivers messages through an SMTP server.
+#[cfg(feature = "smtp")]
+mod smtp;
+
+#[cfg(feature = "smtp")]
+pub use smtp::SmtpMailer;
+
+use crate::prelude::*;
+use crate::user::rand_token;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A plain text message to a single recipient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers the messages sent by [`Users`].
+/// If delivery fails, the operation that sent the message fails too.
+/// ```rust
+/// # use rocket_auth::{Error, mail::{Email, Mailer}};
+/// struct Log;
+///
+/// #[rocket::async_trait]
+/// impl Mailer for Log {
+///     async fn send(&self, email: &Email) -> Result<(), Error> {
+///         println!("to {}: {}", email.to, email.subject);
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<()>;
+}
+
+/// Prints every message to stdout, which is useful during development.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutMailer;
+
+#[async_trait]
+impl Mailer for StdoutMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        println!("{}", email.to_text());
+        Ok(())
+    }
+}
+
+/// Writes every message to its own `.eml` file inside a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMailer {
+    pub dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> FileMailer {
+        FileMailer { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        let name = format!("{}-{}.eml", now(), rand_token(8));
+        let path = self.dir.join(name);
+        tokio::fs::write(path, email.to_text())
+            .await
+            .map_err(|error| Error::MailError(error.to_string()))
+    }
+}
+
+/// Keeps every message in memory instead of sending it.
+/// ```rust
+/// # use rocket_auth::{Users, Error, mail::MemoryMailer};
+/// # use std::{sync::Arc, time::Duration};
+/// # async fn func(mut users: Users) -> Result<(), Error> {
+/// let mailer = Arc::new(MemoryMailer::default());
+/// users.set_mailer(mailer.clone());
+/// let token = users.create_invite("new.hire@acme.com", None, Duration::from_secs(3600)).await?;
+/// assert!(mailer.last_to("new.hire@acme.com").unwrap().body.contains(&token));
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl MemoryMailer {
+    /// Every message sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// The last message sent to `to`.
+    pub fn last_to(&self, to: &str) -> Option<Email> {
+        let sent = self.sent.lock().unwrap();
+        sent.iter().rev().find(|email| email.to == to).cloned()
+    }
+
+    /// Forgets every message sent so far.
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Mailer + ?Sized> Mailer for std::sync::Arc<T> {
+    async fn send(&self, email: &Email) -> Result<()> {
+        T::send(self, email).await
+    }
+}
+
+impl Email {
+    fn to_text(&self) -> String {
+        format!("To: {}\nSubject: {}\n\n{}\n", self.to, self.subject, self.body)
+    }
+}
+
+/// The subject and body of a message. Variables written as `{name}` are
+/// replaced when the template is rendered, and unknown variables are left as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Template {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Template {
+    pub fn new(subject: impl Into<String>, body: impl Into<String>) -> Template {
+        Template {
+            subject: subject.into(),
+            body: body.into(),
+        }
+    }
+
+    /// Builds the message for `to`, replacing each `{name}` with its value in `vars`.
+    /// ```rust
+    /// # use rocket_auth::mail::Template;
+    /// let template = Template::new("Hi {name}", "Welcome, {name}!");
+    /// let email = template.render("bob@acme.com", &[("name", "Bob")]);
+    /// assert_eq!(email.body, "Welcome, Bob!");
+    /// ```
+    pub fn render(&self, to: &str, vars: &[(&str, &str)]) -> Email {
+        let render = |text: &str| {
+            vars.iter().fold(text.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+        };
+        Email {
+            to: to.into(),
+            subject: render(&self.subject),
+            body: render(&self.body),
+        }
+    }
+}
+
+/// The templates of the messages sent by [`Users`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct Templates {
+    /// Sent by [`Users::create_invite`], with the `{email}`, `{token}` and `{role}` variables.
+    pub invitation: Template,
+    /// Sent by [`Users::invite_member`], with the `{email}`, `{token}`, `{role}` and `{organization}` variables.
+    pub organization_invitation: Template,
+}
+
+impl Default for Templates {
+    fn default() -> Templates {
+        Templates {
+            invitation: Template::new(
+                "You have been invited to sign up",
+                "You have been invited to sign up with {email}. Your invitation code is {token}.",
+            ),
+            organization_invitation: Template::new(
+                "You have been invited to join {organization}",
+                "You have been invited to join {organization} as {role}. Your invitation code is {token}.",
+            ),
+        }
+    }
+}
+
+impl Users {
+    /// Sets the mailer used to notify users. No messages are sent until it is set.
+    pub fn set_mailer(&mut self, mailer: impl Mailer + 'static) {
+        self.mailer = Some(Box::new(mailer));
+    }
+
+    /// Replaces the templates of the messages sent to users.
+    pub fn set_mail_templates(&mut self, templates: Templates) {
+        self.templates = templates;
+    }
+
+    /// Renders `template` and sends it to `to`, if a mailer is set.
+    #[throws(Error)]
+    pub(crate) async fn notify(&self, template: &Template, to: &str, vars: &[(&str, &str)]) {
+        if let Some(mailer) = &self.mailer {
+            mailer.send(&template.render(to, vars)).await?;
+        }
+    }
+}
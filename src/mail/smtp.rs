@@ -0,0 +1,55 @@
+use super::{Email, Mailer};
+use crate::prelude::*;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// Delivers messages through an SMTP server.
+/// ```rust,no_run
+/// # use rocket_auth::{Users, Error, mail::SmtpMailer};
+/// # fn func(mut users: Users) -> Result<(), Error> {
+/// let mailer = SmtpMailer::relay("smtp.acme.com", "mailer", "secret", "Acme <no-reply@acme.com>")?;
+/// users.set_mailer(mailer);
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Connects to `host` over TLS, authenticating with `username` and `password`.
+    /// Messages are sent from the `from` address, such as `"Acme <no-reply@acme.com>"`.
+    #[throws(Error)]
+    pub fn relay(host: &str, username: &str, password: &str, from: &str) -> SmtpMailer {
+        let credentials = Credentials::new(username.into(), password.into());
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+            .credentials(credentials)
+            .build();
+        SmtpMailer::from_transport(transport, from)?
+    }
+
+    /// Uses a transport configured with lettre, for example one using STARTTLS
+    /// or a local server without authentication.
+    #[throws(Error)]
+    pub fn from_transport(transport: AsyncSmtpTransport<Tokio1Executor>, from: &str) -> SmtpMailer {
+        let from = from.parse().map_err(|_| Error::InvalidEmailAddressError)?;
+        SmtpMailer { transport, from }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        let to: Mailbox = email.to.parse().map_err(|_| Error::InvalidEmailAddressError)?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .body(email.body.clone())
+            .map_err(|error| Error::MailError(error.to_string()))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+//! An OAuth2 authorization server, that allows third party applications to obtain
+//! tokens on behalf of the users stored in [`Users`].
+//!
+//! The endpoints are mounted with [`routes`]:
+//! ```rust,no_run
+//! # use rocket_auth::{Users, Error};
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Error> {
+//! let users = Users::open_sqlite("mydb.db").await?;
+//!
+//! rocket::build()
+//!     .mount("/oauth", rocket_auth::oauth::routes())
+//!     .manage(users)
+//!     .launch()
+//!     .await;
+//! # Ok(()) }
+//! ```
+//! The supported grants are authorization code (with PKCE), client credentials and refresh token.
+//! PKCE challenges must use the `S256` method, which is also assumed when `code_challenge_method` is left out.
+//! Users log in through the regular [`Auth`](crate::Auth) guard; the authorization endpoint
+//! reuses their session to record their consent before issuing an authorization code.
+//! Resource routes can then require a valid access token with the [`OAuthAccess`] guard.
+
+// rocket generates an unused `pub use` for the `uri!` macro of each route.
+#[allow(unused_imports)]
+mod routes;
+
+pub use routes::{routes, AuthorizeRequest, AuthorizeResponse, ConsentRequest, TokenResponse};
+
+use crate::hashing::constant_time_eq;
+use crate::prelude::*;
+use crate::user::rand_token;
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+const CODE_LIFETIME: i64 = 10 * 60;
+const ACCESS_TOKEN_LIFETIME: i64 = 60 * 60;
+const REFRESH_TOKEN_LIFETIME: i64 = 30 * 24 * 60 * 60;
+
+pub(crate) const CODE: &str = "code";
+pub(crate) const ACCESS_TOKEN: &str = "access";
+pub(crate) const REFRESH_TOKEN: &str = "refresh";
+
+/// A third party application registered to request tokens from this server.
+/// Clients are created with [`Users::register_oauth_client`].
+/// Confidential clients authenticate with a secret, while public clients
+/// (such as mobile or single page applications) must use PKCE instead.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub(crate) secret_hash: Option<String>,
+    /// The allowed redirect uris, separated by spaces.
+    pub redirect_uris: String,
+    /// The scopes this client is allowed to request, separated by spaces.
+    pub scopes: String,
+}
+
+impl OAuthClient {
+    /// Returns `true` for clients that were registered with a secret.
+    pub fn is_confidential(&self) -> bool {
+        self.secret_hash.is_some()
+    }
+
+    fn allows_redirect(&self, uri: &str) -> bool {
+        self.redirect_uris
+            .split_whitespace()
+            .any(|allowed| allowed == uri)
+    }
+
+    fn verify_secret(&self, secret: Option<&str>) -> bool {
+        match (&self.secret_hash, secret) {
+            (None, _) => true,
+            (Some(hash), Some(secret)) => constant_time_eq(hash.as_bytes(), hash_token(secret).as_bytes()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// A token issued by the server. It is used for authorization codes,
+/// access tokens and refresh tokens, which are told apart by their `kind`.
+/// Only the sha256 hash of the token is stored in the database.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OAuthToken {
+    pub(crate) token_hash: String,
+    pub(crate) kind: String,
+    pub(crate) client_id: String,
+    pub(crate) user_id: Option<i32>,
+    pub(crate) scope: String,
+    pub(crate) redirect_uri: Option<String>,
+    pub(crate) code_challenge: Option<String>,
+    pub(crate) code_challenge_method: Option<String>,
+    pub(crate) expires_at: i64,
+    /// The hash of the authorization code the token descends from, so they can be revoked if the code is reused.
+    pub(crate) grant_hash: Option<String>,
+}
+
+impl OAuthToken {
+    fn new(kind: &str, client_id: &str, user_id: Option<i32>, scope: &str) -> OAuthToken {
+        OAuthToken {
+            token_hash: String::new(),
+            kind: kind.into(),
+            client_id: client_id.into(),
+            user_id,
+            scope: scope.into(),
+            redirect_uri: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            expires_at: 0,
+            grant_hash: None,
+        }
+    }
+
+    fn is_active(&self, kind: &str) -> bool {
+        self.kind == kind && self.expires_at > now()
+    }
+}
+
+/// The [`OAuthAccess`] guard restricts content to clients presenting a valid
+/// access token in the `Authorization: Bearer` header.
+/// ```rust
+/// # use rocket::get;
+/// # use rocket_auth::oauth::OAuthAccess;
+/// #[get("/api/profile")]
+/// fn profile(access: OAuthAccess) -> Option<String> {
+///     if !access.has_scope("profile") {
+///         return None;
+///     }
+///     Some(format!("Hello user {:?}", access.user_id))
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OAuthAccess {
+    pub client_id: String,
+    /// The user that granted access. It is `None` for tokens issued with the client credentials grant.
+    pub user_id: Option<i32>,
+    pub scope: String,
+    pub expires_at: i64,
+}
+
+impl OAuthAccess {
+    /// Checks whether the token was granted the given scope.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        scope_covers(&self.scope, scope)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for OAuthAccess {
+    type Error = Error;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<OAuthAccess, Error> {
+        let users: &Users = match request.rocket().state() {
+            Some(users) => users,
+            None => {
+                return Outcome::Failure((Status::InternalServerError, Error::UnmanagedStateError))
+            }
+        };
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+        let token = match token {
+            Some(token) => users.conn.get_oauth_token(&hash_token(token.trim())).await,
+            None => Err(Error::UnauthorizedError),
+        };
+        match token {
+            Ok(token) if token.is_active(ACCESS_TOKEN) && users.oauth_user_is_active(token.user_id).await => {
+                Outcome::Success(OAuthAccess {
+                    client_id: token.client_id,
+                    user_id: token.user_id,
+                    scope: token.scope,
+                    expires_at: token.expires_at,
+                })
+            }
+            _ => Outcome::Failure((Status::Unauthorized, Error::UnauthorizedError)),
+        }
+    }
+}
+
+/// The errors defined in [RFC 6749](https://datatracker.ietf.org/doc/html/rfc6749#section-5.2).
+/// They are returned to clients as a json object with an `error` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OAuthError {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnauthorizedClient,
+    UnsupportedGrantType,
+    UnsupportedResponseType,
+    InvalidScope,
+    AccessDenied,
+    ServerError,
+}
+
+impl OAuthError {
+    /// The error code sent to the client.
+    pub fn code(&self) -> &'static str {
+        use OAuthError::*;
+        match self {
+            InvalidRequest => "invalid_request",
+            InvalidClient => "invalid_client",
+            InvalidGrant => "invalid_grant",
+            UnauthorizedClient => "unauthorized_client",
+            UnsupportedGrantType => "unsupported_grant_type",
+            UnsupportedResponseType => "unsupported_response_type",
+            InvalidScope => "invalid_scope",
+            AccessDenied => "access_denied",
+            ServerError => "server_error",
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self {
+            OAuthError::InvalidClient => Status::Unauthorized,
+            OAuthError::ServerError => Status::InternalServerError,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+impl From<Error> for OAuthError {
+    fn from(_error: Error) -> OAuthError {
+        OAuthError::ServerError
+    }
+}
+
+impl<'r> Responder<'r, 'static> for OAuthError {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let payload = serde_json::json!({ "error": self.code() }).to_string();
+        let mut response = Response::build();
+        response
+            .status(self.status())
+            .sized_body(payload.len(), Cursor::new(payload))
+            .header(ContentType::JSON)
+            .header(Header::new("Cache-Control", "no-store"));
+        if self == OAuthError::InvalidClient {
+            response.header(Header::new("WWW-Authenticate", "Basic realm=\"oauth\""));
+        }
+        response.ok()
+    }
+}
+
+impl Users {
+    /// Registers a new OAuth2 client allowed to redirect to `redirect_uris` and to request `scopes`.
+    /// Confidential clients get a secret, which is returned along with the client.
+    /// Only its hash is stored, so it must be handed to the client right away.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let (client, secret) = users
+    ///     .register_oauth_client("Reports", &["https://reports.example.com/callback"], &["profile"], true)
+    ///     .await?;
+    /// println!("client_id: {}, client_secret: {:?}", client.client_id, secret);
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn register_oauth_client(
+        &self,
+        name: &str,
+        redirect_uris: &[&str],
+        scopes: &[&str],
+        confidential: bool,
+    ) -> (OAuthClient, Option<String>) {
+        for uri in redirect_uris {
+            if rocket::http::uri::Absolute::parse(uri).is_err() {
+                throw!(Error::InvalidRedirectUriError)
+            }
+        }
+        let secret = if confidential {
+            Some(rand_token(40))
+        } else {
+            None
+        };
+        let client = OAuthClient {
+            client_id: rand_token(24),
+            name: name.into(),
+            secret_hash: secret.as_deref().map(hash_token),
+            redirect_uris: redirect_uris.join(" "),
+            scopes: scopes.join(" "),
+        };
+        self.conn.create_oauth_client(&client).await?;
+        (client, secret)
+    }
+
+    /// Queries an OAuth2 client by its `client_id`.
+    #[throws(Error)]
+    pub async fn get_oauth_client(&self, client_id: &str) -> OAuthClient {
+        self.conn.get_oauth_client(client_id).await?
+    }
+
+    /// Deletes an OAuth2 client, along with every consent and token issued to it.
+    #[throws(Error)]
+    pub async fn delete_oauth_client(&self, client_id: &str) {
+        self.conn.delete_oauth_client(client_id).await?;
+    }
+
+    /// Revokes the consent a user gave to a client. The client will have to ask
+    /// for consent again the next time it requests authorization for this user.
+    #[throws(Error)]
+    pub async fn revoke_oauth_consent(&self, user_id: i32, client_id: &str) {
+        self.conn.delete_oauth_consent(user_id, client_id).await?;
+    }
+
+    /// Whether the user a token was issued for can still use it, since disabled and deleted users lose their access.
+    /// Tokens issued with the client credentials grant don't belong to any user.
+    async fn oauth_user_is_active(&self, user_id: Option<i32>) -> bool {
+        match user_id {
+            Some(user_id) => matches!(self.conn.get_user_by_id(user_id).await, Ok(user) if user.is_active()),
+            None => true,
+        }
+    }
+
+    #[throws(Error)]
+    async fn issue_oauth_token(&self, mut token: OAuthToken, lifetime: i64) -> String {
+        let secret = rand_token(40);
+        token.token_hash = hash_token(&secret);
+        token.expires_at = now() + lifetime;
+        self.conn.create_oauth_token(&token).await?;
+        secret
+    }
+}
+
+pub(crate) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns `true` if every scope in `requested` is also in `granted`.
+fn scope_covers(granted: &str, requested: &str) -> bool {
+    requested
+        .split_whitespace()
+        .all(|scope| granted.split_whitespace().any(|allowed| allowed == scope))
+}
+
+fn verify_pkce(verifier: &str, challenge: &str, method: &str) -> bool {
+    match method {
+        "S256" => {
+            let digest = Sha256::digest(verifier.as_bytes());
+            let expected = base64::encode_config(digest, base64::URL_SAFE_NO_PAD);
+            constant_time_eq(expected.as_bytes(), challenge.as_bytes())
+        }
+        _ => false,
+    }
+}
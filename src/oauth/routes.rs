@@ -0,0 +1,470 @@
+use super::*;
+use crate::basic::basic_credentials;
+use crate::Auth;
+use rocket::form::Form;
+use rocket::http::RawStr;
+use rocket::response::Redirect;
+use rocket::serde::json::Json;
+use rocket::{get, post, Responder, Route, State};
+use serde_json::{json, Value};
+
+/// Returns the OAuth2 endpoints:
+/// * `GET /authorize`: Authorization endpoint. The user must be logged in with [`Auth`].
+///   If they haven't consented yet it returns a [`ConsentRequest`] that the application can render.
+/// * `POST /authorize`: Submits the consent with the `approve` and `csrf_token` form fields, using the same query string.
+/// * `POST /token`: Token endpoint for the `authorization_code`, `refresh_token` and `client_credentials` grants.
+/// * `POST /introspect`: Token introspection ([RFC 7662](https://datatracker.ietf.org/doc/html/rfc7662)).
+/// * `POST /revoke`: Token revocation ([RFC 7009](https://datatracker.ietf.org/doc/html/rfc7009)).
+pub fn routes() -> Vec<Route> {
+    rocket::routes![authorize, authorize_consent, token, introspect, revoke]
+}
+
+/// The query parameters of an authorization request.
+#[derive(FromForm, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizeRequest {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+/// Returned by the authorization endpoint when the user has to approve the request.
+/// The application should ask the user for consent, and then submit
+/// `approve=true` or `approve=false` to `POST /authorize` with the same query string,
+/// along with the `csrf_token`.
+#[derive(Serialize, Debug, Clone)]
+pub struct ConsentRequest {
+    pub client: OAuthClient,
+    pub scope: String,
+    pub request: AuthorizeRequest,
+    /// Binds the consent to this request and to the session of the user,
+    /// so other sites can't submit it on their behalf.
+    pub csrf_token: String,
+}
+
+/// The response of the authorization endpoint.
+#[derive(Responder)]
+pub enum AuthorizeResponse {
+    /// Redirects back to the client, either with a code or with an error.
+    Redirect(Redirect),
+    /// The user has to consent to the request.
+    Consent(Json<ConsentRequest>),
+    /// The client or the redirect uri are invalid, so it is not safe to redirect.
+    Rejected(OAuthError),
+}
+
+/// A successful response of the token endpoint.
+#[derive(Serialize, Debug, Clone)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+}
+
+impl<'r> Responder<'r, 'static> for TokenResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build_from(Json(self).respond_to(request)?)
+            .header(Header::new("Cache-Control", "no-store"))
+            .header(Header::new("Pragma", "no-cache"))
+            .ok()
+    }
+}
+
+#[derive(FromForm)]
+struct Consent {
+    approve: bool,
+    csrf_token: String,
+}
+
+#[derive(FromForm)]
+struct TokenForm {
+    grant_type: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    code_verifier: Option<String>,
+    refresh_token: Option<String>,
+    scope: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(FromForm)]
+struct TokenHint {
+    token: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+/// Client credentials sent through the `Authorization: Basic` header.
+struct BasicCredentials {
+    client_id: String,
+    client_secret: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BasicCredentials {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<BasicCredentials, ()> {
+        match basic_credentials(request) {
+            Some((id, secret)) => Outcome::Success(BasicCredentials {
+                client_id: url_decode(&id),
+                client_secret: url_decode(&secret),
+            }),
+            None => Outcome::Forward(()),
+        }
+    }
+}
+
+#[get("/authorize?<request..>")]
+async fn authorize(request: AuthorizeRequest, auth: Auth<'_>) -> Result<AuthorizeResponse, Error> {
+    authorize_request(request, &auth, None).await
+}
+
+#[post("/authorize?<request..>", data = "<consent>")]
+async fn authorize_consent(
+    request: AuthorizeRequest,
+    consent: Form<Consent>,
+    auth: Auth<'_>,
+) -> Result<AuthorizeResponse, Error> {
+    authorize_request(request, &auth, Some(&consent)).await
+}
+
+#[post("/token", data = "<form>")]
+async fn token(
+    form: Form<TokenForm>,
+    basic: Option<BasicCredentials>,
+    users: &State<Users>,
+) -> Result<TokenResponse, OAuthError> {
+    let client = authenticate_client(users, basic, &form.client_id, &form.client_secret).await?;
+    match form.grant_type.as_str() {
+        "authorization_code" => authorization_code_grant(users, &client, &form).await,
+        "refresh_token" => refresh_token_grant(users, &client, &form).await,
+        "client_credentials" => client_credentials_grant(users, &client, &form).await,
+        _ => Err(OAuthError::UnsupportedGrantType),
+    }
+}
+
+#[post("/introspect", data = "<form>")]
+async fn introspect(
+    form: Form<TokenHint>,
+    basic: Option<BasicCredentials>,
+    users: &State<Users>,
+) -> Result<Json<Value>, OAuthError> {
+    let client = authenticate_client(users, basic, &form.client_id, &form.client_secret).await?;
+    if !client.is_confidential() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+    let token = users
+        .conn
+        .get_oauth_token(&hash_token(&form.token))
+        .await
+        .ok()
+        .filter(|token| token.is_active(ACCESS_TOKEN) || token.is_active(REFRESH_TOKEN));
+    let token = match token {
+        Some(token) => token,
+        None => return Ok(Json(json!({ "active": false }))),
+    };
+    let mut response = json!({
+        "active": true,
+        "client_id": token.client_id,
+        "scope": token.scope,
+        "exp": token.expires_at,
+        "token_type": if token.kind == ACCESS_TOKEN { "Bearer" } else { "refresh_token" },
+    });
+    if let Some(user_id) = token.user_id {
+        response["sub"] = user_id.to_string().into();
+    }
+    Ok(Json(response))
+}
+
+#[post("/revoke", data = "<form>")]
+async fn revoke(
+    form: Form<TokenHint>,
+    basic: Option<BasicCredentials>,
+    users: &State<Users>,
+) -> Result<(), OAuthError> {
+    let client = authenticate_client(users, basic, &form.client_id, &form.client_secret).await?;
+    let hash = hash_token(&form.token);
+    if let Ok(token) = users.conn.get_oauth_token(&hash).await {
+        if token.client_id == client.client_id && token.kind != CODE {
+            users.conn.delete_oauth_token(&hash).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn authorize_request(
+    request: AuthorizeRequest,
+    auth: &Auth<'_>,
+    consent: Option<&Consent>,
+) -> Result<AuthorizeResponse, Error> {
+    let user = auth.get_user().await.ok_or(Error::UnauthenticatedError)?;
+    let users = auth.users;
+    let csrf_token = csrf_token(auth, &request)?;
+    if matches!(consent, Some(consent) if consent.csrf_token != csrf_token) {
+        return Ok(AuthorizeResponse::Rejected(OAuthError::InvalidRequest));
+    }
+    let client = match users.conn.get_oauth_client(&request.client_id).await {
+        Ok(client) => client,
+        Err(_) => return Ok(AuthorizeResponse::Rejected(OAuthError::InvalidClient)),
+    };
+    let redirect_uri = match &request.redirect_uri {
+        Some(uri) if client.allows_redirect(uri) => uri.clone(),
+        None if client.redirect_uris.split_whitespace().count() == 1 => {
+            client.redirect_uris.trim().into()
+        }
+        _ => return Ok(AuthorizeResponse::Rejected(OAuthError::InvalidRequest)),
+    };
+    let reject = |error: OAuthError| {
+        let params = [("error", error.code())];
+        Ok(AuthorizeResponse::Redirect(redirect(
+            &redirect_uri,
+            &params,
+            &request.state,
+        )))
+    };
+
+    if request.response_type != "code" {
+        return reject(OAuthError::UnsupportedResponseType);
+    }
+    let scope = request
+        .scope
+        .clone()
+        .unwrap_or_else(|| client.scopes.clone());
+    if !scope_covers(&client.scopes, &scope) {
+        return reject(OAuthError::InvalidScope);
+    }
+    // the `plain` method would send the verifier itself through the browser, so only `S256` is supported.
+    let method = request.code_challenge_method.as_deref().unwrap_or("S256");
+    let missing_challenge = request.code_challenge.is_none() && !client.is_confidential();
+    if missing_challenge || method != "S256" {
+        return reject(OAuthError::InvalidRequest);
+    }
+
+    let granted = users
+        .conn
+        .get_oauth_consent(user.id, &client.client_id)
+        .await?;
+    match consent.map(|consent| consent.approve) {
+        Some(false) => return reject(OAuthError::AccessDenied),
+        Some(true) => {
+            let scope = merge_scopes(granted.as_deref().unwrap_or(""), &scope);
+            users
+                .conn
+                .set_oauth_consent(user.id, &client.client_id, &scope)
+                .await?;
+        }
+        None => {
+            if !matches!(&granted, Some(granted) if scope_covers(granted, &scope)) {
+                let consent = ConsentRequest {
+                    client,
+                    scope,
+                    request,
+                    csrf_token,
+                };
+                return Ok(AuthorizeResponse::Consent(Json(consent)));
+            }
+        }
+    }
+
+    let code = OAuthToken {
+        redirect_uri: request.redirect_uri.clone(),
+        code_challenge: request.code_challenge.clone(),
+        code_challenge_method: request.code_challenge.as_ref().map(|_| method.into()),
+        ..OAuthToken::new(CODE, &client.client_id, Some(user.id), &scope)
+    };
+    let code = users.issue_oauth_token(code, CODE_LIFETIME).await?;
+    let params = [("code", code.as_str())];
+    Ok(AuthorizeResponse::Redirect(redirect(
+        &redirect_uri,
+        &params,
+        &request.state,
+    )))
+}
+
+async fn authenticate_client(
+    users: &Users,
+    basic: Option<BasicCredentials>,
+    client_id: &Option<String>,
+    client_secret: &Option<String>,
+) -> Result<OAuthClient, OAuthError> {
+    let (client_id, client_secret) = match &basic {
+        Some(basic) => (
+            Some(basic.client_id.as_str()),
+            Some(basic.client_secret.as_str()),
+        ),
+        None => (client_id.as_deref(), client_secret.as_deref()),
+    };
+    let client_id = client_id.ok_or(OAuthError::InvalidClient)?;
+    let client = users
+        .conn
+        .get_oauth_client(client_id)
+        .await
+        .map_err(|_| OAuthError::InvalidClient)?;
+    if client.verify_secret(client_secret) {
+        Ok(client)
+    } else {
+        Err(OAuthError::InvalidClient)
+    }
+}
+
+async fn authorization_code_grant(
+    users: &Users,
+    client: &OAuthClient,
+    form: &TokenForm,
+) -> Result<TokenResponse, OAuthError> {
+    let code = form.code.as_deref().ok_or(OAuthError::InvalidRequest)?;
+    let hash = hash_token(code);
+    let grant = users.conn.get_oauth_token(&hash).await;
+    // codes are single use, so it is removed even if the exchange fails.
+    // Only the request that removes it can exchange it.
+    let consumed = users.conn.delete_oauth_token(&hash).await?;
+    let grant = match grant {
+        Ok(grant) if consumed => grant,
+        _ => {
+            // the code was already used, so the tokens issued for it are revoked (RFC 6749 section 4.1.2).
+            users.conn.delete_oauth_grant(&hash).await?;
+            return Err(OAuthError::InvalidGrant);
+        }
+    };
+
+    if !grant.is_active(CODE)
+        || grant.client_id != client.client_id
+        || grant.redirect_uri != form.redirect_uri
+        || !users.oauth_user_is_active(grant.user_id).await
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+    if let Some(challenge) = &grant.code_challenge {
+        let verifier = form
+            .code_verifier
+            .as_deref()
+            .ok_or(OAuthError::InvalidGrant)?;
+        let method = grant.code_challenge_method.as_deref().unwrap_or("S256");
+        if !verify_pkce(verifier, challenge, method) {
+            return Err(OAuthError::InvalidGrant);
+        }
+    }
+    issue_tokens(users, client, grant.user_id, Some(&hash), grant.scope, true).await
+}
+
+async fn refresh_token_grant(
+    users: &Users,
+    client: &OAuthClient,
+    form: &TokenForm,
+) -> Result<TokenResponse, OAuthError> {
+    let refresh_token = form
+        .refresh_token
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequest)?;
+    let hash = hash_token(refresh_token);
+    let grant = users
+        .conn
+        .get_oauth_token(&hash)
+        .await
+        .map_err(|_| OAuthError::InvalidGrant)?;
+    if !grant.is_active(REFRESH_TOKEN)
+        || grant.client_id != client.client_id
+        || !users.oauth_user_is_active(grant.user_id).await
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+    let scope = match &form.scope {
+        Some(scope) if !scope_covers(&grant.scope, scope) => return Err(OAuthError::InvalidScope),
+        Some(scope) => scope.clone(),
+        None => grant.scope,
+    };
+    // refresh tokens are rotated on every use, so only one request can redeem them.
+    if !users.conn.delete_oauth_token(&hash).await? {
+        return Err(OAuthError::InvalidGrant);
+    }
+    let grant_hash = grant.grant_hash.as_deref();
+    issue_tokens(users, client, grant.user_id, grant_hash, scope, true).await
+}
+
+async fn client_credentials_grant(
+    users: &Users,
+    client: &OAuthClient,
+    form: &TokenForm,
+) -> Result<TokenResponse, OAuthError> {
+    if !client.is_confidential() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+    let scope = form.scope.clone().unwrap_or_else(|| client.scopes.clone());
+    if !scope_covers(&client.scopes, &scope) {
+        return Err(OAuthError::InvalidScope);
+    }
+    issue_tokens(users, client, None, None, scope, false).await
+}
+
+async fn issue_tokens(
+    users: &Users,
+    client: &OAuthClient,
+    user_id: Option<i32>,
+    grant_hash: Option<&str>,
+    scope: String,
+    with_refresh_token: bool,
+) -> Result<TokenResponse, OAuthError> {
+    let new_token = |kind| OAuthToken {
+        grant_hash: grant_hash.map(String::from),
+        ..OAuthToken::new(kind, &client.client_id, user_id, &scope)
+    };
+    let access_token = users
+        .issue_oauth_token(new_token(ACCESS_TOKEN), ACCESS_TOKEN_LIFETIME)
+        .await?;
+    let refresh_token = if with_refresh_token {
+        let refresh_token = users
+            .issue_oauth_token(new_token(REFRESH_TOKEN), REFRESH_TOKEN_LIFETIME)
+            .await?;
+        Some(refresh_token)
+    } else {
+        None
+    };
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: ACCESS_TOKEN_LIFETIME,
+        refresh_token,
+        scope,
+    })
+}
+
+/// Appends the parameters and the state to the query of the redirect uri.
+fn redirect(uri: &str, params: &[(&str, &str)], state: &Option<String>) -> Redirect {
+    let mut uri = uri.to_string();
+    let state = state.as_deref().map(|state| ("state", state));
+    for (key, value) in params.iter().copied().chain(state) {
+        uri.push(if uri.contains('?') { '&' } else { '?' });
+        uri.push_str(key);
+        uri.push('=');
+        uri.push_str(RawStr::new(value).percent_encode().as_str());
+    }
+    Redirect::to(uri)
+}
+
+/// Derives the consent token from the session of the user, so it doesn't need to be stored.
+fn csrf_token(auth: &Auth<'_>, request: &AuthorizeRequest) -> Result<String, Error> {
+    let session = auth.get_session()?;
+    let request = serde_json::to_string(request)?;
+    Ok(hash_token(&format!("{}:{}", session.auth_key, request)))
+}
+
+fn merge_scopes(granted: &str, requested: &str) -> String {
+    let mut scopes: Vec<&str> = granted.split_whitespace().collect();
+    for scope in requested.split_whitespace() {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    scopes.join(" ")
+}
+
+fn url_decode(value: &str) -> String {
+    RawStr::new(value).url_decode_lossy().into_owned()
+}
@@ -0,0 +1,313 @@
+//! Organizations group users into tenants. Each member of an organization has
+//! a role within it, independent of the global roles assigned with [`Users::assign_role`].
+//! ```rust
+//! # use rocket_auth::{Users, Error};
+//! # use std::time::Duration;
+//! # async fn func(users: Users) -> Result<(), Error> {
+//! let org = users.create_organization("Acme Inc.", "acme").await?;
+//! users.add_member(org.id, 1, "owner").await?;
+//! let one_week = Duration::from_secs(7 * 24 * 60 * 60);
+//! let token = users.invite_member(org.id, "colleague@acme.com", "member", one_week).await?;
+//! # Ok(()) }
+//! ```
+//! Routes can then be restricted to the members of the active organization with the [`OrgMember`] guard.
+
+use crate::oauth::hash_token;
+use crate::prelude::*;
+use crate::user::rand_token;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::State;
+
+/// The header used to select the active organization when the route has no `<org>` segment.
+pub const ORGANIZATION_HEADER: &str = "X-Organization";
+
+/// An organization, identified in urls by its `slug`.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Organization {
+    pub id: i32,
+    pub name: String,
+    pub slug: String,
+}
+
+/// The membership of a user in an organization, along with their role within it.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Membership {
+    pub organization_id: i32,
+    pub user_id: i32,
+    pub role: String,
+}
+
+/// A pending invitation to join an organization.
+/// It becomes a [`Membership`] once its token is accepted with [`Users::accept_invitation`].
+/// Only the sha256 hash of the token is stored.
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Invitation {
+    pub organization_id: i32,
+    pub email: String,
+    pub role: String,
+    /// The Unix time in which the invitation expires. It is measured in seconds.
+    pub expires_at: i64,
+    #[serde(skip)]
+    pub(crate) token_hash: String,
+}
+
+/// The [`OrgMember`] guard restricts content to the members of the active organization.
+/// The organization is taken from the `<org>` segment of the route, or otherwise from the
+/// `X-Organization` header, and must contain the slug of the organization.
+/// Clients that are not members are rejected with `403 Forbidden`.
+/// ```rust
+/// # use rocket::get;
+/// # use rocket_auth::organizations::OrgMember;
+/// #[get("/orgs/<org>/projects")]
+/// fn projects(org: &str, member: OrgMember) -> String {
+///     format!("Projects of {} visible to {}", member.organization.name, member.role())
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OrgMember {
+    pub organization: Organization,
+    pub membership: Membership,
+    user: User,
+}
+
+impl OrgMember {
+    /// The role of the user within the active organization.
+    pub fn role(&self) -> &str {
+        &self.membership.role
+    }
+}
+
+impl Debug for OrgMember {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OrgMember({}, {:?})", self.organization.slug, self.user)
+    }
+}
+
+impl Deref for OrgMember {
+    type Target = User;
+    fn deref(&self) -> &User {
+        &self.user
+    }
+}
+
+impl From<OrgMember> for User {
+    fn from(member: OrgMember) -> User {
+        member.user
+    }
+}
+
+/// Finds the slug of the active organization in the route or the headers.
+fn organization_slug<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    let from_route = request.route().and_then(|route| {
+        route
+            .uri
+            .unmounted_origin
+            .path()
+            .segments()
+            .position(|segment| segment == "<org>")
+    });
+    match from_route {
+        Some(position) => request.routed_segment(position),
+        None => request.headers().get_one(ORGANIZATION_HEADER),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for OrgMember {
+    type Error = Error;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<OrgMember, Error> {
+        use rocket::outcome::Outcome::*;
+        let user: User = match request.guard().await {
+            Success(user) => user,
+            Failure(x) => return Failure(x),
+            Forward(x) => return Forward(x),
+        };
+        let users: &State<Users> = match request.guard().await {
+            Success(users) => users,
+            _ => return Failure((Status::InternalServerError, Error::UnmanagedStateError)),
+        };
+        let slug = match organization_slug(request) {
+            Some(slug) => slug,
+            None => return Failure((Status::BadRequest, Error::OrganizationNotFoundError)),
+        };
+        let organization = match users.get_organization(slug).await {
+            Ok(organization) => organization,
+            Err(_) => return Failure((Status::NotFound, Error::OrganizationNotFoundError)),
+        };
+        match users.get_membership(organization.id, user.id).await {
+            Ok(Some(membership)) => Success(OrgMember {
+                organization,
+                membership,
+                user,
+            }),
+            Ok(None) => Failure((Status::Forbidden, Error::NotAMemberError)),
+            Err(error) => Failure((Status::InternalServerError, error)),
+        }
+    }
+}
+
+impl Users {
+    /// Creates an organization. Slugs are used in urls, so they may only contain
+    /// ascii letters, digits, dashes and underscores.
+    #[throws(Error)]
+    pub async fn create_organization(&self, name: &str, slug: &str) -> Organization {
+        let is_valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if slug.is_empty() || !slug.chars().all(is_valid) {
+            throw!(Error::InvalidSlugError)
+        }
+        self.conn.create_organization(name, slug).await?;
+        self.conn.get_organization_by_slug(slug).await?
+    }
+
+    /// Queries an organization by its slug.
+    #[throws(Error)]
+    pub async fn get_organization(&self, slug: &str) -> Organization {
+        self.conn.get_organization_by_slug(slug).await?
+    }
+
+    /// Queries an organization by its id.
+    #[throws(Error)]
+    pub async fn get_organization_by_id(&self, organization_id: i32) -> Organization {
+        self.conn.get_organization_by_id(organization_id).await?
+    }
+
+    /// Deletes an organization along with its memberships and invitations.
+    #[throws(Error)]
+    pub async fn delete_organization(&self, organization_id: i32) {
+        self.conn.delete_organization(organization_id).await?;
+    }
+
+    /// Adds a user to an organization with the given role.
+    /// If the user is already a member, their role is replaced.
+    #[throws(Error)]
+    pub async fn add_member(&self, organization_id: i32, user_id: i32, role: &str) {
+        let membership = Membership {
+            organization_id,
+            user_id,
+            role: role.into(),
+        };
+        self.conn.set_membership(&membership).await?;
+    }
+
+    /// Changes the role of an existing member of an organization.
+    #[throws(Error)]
+    pub async fn set_member_role(&self, organization_id: i32, user_id: i32, role: &str) {
+        let membership = self.conn.get_membership(organization_id, user_id).await?;
+        let mut membership = membership.ok_or(Error::NotAMemberError)?;
+        membership.role = role.into();
+        self.conn.set_membership(&membership).await?;
+    }
+
+    /// Removes a user from an organization.
+    #[throws(Error)]
+    pub async fn remove_member(&self, organization_id: i32, user_id: i32) {
+        self.conn
+            .delete_membership(organization_id, user_id)
+            .await?;
+    }
+
+    /// Queries the membership of a user in an organization, if there is one.
+    #[throws(Error)]
+    pub async fn get_membership(&self, organization_id: i32, user_id: i32) -> Option<Membership> {
+        self.conn.get_membership(organization_id, user_id).await?
+    }
+
+    /// Lists the members of an organization.
+    #[throws(Error)]
+    pub async fn get_members(&self, organization_id: i32) -> Vec<Membership> {
+        self.conn
+            .get_memberships_by_organization(organization_id)
+            .await?
+    }
+
+    /// Lists the organizations a user is a member of.
+    #[throws(Error)]
+    pub async fn get_user_memberships(&self, user_id: i32) -> Vec<Membership> {
+        self.conn.get_memberships_by_user(user_id).await?
+    }
+
+    /// Invites someone to join an organization with the given role. The invited
+    /// person doesn't need to have signed up yet. The returned token can only be used once
+    /// and expires after `ttl`. Inviting the same email again replaces the previous invitation and its token.
+    /// If a mailer is set with [`Users::set_mailer`], the invitation is also sent to `email`.
+    #[throws(Error)]
+    pub async fn invite_member(&self, organization_id: i32, email: &str, role: &str, ttl: Duration) -> String {
+        if !validator::validate_email(email) {
+            throw!(Error::InvalidEmailAddressError)
+        }
+        let organization = self.conn.get_organization_by_id(organization_id).await?;
+        let token = rand_token(32);
+        let invitation = Invitation {
+            organization_id,
+            email: email.to_lowercase(),
+            role: role.into(),
+            expires_at: now() + ttl.as_secs() as i64,
+            token_hash: hash_token(&token),
+        };
+        self.conn.set_invitation(&invitation).await?;
+        let vars = [
+            ("email", email),
+            ("token", &token),
+            ("role", role),
+            ("organization", &organization.name),
+        ];
+        let template = &self.templates.organization_invitation;
+        self.notify(template, &invitation.email, &vars).await?;
+        token
+    }
+
+    /// Lists the pending invitations sent to an email that have not expired.
+    #[throws(Error)]
+    pub async fn get_invitations(&self, email: &str) -> Vec<Invitation> {
+        let mut invitations = self.conn.get_invitations(&email.to_lowercase()).await?;
+        invitations.retain(|invitation| invitation.expires_at > now());
+        invitations
+    }
+
+    /// Accepts an invitation with the token returned by [`Users::invite_member`], making `user` a member of the organization.
+    /// The invitation must have been sent to the email of `user`.
+    /// ```rust
+    /// # use rocket::{post, State};
+    /// # use rocket_auth::{User, Users, Error, organizations::Membership};
+    /// #[post("/invitations/<token>/accept")]
+    /// async fn accept(token: &str, user: User, users: &State<Users>) -> Result<String, Error> {
+    ///     let membership: Membership = users.accept_invitation(token, &user).await?;
+    ///     Ok(format!("You joined as {}", membership.role))
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub async fn accept_invitation(&self, token: &str, user: &User) -> Membership {
+        let token_hash = hash_token(token);
+        let invitation = self.conn.get_invitation_by_token(&token_hash).await?;
+        let invitation = match invitation {
+            Some(invitation) if invitation.expires_at > now() => invitation,
+            _ => throw!(Error::InvitationNotFoundError),
+        };
+        if invitation.email != user.email().to_lowercase() {
+            throw!(Error::InvitationNotFoundError)
+        }
+        // invitations are single use, so only the request that removes it can accept it.
+        if !self.conn.delete_invitation_by_token(&token_hash).await? {
+            throw!(Error::InvitationNotFoundError)
+        }
+        let membership = Membership {
+            organization_id: invitation.organization_id,
+            user_id: user.id,
+            role: invitation.role,
+        };
+        self.conn.set_membership(&membership).await?;
+        membership
+    }
+
+    /// Cancels or declines an invitation.
+    #[throws(Error)]
+    pub async fn delete_invitation(&self, organization_id: i32, email: &str) {
+        self.conn
+            .delete_invitation(organization_id, &email.to_lowercase())
+            .await?;
+    }
+}
@@ -1,11 +1,17 @@
 // pub use crate::language::Language;
+pub use crate::basic::{BasicAuthChallenge, BasicAuthUser};
 pub use crate::cookies::Session;
 pub use crate::error::Error;
-pub use crate::forms::{Login, Signup};
-pub use crate::{AdminUser, Auth, User, Users};
+pub use crate::forms::{BreachedPasswords, Login, PasswordPolicy, PasswordStrength, Signup, SignupWith};
+pub use crate::listing::{Page, UserFilter, UserList, UserOrder};
+pub use crate::proxy::ProxyAuth;
+pub use crate::roles::{Authorized, HasRole, Permission, Role};
+pub use crate::{AdminUser, Auth, LoginStatus, User, Users};
 /// A type alias of result to omit the error type. 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+pub(crate) use crate::credentials::CredentialVerifier;
+pub(crate) use crate::roles::NameList;
 pub(crate) use crate::session::SessionManager;
 pub(crate) use crate::db::DBConnection;
 pub(crate) use async_trait::async_trait;
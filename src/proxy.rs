@@ -0,0 +1,88 @@
+use crate::prelude::*;
+use crate::user::rand_token;
+use ipnet::IpNet;
+use rocket::Request;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Configures `rocket_auth` to run behind a trusted authentication gateway, such as oauth2-proxy.
+/// Once it is set with [`Users::set_proxy_auth`], the [`User`] and [`AdminUser`] guards identify
+/// clients by the headers set by the gateway, and the session cookie is never consulted.
+/// The headers are only trusted on requests coming from one of the `trusted_proxies`,
+/// every other request is rejected. The same goes for the `X-Real-IP` header recorded
+/// in the [audit log](crate::audit).
+/// ```rust
+/// # use rocket_auth::{Users, Error, ProxyAuth};
+/// # fn func(mut users: Users) -> Result<(), Error> {
+/// let proxy = ProxyAuth::new(&["10.0.0.0/8", "127.0.0.1"])?;
+/// users.set_proxy_auth(proxy);
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyAuth {
+    /// The addresses the gateway connects from.
+    pub trusted_proxies: Vec<IpNet>,
+    /// The header carrying the name of the user. It defaults to `X-Forwarded-User`.
+    /// It is only used to identify the user when the email header is missing and it contains an email.
+    pub user_header: String,
+    /// The header carrying the email of the user. It defaults to `X-Forwarded-Email`.
+    pub email_header: String,
+    /// Whether to create a local user the first time they are seen. It defaults to `true`.
+    pub provision: bool,
+}
+
+impl ProxyAuth {
+    /// Creates a configuration trusting the given addresses, which can be
+    /// either ip addresses or ranges in CIDR notation.
+    #[throws(Error)]
+    pub fn new(trusted_proxies: &[&str]) -> ProxyAuth {
+        let trusted_proxies = trusted_proxies
+            .iter()
+            .map(|range| parse_range(range))
+            .collect::<Result<_>>()?;
+        ProxyAuth {
+            trusted_proxies,
+            user_header: "X-Forwarded-User".into(),
+            email_header: "X-Forwarded-Email".into(),
+            provision: true,
+        }
+    }
+
+    pub(crate) fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|range| range.contains(&ip))
+    }
+
+    /// Retrieves the user identified by the gateway headers,
+    /// creating it if it doesn't exist and `provision` is enabled.
+    pub(crate) async fn get_user(&self, request: &Request<'_>, users: &Users) -> Option<User> {
+        let remote = request.remote()?.ip();
+        if !self.is_trusted(remote) {
+            return None;
+        }
+        let headers = request.headers();
+        let email = headers
+            .get_one(&self.email_header)
+            .or_else(|| headers.get_one(&self.user_header))
+            .filter(|email| validator::validate_email(*email))?
+            .to_lowercase();
+        match users.get_by_email(&email).await {
+            Ok(user) => Some(user).filter(User::is_active),
+            Err(_) if self.provision => {
+                users.create_user(&email, &rand_token(40), false).await.ok()?;
+                users.get_by_email(&email).await.ok()
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+#[throws(Error)]
+fn parse_range(range: &str) -> IpNet {
+    if let Ok(range) = IpNet::from_str(range) {
+        range
+    } else if let Ok(ip) = IpAddr::from_str(range) {
+        IpNet::from(ip)
+    } else {
+        throw!(Error::InvalidIpRangeError(range.into()))
+    }
+}
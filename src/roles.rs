@@ -0,0 +1,277 @@
+use crate::prelude::*;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::marker::PhantomData;
+
+/// A role that can be required with the [`HasRole`] guard.
+/// Roles are usually declared as unit structs:
+/// ```rust
+/// use rocket_auth::Role;
+///
+/// struct Editor;
+/// impl Role for Editor {
+///     const NAME: &'static str = "editor";
+/// }
+/// ```
+pub trait Role {
+    /// The name of the role, as passed to [`Users::create_role`].
+    const NAME: &'static str;
+}
+
+/// A permission that can be required with the [`Authorized`] guard.
+/// Permissions are usually declared as unit structs:
+/// ```rust
+/// use rocket_auth::Permission;
+///
+/// struct CanViewReports;
+/// impl Permission for CanViewReports {
+///     const NAME: &'static str = "reports:view";
+/// }
+/// ```
+pub trait Permission {
+    /// The name of the permission, as passed to [`Users::grant_permission`].
+    const NAME: &'static str;
+}
+
+/// The [`HasRole`] guard restricts content to authenticated users that were assigned the role `R`.
+/// Authenticated users lacking the role are rejected with `403 Forbidden`.
+/// ```rust
+/// # use rocket::get;
+/// # use rocket_auth::{HasRole, Role};
+/// struct Editor;
+/// impl Role for Editor {
+///     const NAME: &'static str = "editor";
+/// }
+///
+/// #[get("/drafts")]
+/// fn drafts(user: HasRole<Editor>) -> String {
+///     format!("Drafts of {}", user.email())
+/// }
+/// ```
+pub struct HasRole<R>(User, PhantomData<R>);
+
+/// The [`Authorized`] guard restricts content to authenticated users that were granted the
+/// permission `P` through any of their roles.
+/// Authenticated users lacking the permission are rejected with `403 Forbidden`.
+/// ```rust
+/// # use rocket::get;
+/// # use rocket_auth::{Authorized, Permission};
+/// struct CanViewReports;
+/// impl Permission for CanViewReports {
+///     const NAME: &'static str = "reports:view";
+/// }
+///
+/// #[get("/reports")]
+/// fn report(user: Authorized<CanViewReports>) -> String {
+///     format!("Reports for {}", user.email())
+/// }
+/// ```
+pub struct Authorized<P>(User, PhantomData<P>);
+
+macro_rules! impl_guard {
+    ($guard:ident, $bound:ident, $check:ident) => {
+        impl<T> Debug for $guard<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}{:?}", stringify!($guard), self.0)
+            }
+        }
+
+        impl<T> Deref for $guard<T> {
+            type Target = User;
+            fn deref(&self) -> &User {
+                &self.0
+            }
+        }
+
+        impl<T> From<$guard<T>> for User {
+            fn from(guard: $guard<T>) -> User {
+                guard.0
+            }
+        }
+
+        #[rocket::async_trait]
+        impl<'r, T: $bound> FromRequest<'r> for $guard<T> {
+            type Error = Error;
+            async fn from_request(request: &'r Request<'_>) -> Outcome<$guard<T>, Error> {
+                use rocket::outcome::Outcome::*;
+                let user: User = match request.guard().await {
+                    Success(user) => user,
+                    Failure(x) => return Failure(x),
+                    Forward(x) => return Forward(x),
+                };
+                if user.$check(T::NAME) {
+                    Success($guard(user, PhantomData))
+                } else {
+                    Failure((Status::Forbidden, Error::UnauthorizedError))
+                }
+            }
+        }
+    };
+}
+
+impl_guard!(HasRole, Role, has_role);
+impl_guard!(Authorized, Permission, has_permission);
+
+/// The roles or permissions of a user, stored as a comma separated list.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Default, Debug)]
+#[serde(transparent)]
+pub(crate) struct NameList(Vec<String>);
+
+impl NameList {
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|item| item == name)
+    }
+
+    pub(crate) fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl From<Option<String>> for NameList {
+    fn from(list: Option<String>) -> NameList {
+        let names = list
+            .iter()
+            .flat_map(|list| list.split(','))
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        NameList(names)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> sqlx::Type<DB> for NameList
+where
+    String: sqlx::Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as sqlx::Type<DB>>::type_info()
+    }
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as sqlx::Type<DB>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<'r, DB: sqlx::Database> sqlx::Decode<'r, DB> for NameList
+where
+    Option<String>: sqlx::Decode<'r, DB>,
+{
+    fn decode(
+        value: <DB as sqlx::database::HasValueRef<'r>>::ValueRef,
+    ) -> Result<NameList, sqlx::error::BoxDynError> {
+        let list = <Option<String> as sqlx::Decode<DB>>::decode(value)?;
+        Ok(list.into())
+    }
+}
+
+impl User {
+    /// Checks whether the user was assigned the given role.
+    /// ```rust
+    /// # use rocket::get;
+    /// # use rocket_auth::User;
+    /// #[get("/dashboard")]
+    /// fn dashboard(user: User) -> &'static str {
+    ///     if user.has_role("editor") {
+    ///         "Editor dashboard"
+    ///     } else {
+    ///         "Dashboard"
+    ///     }
+    /// }
+    /// ```
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+
+    /// Checks whether any of the roles of the user grants the given permission.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    /// The roles assigned to the user.
+    pub fn roles(&self) -> &[String] {
+        self.roles.as_slice()
+    }
+
+    /// The permissions granted to the user through their roles.
+    pub fn permissions(&self) -> &[String] {
+        self.permissions.as_slice()
+    }
+}
+
+impl Users {
+    /// Creates a new role. Roles have no permissions until they are granted with [`Users::grant_permission`].
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// users.create_role("editor").await?;
+    /// users.grant_permission("editor", "posts:write").await?;
+    /// users.assign_role(1, "editor").await?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn create_role(&self, role: &str) {
+        validate_name(role)?;
+        self.conn.create_role(role).await?;
+    }
+
+    /// Deletes a role, removing it from every user it was assigned to.
+    #[throws(Error)]
+    pub async fn delete_role(&self, role: &str) {
+        self.conn.delete_role(role).await?;
+    }
+
+    /// Lists the names of every role.
+    #[throws(Error)]
+    pub async fn get_roles(&self) -> Vec<String> {
+        self.conn.get_roles().await?
+    }
+
+    /// Grants a permission to every user with the given role.
+    #[throws(Error)]
+    pub async fn grant_permission(&self, role: &str, permission: &str) {
+        validate_name(permission)?;
+        self.assert_role_exists(role).await?;
+        self.conn.grant_permission(role, permission).await?;
+    }
+
+    /// Revokes a permission from a role.
+    #[throws(Error)]
+    pub async fn revoke_permission(&self, role: &str, permission: &str) {
+        self.conn.revoke_permission(role, permission).await?;
+    }
+
+    /// Lists the permissions granted to a role.
+    #[throws(Error)]
+    pub async fn get_role_permissions(&self, role: &str) -> Vec<String> {
+        self.conn.get_role_permissions(role).await?
+    }
+
+    /// Assigns a role to a user. Assigning a role the user already has does nothing.
+    #[throws(Error)]
+    pub async fn assign_role(&self, user_id: i32, role: &str) {
+        self.assert_role_exists(role).await?;
+        self.conn.assign_role(user_id, role).await?;
+    }
+
+    /// Removes a role from a user.
+    #[throws(Error)]
+    pub async fn unassign_role(&self, user_id: i32, role: &str) {
+        self.conn.unassign_role(user_id, role).await?;
+    }
+
+    #[throws(Error)]
+    async fn assert_role_exists(&self, role: &str) {
+        if !self.conn.get_roles().await?.iter().any(|name| name == role) {
+            throw!(Error::RoleNotFoundError(role.into()))
+        }
+    }
+}
+
+/// Names are stored as comma separated lists, so they cannot contain commas.
+#[throws(Error)]
+fn validate_name(name: &str) {
+    if name.is_empty() || name.contains(',') {
+        throw!(Error::InvalidRoleNameError)
+    }
+}
@@ -0,0 +1,123 @@
+use crate::prelude::*;
+use rocket::form::Form;
+use rocket::serde::json::Json;
+use rocket::{get, post, Route};
+use serde_json::{json, Value};
+
+/// Returns ready-made endpoints to manage the account of the current client:
+/// * `POST /signup`: Signs up with a [`Signup`] form and logs the new user in.
+/// * `POST /login`: Logs in with a [`Login`] form. If the user has to change their password, the returned user has
+///   `must_change_password` set, and the [`User`] guards reject the session until it is changed with `POST /password`.
+/// * `POST /logout`: Logs the current user out.
+/// * `GET /me`: Shows the current user.
+/// * `POST /password`: Changes the password of the current user, with `current_password` and `new_password` fields.
+/// * `POST /delete`: Deletes the account of the current user.
+///
+/// Request bodies can be sent either as json or as forms, according to their `Content-Type`.
+/// Responses are always json, and errors are returned through the [`Error`] responder.
+/// ```rust,no_run
+/// # use rocket_auth::{Users, Error};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Error> {
+/// let users = Users::open_sqlite("mydb.db").await?;
+///
+/// rocket::build()
+///     .mount("/auth", rocket_auth::routes())
+///     .manage(users)
+///     .launch()
+///     .await;
+/// # Ok(()) }
+/// ```
+/// Endpoints can be left out by filtering them by name. For example, to disable account deletion:
+/// ```rust
+/// let routes: Vec<_> = rocket_auth::routes()
+///     .into_iter()
+///     .filter(|route| route.name.as_deref() != Some("delete"))
+///     .collect();
+/// ```
+pub fn routes() -> Vec<Route> {
+    rocket::routes![
+        signup_json,
+        signup_form,
+        login_json,
+        login_form,
+        logout,
+        me,
+        change_password_json,
+        change_password_form,
+        delete,
+    ]
+}
+
+/// The body of `POST /password`.
+#[derive(FromForm, Deserialize, Clone, PartialEq, Eq)]
+struct ChangePassword {
+    current_password: String,
+    new_password: String,
+}
+
+#[post("/signup", format = "json", data = "<form>")]
+async fn signup_json(form: Json<Signup>, auth: Auth<'_>) -> Result<Json<User>> {
+    signup(&form, &auth).await
+}
+
+#[post("/signup", data = "<form>", rank = 2)]
+async fn signup_form(form: Form<Signup>, auth: Auth<'_>) -> Result<Json<User>> {
+    signup(&form, &auth).await
+}
+
+#[post("/login", format = "json", data = "<form>")]
+async fn login_json(form: Json<Login>, auth: Auth<'_>) -> Result<Json<User>> {
+    login(&form, &auth).await
+}
+
+#[post("/login", data = "<form>", rank = 2)]
+async fn login_form(form: Form<Login>, auth: Auth<'_>) -> Result<Json<User>> {
+    login(&form, &auth).await
+}
+
+#[post("/logout")]
+async fn logout(auth: Auth<'_>) -> Result<Json<Value>> {
+    auth.logout().await?;
+    Ok(Json(json!({ "status": "success" })))
+}
+
+#[get("/me")]
+async fn me(auth: Auth<'_>) -> Result<Json<User>> {
+    let user = auth.get_user().await;
+    Ok(Json(user.ok_or(Error::UnauthenticatedError)?))
+}
+
+#[post("/password", format = "json", data = "<form>")]
+async fn change_password_json(form: Json<ChangePassword>, auth: Auth<'_>) -> Result<Json<Value>> {
+    change_password(&form, &auth).await
+}
+
+#[post("/password", data = "<form>", rank = 2)]
+async fn change_password_form(form: Form<ChangePassword>, auth: Auth<'_>) -> Result<Json<Value>> {
+    change_password(&form, &auth).await
+}
+
+#[post("/delete")]
+async fn delete(auth: Auth<'_>) -> Result<Json<Value>> {
+    auth.delete().await?;
+    Ok(Json(json!({ "status": "success" })))
+}
+
+async fn signup(form: &Signup, auth: &Auth<'_>) -> Result<Json<User>> {
+    auth.signup(form).await?;
+    login(&form.clone().into(), auth).await
+}
+
+async fn login(form: &Login, auth: &Auth<'_>) -> Result<Json<User>> {
+    auth.login(form).await?;
+    Ok(Json(auth.users.get_by_login(&form.email).await?))
+}
+
+async fn change_password(form: &ChangePassword, auth: &Auth<'_>) -> Result<Json<Value>> {
+    if !auth.compare_password(&form.current_password).await? {
+        return Err(Error::UnauthorizedError);
+    }
+    auth.change_password(&form.new_password).await?;
+    Ok(Json(json!({ "status": "success" })))
+}
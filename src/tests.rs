@@ -1,5 +1,23 @@
-
-
-
-
-
+#[cfg(feature = "sqlx-sqlite")]
+mod audit;
+#[cfg(any(feature = "sqlx-sqlite", feature = "rusqlite"))]
+mod conflicts;
+mod hashing;
+#[cfg(any(feature = "sqlx-sqlite", feature = "rusqlite"))]
+mod history;
+#[cfg(all(feature = "ldap", feature = "sqlx-sqlite"))]
+mod ldap;
+#[cfg(any(feature = "sqlx-sqlite", feature = "rusqlite"))]
+mod migrations;
+#[cfg(feature = "sqlx-sqlite")]
+#[allow(unused_imports)]
+mod password_change;
+#[cfg(feature = "sqlx-sqlite")]
+mod policy;
+#[cfg(feature = "sqlx-sqlite")]
+// rocket generates an unused `pub use` for the `uri!` macro of each route.
+#[allow(unused_imports)]
+mod oauth;
+#[cfg(feature = "sqlx-sqlite")]
+#[allow(unused_imports)]
+mod profile;
@@ -0,0 +1,47 @@
+//! Checks which address is recorded in the audit log for requests
+//! that carry an `X-Real-IP` header.
+use crate::audit::DatabaseSink;
+use crate::prelude::*;
+use crate::ProxyAuth;
+use rocket::http::{ContentType, Header};
+use rocket::local::asynchronous::Client;
+
+async fn recorded_ip(proxy: Option<ProxyAuth>, remote: &str) -> Option<String> {
+    let mut users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    users.set_audit_sink(DatabaseSink);
+    if let Some(proxy) = proxy {
+        users.set_proxy_auth(proxy);
+    }
+    let rocket = rocket::build()
+        .mount("/auth", crate::routes())
+        .manage(users);
+    let client = Client::tracked(rocket).await.unwrap();
+    client
+        .post("/auth/signup")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Real-IP", "203.0.113.7"))
+        .remote(remote.parse().unwrap())
+        .body(r#"{"email": "alice@example.com", "password": "Password123"}"#)
+        .dispatch()
+        .await;
+    let users = client.rocket().state::<Users>().unwrap();
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    let entries = users.audit_log(user.id, &Page::default()).await.unwrap();
+    entries[0].ip.clone()
+}
+
+#[rocket::async_test]
+async fn real_ip_header() {
+    let ip = recorded_ip(None, "127.0.0.1:8000").await;
+    assert_eq!(ip.as_deref(), Some("127.0.0.1"));
+
+    let proxy = || Some(ProxyAuth::new(&["10.0.0.0/8"]).unwrap());
+    let ip = recorded_ip(proxy(), "192.0.2.1:8000").await;
+    assert_eq!(ip.as_deref(), Some("192.0.2.1"));
+    let ip = recorded_ip(proxy(), "10.1.2.3:8000").await;
+    assert_eq!(ip.as_deref(), Some("203.0.113.7"));
+}
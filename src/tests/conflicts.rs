@@ -0,0 +1,34 @@
+//! Checks that inserting a user whose email or username is taken, as a concurrent
+//! signup would after passing the checks of the first one, reports which one is taken.
+use crate::prelude::*;
+
+async fn assert_conflicts(users: Users) {
+    users.create_table().await.unwrap();
+    let insert = |email: &'static str, username: Option<&'static str>| {
+        users.insert_user(email, "Password123", false, username, None)
+    };
+    insert("alice@example.com", Some("alice")).await.unwrap();
+    insert("username@example.com", None).await.unwrap();
+
+    let error = insert("alice@example.com", None).await.unwrap_err();
+    assert!(matches!(error, Error::EmailAlreadyExists), "{:?}", error);
+    let error = insert("username@example.com", None).await.unwrap_err();
+    assert!(matches!(error, Error::EmailAlreadyExists), "{:?}", error);
+    let error = insert("bob@example.com", Some("Alice")).await.unwrap_err();
+    assert!(matches!(error, Error::UsernameAlreadyExists), "{:?}", error);
+}
+
+#[cfg(feature = "sqlx-sqlite")]
+#[rocket::async_test]
+async fn sqlx_sqlite() {
+    let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+    assert_conflicts(pool.into()).await;
+}
+
+#[cfg(feature = "rusqlite")]
+#[rocket::async_test]
+async fn rusqlite() {
+    use tokio::sync::Mutex;
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    assert_conflicts(Mutex::new(conn).into()).await;
+}
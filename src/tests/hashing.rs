@@ -0,0 +1,182 @@
+//! Checks that passwords hashed with each algorithm can be verified, and that
+//! hashes are flagged for rehashing once the algorithm or its parameters change.
+use crate::hashing::{self, Argon2Variant, PasswordHasher};
+use crate::prelude::*;
+
+const ARGON2: PasswordHasher = PasswordHasher::Argon2 {
+    variant: Argon2Variant::Argon2id,
+    mem_cost: 1024,
+    time_cost: 1,
+    lanes: 1,
+};
+const BCRYPT: PasswordHasher = PasswordHasher::Bcrypt { cost: 4 };
+const SCRYPT: PasswordHasher = PasswordHasher::Scrypt {
+    log_n: 4,
+    r: 8,
+    p: 1,
+};
+
+// hashes of "lètmein", as stored by Django.
+const DJANGO_PBKDF2_SHA256: &str =
+    "pbkdf2_sha256$36000$seasalt$mEUPPFJkT/xtwDU8rB7Q+puHRZnR07WRjerTkt/3HI0=";
+const DJANGO_BCRYPT_SHA256: &str =
+    "bcrypt_sha256$$2b$04$Ab1cD2eF3gH4iJ5kL6mN7.J4qJbEoA9ZOfOgi7mbPzx9wkSdJCZ8y";
+const DJANGO_BCRYPT: &str = "bcrypt$$2b$04$Ab1cD2eF3gH4iJ5kL6mN7.gBWUB7Oe1JNqJdFK1Vg4zCNQPcLukUi";
+
+#[test]
+fn round_trips() {
+    for hasher in [ARGON2, BCRYPT, SCRYPT] {
+        let hash = hasher.hash("Password123").unwrap();
+        assert!(hashing::verify(&hash, "Password123").unwrap(), "{}", hash);
+        assert!(!hashing::verify(&hash, "Password124").unwrap(), "{}", hash);
+        // every hash is salted.
+        assert_ne!(hash, hasher.hash("Password123").unwrap());
+        assert!(!hasher.needs_rehash(&hash), "{}", hash);
+    }
+}
+
+#[test]
+fn rehash_on_other_parameters() {
+    let stronger = [
+        PasswordHasher::argon2id(2048, 1, 1),
+        PasswordHasher::argon2id(1024, 2, 1),
+        PasswordHasher::argon2id(1024, 1, 2),
+        PasswordHasher::Argon2 {
+            variant: Argon2Variant::Argon2i,
+            mem_cost: 1024,
+            time_cost: 1,
+            lanes: 1,
+        },
+        PasswordHasher::Bcrypt { cost: 5 },
+        PasswordHasher::Scrypt {
+            log_n: 5,
+            r: 8,
+            p: 1,
+        },
+        PasswordHasher::Scrypt {
+            log_n: 4,
+            r: 4,
+            p: 1,
+        },
+    ];
+    for hasher in [ARGON2, BCRYPT, SCRYPT] {
+        let hash = hasher.hash("Password123").unwrap();
+        for other in stronger {
+            assert!(other.needs_rehash(&hash), "{:?} {}", other, hash);
+        }
+    }
+}
+
+#[test]
+fn rehash_on_other_algorithm() {
+    let hashers = [ARGON2, BCRYPT, SCRYPT];
+    for hasher in hashers {
+        let hash = hasher.hash("Password123").unwrap();
+        for other in hashers.iter().filter(|other| **other != hasher) {
+            assert!(other.needs_rehash(&hash), "{:?} {}", other, hash);
+        }
+    }
+    // bcrypt hashes of other versions are kept as long as the cost matches.
+    let hash = BCRYPT
+        .hash("Password123")
+        .unwrap()
+        .replacen("$2b$", "$2y$", 1);
+    assert!(hashing::verify(&hash, "Password123").unwrap());
+    assert!(!BCRYPT.needs_rehash(&hash));
+}
+
+#[test]
+fn django_hashes() {
+    for hash in [DJANGO_PBKDF2_SHA256, DJANGO_BCRYPT_SHA256, DJANGO_BCRYPT] {
+        assert!(hashing::verify(hash, "lètmein").unwrap(), "{}", hash);
+        assert!(!hashing::verify(hash, "letmein").unwrap(), "{}", hash);
+        assert!(PasswordHasher::default().needs_rehash(hash));
+    }
+    let truncated = "pbkdf2_sha256$36000$seasalt";
+    assert!(matches!(
+        hashing::verify(truncated, "lètmein"),
+        Err(Error::UnsupportedHashError)
+    ));
+}
+
+#[cfg(feature = "sqlx-sqlite")]
+#[rocket::async_test]
+async fn rehash_on_login() {
+    let mut users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    users.set_password_hasher(ARGON2);
+    users
+        .conn
+        .create_user("alice@example.com", DJANGO_PBKDF2_SHA256, false, None, None)
+        .await
+        .unwrap();
+    let login = |password: &str| Login {
+        email: "alice@example.com".into(),
+        password: password.into(),
+    };
+
+    assert!(users.verify_credentials(&login("letmein")).await.is_err());
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    assert_eq!(user.password, DJANGO_PBKDF2_SHA256);
+
+    users.verify_credentials(&login("lètmein")).await.unwrap();
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    assert!(user.password.starts_with("$argon2id$"), "{}", user.password);
+    assert!(!users.needs_rehash(&user.password));
+    users.verify_credentials(&login("lètmein")).await.unwrap();
+}
+
+#[cfg(feature = "sqlx-sqlite")]
+#[rocket::async_test]
+async fn pepper_rotation() {
+    let mut users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    users.set_password_hasher(ARGON2);
+    users.set_pepper(1, "old pepper");
+    users
+        .create_user("alice@example.com", "Password123", false)
+        .await
+        .unwrap();
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    assert!(
+        user.password.starts_with("pepper1$$argon2id$"),
+        "{}",
+        user.password
+    );
+    assert!(matches!(
+        hashing::verify(&user.password, "Password123"),
+        Err(Error::UnknownPepperError(1))
+    ));
+
+    users.set_pepper(2, "new pepper");
+    assert!(users.verify_password(&user, "Password123").await.unwrap());
+    assert!(!users.verify_password(&user, "Password124").await.unwrap());
+    assert!(users.needs_rehash(&user.password));
+
+    let login = Login {
+        email: "alice@example.com".into(),
+        password: "Password123".into(),
+    };
+    users.verify_credentials(&login).await.unwrap();
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    assert!(
+        user.password.starts_with("pepper2$$argon2id$"),
+        "{}",
+        user.password
+    );
+    assert!(!users.needs_rehash(&user.password));
+
+    // once rehashed, the old pepper is no longer needed.
+    users.peppers = Default::default();
+    users.set_pepper(2, "new pepper");
+    assert!(users.verify_password(&user, "Password123").await.unwrap());
+    // a hash is only verified with the pepper of its version.
+    users.set_pepper(2, "another pepper");
+    assert!(!users.verify_password(&user, "Password123").await.unwrap());
+}
@@ -0,0 +1,55 @@
+//! Checks that the last passwords of a user can't be reused,
+//! and that only the ones that are still checked are kept.
+use crate::hashing::PasswordHasher;
+use crate::prelude::*;
+
+async fn assert_history(mut users: Users) {
+    users.create_table().await.unwrap();
+    users.set_password_hasher(PasswordHasher::Bcrypt { cost: 4 });
+    users.set_password_history(3);
+    users
+        .create_user("alice@example.com", "Password1", false)
+        .await
+        .unwrap();
+    let mut user = users.get_by_email("alice@example.com").await.unwrap();
+    let history = |user: &User| users.conn.get_password_history(user.id, 100);
+
+    for password in ["Password2", "Password3"] {
+        users.set_password(&mut user, password).await.unwrap();
+        users.modify(&user).await.unwrap();
+    }
+    assert_eq!(history(&user).await.unwrap().len(), 2);
+    for password in ["Password1", "Password2", "Password3"] {
+        let error = users.set_password(&mut user, password).await.unwrap_err();
+        assert!(
+            matches!(error, Error::FormValidationErrors(_)),
+            "{:?}",
+            error
+        );
+    }
+
+    users.set_password(&mut user, "Password4").await.unwrap();
+    users.modify(&user).await.unwrap();
+    // the oldest password is pruned from the history once it is out of the window.
+    let hashes = history(&user).await.unwrap();
+    assert_eq!(hashes.len(), 2);
+    for hash in hashes {
+        assert!(!users.verify_hash(&hash, "Password1").await.unwrap());
+    }
+    users.set_password(&mut user, "Password1").await.unwrap();
+}
+
+#[cfg(feature = "sqlx-sqlite")]
+#[rocket::async_test]
+async fn sqlx_sqlite() {
+    let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+    assert_history(pool.into()).await;
+}
+
+#[cfg(feature = "rusqlite")]
+#[rocket::async_test]
+async fn rusqlite() {
+    use tokio::sync::Mutex;
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    assert_history(Mutex::new(conn).into()).await;
+}
@@ -0,0 +1,188 @@
+//! Tests [`LdapVerifier`] against a minimal in-process LDAP server,
+//! which answers simple binds and base searches from an in-memory directory.
+use crate::credentials::{CredentialVerifier, LdapBind, LdapVerifier};
+use crate::prelude::*;
+use ldap3::asn1::{parse_tag, StructureTag, TagClass};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const ADMINS: &str = "cn=admins,ou=groups,dc=example,dc=com";
+const EDITORS: &str = "cn=editors,ou=groups,dc=example,dc=com";
+
+/// Maps the DN of each entry to its password and its groups.
+type Directory = Arc<Mutex<HashMap<String, (String, Vec<String>)>>>;
+
+async fn serve(directory: Directory) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ldap://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle(stream, directory.clone()));
+        }
+    });
+    url
+}
+
+async fn handle(mut stream: TcpStream, directory: Directory) {
+    let mut buffer = vec![];
+    let mut chunk = [0; 1024];
+    loop {
+        let (message, len) = match parse_tag(&buffer) {
+            Ok((rest, message)) => (message, buffer.len() - rest.len()),
+            Err(_) => match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(read) => {
+                    buffer.extend_from_slice(&chunk[..read]);
+                    continue;
+                }
+            },
+        };
+        buffer.drain(..len);
+        let mut message = message.expect_constructed().unwrap().into_iter();
+        let id = message.next().unwrap().expect_primitive().unwrap();
+        let operation = message.next().unwrap();
+        assert_eq!(operation.class, TagClass::Application);
+        let responses = match operation.id {
+            0 => vec![bind(&directory, operation)],
+            3 => search(&directory, operation),
+            _ => return,
+        };
+        for response in responses {
+            let message = ber(0x30, &[ber(0x02, &id), response].concat());
+            stream.write_all(&message).await.unwrap();
+        }
+    }
+}
+
+fn bind(directory: &Directory, operation: StructureTag) -> Vec<u8> {
+    let mut fields = operation.expect_constructed().unwrap().into_iter().skip(1);
+    let dn = string(fields.next().unwrap());
+    let password = string(fields.next().unwrap());
+    let valid = match directory.lock().unwrap().get(&dn) {
+        Some((expected, _)) => *expected == password,
+        None => false,
+    };
+    // 49 is invalidCredentials.
+    ber(0x61, &result(if valid { 0 } else { 49 }))
+}
+
+/// Returns the entry, if it exists, followed by the result of the search.
+fn search(directory: &Directory, operation: StructureTag) -> Vec<Vec<u8>> {
+    let dn = string(operation.expect_constructed().unwrap().remove(0));
+    let mut responses = vec![];
+    if let Some((_, groups)) = directory.lock().unwrap().get(&dn) {
+        let groups: Vec<u8> = groups
+            .iter()
+            .flat_map(|group| ber(0x04, group.as_bytes()))
+            .collect();
+        let attribute = ber(0x30, &[ber(0x04, b"memberOf"), ber(0x31, &groups)].concat());
+        let entry = [ber(0x04, dn.as_bytes()), ber(0x30, &attribute)].concat();
+        responses.push(ber(0x64, &entry));
+    }
+    responses.push(ber(0x65, &result(0)));
+    responses
+}
+
+fn result(code: u8) -> Vec<u8> {
+    [ber(0x0a, &[code]), ber(0x04, b""), ber(0x04, b"")].concat()
+}
+
+fn string(tag: StructureTag) -> String {
+    String::from_utf8(tag.expect_primitive().unwrap()).unwrap()
+}
+
+/// Encodes a BER value with a single byte tag.
+fn ber(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if content.len() < 128 {
+        out.push(content.len() as u8);
+    } else {
+        let len = (content.len() as u32).to_be_bytes();
+        let len: Vec<u8> = len.iter().copied().skip_while(|byte| *byte == 0).collect();
+        out.push(0x80 | len.len() as u8);
+        out.extend(len);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+async fn setup(groups: &[&str]) -> (Users, LdapVerifier, Directory) {
+    let directory: Directory = Default::default();
+    directory.lock().unwrap().insert(
+        "uid=alice,ou=people,dc=example,dc=com".into(),
+        (
+            "secret".into(),
+            groups.iter().map(|group| group.to_string()).collect(),
+        ),
+    );
+    let url = serve(directory.clone()).await;
+    let users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    users.create_role("editor").await.unwrap();
+    let template = LdapBind::Template("uid={username},ou=people,dc=example,dc=com".into());
+    let mut ldap = LdapVerifier::new(&url, template);
+    ldap.admin_group = Some(ADMINS.into());
+    ldap.role_groups = vec![(EDITORS.into(), "editor".into())];
+    (users, ldap, directory)
+}
+
+#[rocket::async_test]
+async fn bind_failure() {
+    let (users, ldap, _) = setup(&[]).await;
+    let result = ldap.verify(&users, "alice@example.com", "wrong").await;
+    assert!(matches!(result, Err(Error::UnauthorizedError)));
+    let result = ldap.verify(&users, "bob@example.com", "secret").await;
+    assert!(matches!(result, Err(Error::UnauthorizedError)));
+    let result = ldap.verify(&users, "alice@example.com", "").await;
+    assert!(matches!(result, Err(Error::UnauthorizedError)));
+    assert!(users.get_by_email("alice@example.com").await.is_err());
+}
+
+#[rocket::async_test]
+async fn provisions_users() {
+    let (users, mut ldap, _) = setup(&[]).await;
+    ldap.provision = false;
+    let result = ldap.verify(&users, "alice@example.com", "secret").await;
+    assert!(matches!(result, Err(Error::EmailDoesNotExist(_))));
+
+    ldap.provision = true;
+    let user = ldap
+        .verify(&users, "alice@example.com", "secret")
+        .await
+        .unwrap();
+    assert_eq!(user.email(), "alice@example.com");
+    assert!(!user.is_admin);
+    assert_eq!(
+        user.id,
+        users.get_by_email("alice@example.com").await.unwrap().id
+    );
+}
+
+#[rocket::async_test]
+async fn group_sync() {
+    let (users, ldap, directory) = setup(&[ADMINS, EDITORS]).await;
+    let user = ldap
+        .verify(&users, "alice@example.com", "secret")
+        .await
+        .unwrap();
+    assert!(user.is_admin);
+    assert!(user.has_role("editor"));
+
+    for (_, groups) in directory.lock().unwrap().values_mut() {
+        groups.clear();
+    }
+    let user = ldap
+        .verify(&users, "alice@example.com", "secret")
+        .await
+        .unwrap();
+    assert!(!user.is_admin);
+    assert!(!user.has_role("editor"));
+    let stored = users.get_by_id(user.id).await.unwrap();
+    assert!(!stored.is_admin);
+    assert!(!stored.has_role("editor"));
+}
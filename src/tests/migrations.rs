@@ -0,0 +1,107 @@
+//! Opens databases created with the first version of the `users` table,
+//! and checks that initializing them adds the columns added since.
+use crate::prelude::*;
+use std::path::PathBuf;
+
+const BASELINE_SCHEMA: &str = "
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY,
+    email TEXT UNIQUE,
+    password TEXT NOT NULL,
+    is_admin BOOL DEFAULT 0
+);
+INSERT INTO users (email, password, is_admin) VALUES ('legacy@example.com', 'hash', 0);
+INSERT INTO users (email, password, is_admin) VALUES ('other@example.com', 'hash', 0);
+";
+
+const NEW_COLUMNS: [&str; 7] = [
+    "disabled_at",
+    "deleted_at",
+    "profile",
+    "username",
+    "created_at",
+    "password_changed_at",
+    "must_change_password",
+];
+
+fn database_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rocket_auth_{}_{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn assert_migrated(columns: &[String]) {
+    for column in NEW_COLUMNS {
+        assert!(
+            columns.iter().any(|name| name == column),
+            "{} is missing",
+            column
+        );
+    }
+}
+
+async fn assert_loads_legacy_user(users: &Users) {
+    let user = users.get_by_email("legacy@example.com").await.unwrap();
+    assert_eq!(user.password_changed_at(), None);
+    assert!(!user.must_change_password());
+}
+
+#[cfg(feature = "sqlx-sqlite")]
+#[rocket::async_test]
+async fn migrates_sqlx_sqlite() {
+    use sqlx::{Connection, SqliteConnection};
+    let path = database_path("sqlx");
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+    let mut conn = SqliteConnection::connect(&url).await.unwrap();
+    sqlx::query(BASELINE_SCHEMA)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let users = Users::open_sqlite(&url).await.unwrap();
+    // initializing twice must not add the columns again.
+    users.create_table().await.unwrap();
+
+    let columns = sqlx::query_scalar("SELECT name FROM pragma_table_info('users')")
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+    assert_migrated(&columns);
+    assert_loads_legacy_user(&users).await;
+    let set_username = |id: i32, username: &'static str| {
+        sqlx::query("UPDATE users SET username = ?1 WHERE id = ?2")
+            .bind(username)
+            .bind(id)
+    };
+    set_username(1, "Legacy").execute(&mut conn).await.unwrap();
+    assert!(set_username(2, "legacy").execute(&mut conn).await.is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "rusqlite")]
+#[rocket::async_test]
+async fn migrates_rusqlite() {
+    let path = database_path("rusqlite");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(BASELINE_SCHEMA).unwrap();
+
+    let users = Users::open_rusqlite(&path).unwrap();
+    users.create_table().await.unwrap();
+
+    let columns = conn
+        .prepare("SELECT name FROM pragma_table_info('users')")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .unwrap();
+    assert_migrated(&columns);
+    assert_loads_legacy_user(&users).await;
+    let set_username = "UPDATE users SET username = ?1 WHERE id = ?2";
+    conn.execute(set_username, rusqlite::params!["Legacy", 1])
+        .unwrap();
+    assert!(conn
+        .execute(set_username, rusqlite::params!["legacy", 2])
+        .is_err());
+    std::fs::remove_file(path).unwrap();
+}
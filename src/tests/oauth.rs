@@ -0,0 +1,346 @@
+//! Tests the OAuth2 endpoints through a local Rocket client, from the consent
+//! of a logged in user to the exchange and rotation of the issued tokens.
+use crate::oauth::{self, OAuthAccess, OAuthClient};
+use crate::prelude::*;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::asynchronous::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const REDIRECT_URI: &str = "https://client.example.com/callback";
+
+#[rocket::get("/api")]
+fn api(access: OAuthAccess) -> String {
+    format!("{:?}", access.user_id)
+}
+
+struct Setup {
+    client: Client,
+    confidential: OAuthClient,
+    secret: String,
+    public: OAuthClient,
+}
+
+async fn setup() -> Setup {
+    let users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    users
+        .create_user("alice@example.com", "Password123", false)
+        .await
+        .unwrap();
+    let (confidential, secret) = users
+        .register_oauth_client("Reports", &[REDIRECT_URI], &["profile"], true)
+        .await
+        .unwrap();
+    let (public, _) = users
+        .register_oauth_client("Mobile", &[REDIRECT_URI], &["profile"], false)
+        .await
+        .unwrap();
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![api])
+        .mount("/auth", crate::routes())
+        .mount("/oauth", oauth::routes())
+        .manage(users);
+    let client = Client::tracked(rocket).await.unwrap();
+    let status = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{"email": "alice@example.com", "password": "Password123"}"#)
+        .dispatch()
+        .await
+        .status();
+    assert_eq!(status, Status::Ok);
+    Setup {
+        client,
+        confidential,
+        secret: secret.unwrap(),
+        public,
+    }
+}
+
+fn authorize_uri(client: &OAuthClient, extra: &str) -> String {
+    format!(
+        "/oauth/authorize?response_type=code&client_id={}&redirect_uri={}&scope=profile{}",
+        client.client_id, REDIRECT_URI, extra
+    )
+}
+
+/// Asks for consent and approves it, returning the redirect location.
+async fn approve(client: &Client, uri: &str) -> String {
+    let response = client.get(uri.to_string()).dispatch().await;
+    let consent: Value = response.into_json().await.unwrap();
+    let csrf_token = consent["csrf_token"].as_str().unwrap();
+    let response = client
+        .post(uri.to_string())
+        .header(ContentType::Form)
+        .body(format!("approve=true&csrf_token={}", csrf_token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::SeeOther);
+    response.headers().get_one("Location").unwrap().to_string()
+}
+
+fn query_param(location: &str, name: &str) -> Option<String> {
+    let query = location.split_once('?')?.1;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        Some(value.to_string()).filter(|_| key == name)
+    })
+}
+
+async fn post_token(client: &Client, body: String) -> (Status, Value) {
+    let response = client
+        .post("/oauth/token")
+        .header(ContentType::Form)
+        .body(body)
+        .dispatch()
+        .await;
+    (response.status(), response.into_json().await.unwrap())
+}
+
+async fn exchange_code(setup: &Setup, code: &str) -> (Status, Value) {
+    let body = format!(
+        "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&client_secret={}",
+        code, REDIRECT_URI, setup.confidential.client_id, setup.secret
+    );
+    post_token(&setup.client, body).await
+}
+
+async fn refresh(setup: &Setup, refresh_token: &str) -> (Status, Value) {
+    let body = format!(
+        "grant_type=refresh_token&refresh_token={}&client_id={}&client_secret={}",
+        refresh_token, setup.confidential.client_id, setup.secret
+    );
+    post_token(&setup.client, body).await
+}
+
+async fn call_api(client: &Client, access_token: &Value) -> Status {
+    let authorization = format!("Bearer {}", access_token.as_str().unwrap());
+    client
+        .get("/api")
+        .header(Header::new("Authorization", authorization))
+        .dispatch()
+        .await
+        .status()
+}
+
+fn s256(verifier: &str) -> String {
+    base64::encode_config(Sha256::digest(verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+#[rocket::async_test]
+async fn authorization_code() {
+    let setup = setup().await;
+    let location = approve(
+        &setup.client,
+        &authorize_uri(&setup.confidential, "&state=xyz"),
+    )
+    .await;
+    assert!(location.starts_with(REDIRECT_URI));
+    assert_eq!(query_param(&location, "state").as_deref(), Some("xyz"));
+    let code = query_param(&location, "code").unwrap();
+
+    let (status, tokens) = exchange_code(&setup, &code).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(tokens["token_type"], "Bearer");
+    assert_eq!(tokens["scope"], "profile");
+    assert_eq!(
+        call_api(&setup.client, &tokens["access_token"]).await,
+        Status::Ok
+    );
+
+    // once consented, the user is redirected right away.
+    let uri = authorize_uri(&setup.confidential, "");
+    let response = setup.client.get(uri).dispatch().await;
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[rocket::async_test]
+async fn consent_csrf() {
+    let setup = setup().await;
+    let uri = authorize_uri(&setup.confidential, "");
+    setup.client.get(uri.clone()).dispatch().await;
+    let consent = |body: &'static str| {
+        setup
+            .client
+            .post(uri.clone())
+            .header(ContentType::Form)
+            .body(body)
+            .dispatch()
+    };
+    let response = consent("approve=true&csrf_token=forged").await;
+    assert_eq!(response.status(), Status::BadRequest);
+    let response = consent("approve=true").await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    // the token of another request is rejected too.
+    let other = authorize_uri(&setup.confidential, "&state=other");
+    let response = setup.client.get(other).dispatch().await;
+    let csrf_token = response.into_json::<Value>().await.unwrap()["csrf_token"].clone();
+    let response = setup
+        .client
+        .post(uri)
+        .header(ContentType::Form)
+        .body(format!(
+            "approve=true&csrf_token={}",
+            csrf_token.as_str().unwrap()
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn pkce() {
+    let setup = setup().await;
+    let missing = setup
+        .client
+        .get(authorize_uri(&setup.public, ""))
+        .dispatch()
+        .await;
+    let location = missing.headers().get_one("Location").unwrap();
+    assert_eq!(
+        query_param(location, "error").as_deref(),
+        Some("invalid_request")
+    );
+
+    let verifier = "a-verifier-that-is-long-enough-to-be-used-with-pkce";
+    let plain = format!("&code_challenge={}&code_challenge_method=plain", verifier);
+    let plain = setup
+        .client
+        .get(authorize_uri(&setup.public, &plain))
+        .dispatch()
+        .await;
+    let location = plain.headers().get_one("Location").unwrap();
+    assert_eq!(
+        query_param(location, "error").as_deref(),
+        Some("invalid_request")
+    );
+
+    // S256 is the default method.
+    let challenge = format!("&code_challenge={}", s256(verifier));
+    let exchange = |code: String, verifier: &str| {
+        let body = format!(
+            "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&code_verifier={}",
+            code, REDIRECT_URI, setup.public.client_id, verifier
+        );
+        post_token(&setup.client, body)
+    };
+    let location = approve(&setup.client, &authorize_uri(&setup.public, &challenge)).await;
+    let code = query_param(&location, "code").unwrap();
+    let (status, error) = exchange(code, "another-verifier").await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(error["error"], "invalid_grant");
+
+    let uri = authorize_uri(&setup.public, &challenge);
+    let response = setup.client.get(uri).dispatch().await;
+    let location = response.headers().get_one("Location").unwrap();
+    let code = query_param(location, "code").unwrap();
+    let (status, tokens) = exchange(code, verifier).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        call_api(&setup.client, &tokens["access_token"]).await,
+        Status::Ok
+    );
+}
+
+#[rocket::async_test]
+async fn code_reuse_revokes_tokens() {
+    let setup = setup().await;
+    let location = approve(&setup.client, &authorize_uri(&setup.confidential, "")).await;
+    let code = query_param(&location, "code").unwrap();
+    let (status, tokens) = exchange_code(&setup, &code).await;
+    assert_eq!(status, Status::Ok);
+    let (status, refreshed) = refresh(&setup, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status, Status::Ok);
+
+    let (status, error) = exchange_code(&setup, &code).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(error["error"], "invalid_grant");
+    // the tokens issued for the code, including the rotated ones, are revoked.
+    assert_eq!(
+        call_api(&setup.client, &tokens["access_token"]).await,
+        Status::Unauthorized
+    );
+    assert_eq!(
+        call_api(&setup.client, &refreshed["access_token"]).await,
+        Status::Unauthorized
+    );
+    let (status, _) = refresh(&setup, refreshed["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn refresh_rotation() {
+    let setup = setup().await;
+    let location = approve(&setup.client, &authorize_uri(&setup.confidential, "")).await;
+    let code = query_param(&location, "code").unwrap();
+    let (_, tokens) = exchange_code(&setup, &code).await;
+    let refresh_token = tokens["refresh_token"].as_str().unwrap();
+
+    let (status, rotated) = refresh(&setup, refresh_token).await;
+    assert_eq!(status, Status::Ok);
+    assert_ne!(rotated["refresh_token"], tokens["refresh_token"]);
+    assert_eq!(
+        call_api(&setup.client, &rotated["access_token"]).await,
+        Status::Ok
+    );
+
+    let (status, error) = refresh(&setup, refresh_token).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(error["error"], "invalid_grant");
+    let (status, _) = refresh(&setup, rotated["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status, Status::Ok);
+}
+
+/// Issues tokens for the logged in user through the authorization code grant.
+async fn issue_tokens(setup: &Setup) -> Value {
+    let location = approve(&setup.client, &authorize_uri(&setup.confidential, "")).await;
+    let code = query_param(&location, "code").unwrap();
+    let (status, tokens) = exchange_code(setup, &code).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        call_api(&setup.client, &tokens["access_token"]).await,
+        Status::Ok
+    );
+    tokens
+}
+
+async fn assert_revoked(setup: &Setup, tokens: &Value) {
+    assert_eq!(
+        call_api(&setup.client, &tokens["access_token"]).await,
+        Status::Unauthorized
+    );
+    let (status, error) = refresh(setup, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(error["error"], "invalid_grant");
+}
+
+#[rocket::async_test]
+async fn disabled_user() {
+    let setup = setup().await;
+    let tokens = issue_tokens(&setup).await;
+    let users = setup.client.rocket().state::<Users>().unwrap();
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    users.disable(user.id).await.unwrap();
+    assert_revoked(&setup, &tokens).await;
+}
+
+#[rocket::async_test]
+async fn inactive_user() {
+    let setup = setup().await;
+    let tokens = issue_tokens(&setup).await;
+    // tokens that outlived the user being deactivated, such as the ones of a user
+    // disabled before they were revoked, are rejected too.
+    let users = setup.client.rocket().state::<Users>().unwrap();
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    users
+        .conn
+        .set_user_deleted_at(user.id, Some(now()))
+        .await
+        .unwrap();
+    assert_revoked(&setup, &tokens).await;
+}
@@ -0,0 +1,142 @@
+//! Checks that users whose password expired, or who were required to change it,
+//! can log in but are rejected by the `User` guard until they change it.
+use crate::credentials::CredentialVerifier;
+use crate::prelude::*;
+use crate::{Login, PasswordPolicy};
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::Json;
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[rocket::post("/login", data = "<form>")]
+async fn login(form: Json<Login>, auth: Auth<'_>) -> Result<Json<LoginStatus>> {
+    Ok(Json(auth.login_with_status(&form).await?))
+}
+
+#[rocket::get("/private")]
+fn private(_user: User) {}
+
+/// Trusts any password, like a verifier checking them against an external system would.
+struct External;
+
+#[async_trait]
+impl CredentialVerifier for External {
+    async fn verify(&self, users: &Users, email: &str, _password: &str) -> Result<User> {
+        users.get_by_email(email).await
+    }
+}
+
+async fn client(external: bool) -> Client {
+    let mut users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    let mut policy = PasswordPolicy::default();
+    policy.max_age = Some(Duration::from_secs(90 * DAY as u64));
+    users.set_password_policy(policy);
+    if external {
+        users.set_credential_verifier(External);
+    }
+    users
+        .create_user("alice@example.com", "Password123", false)
+        .await
+        .unwrap();
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![login, private])
+        .mount("/auth", crate::routes())
+        .manage(users);
+    Client::tracked(rocket).await.unwrap()
+}
+
+fn users(client: &Client) -> &Users {
+    client.rocket().state::<Users>().unwrap()
+}
+
+async fn age_password(client: &Client, days: i64) {
+    let users = users(client);
+    let mut user = users.get_by_email("alice@example.com").await.unwrap();
+    user.password_changed_at = Some(now() - days * DAY);
+    users.conn.update_user(&user).await.unwrap();
+}
+
+async fn log_in(client: &Client, password: &str) -> LoginStatus {
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{"email": "alice@example.com", "password": "{}"}}"#,
+            password
+        ))
+        .dispatch()
+        .await;
+    response.into_json().await.unwrap()
+}
+
+async fn private_status(client: &Client) -> Status {
+    client.get("/private").dispatch().await.status()
+}
+
+async fn change_password(client: &Client, current: &str, new: &str) {
+    let response = client
+        .post("/auth/password")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{"current_password": "{}", "new_password": "{}"}}"#,
+            current, new
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn expired_password() {
+    let client = client(false).await;
+    age_password(&client, 89).await;
+    assert_eq!(log_in(&client, "Password123").await, LoginStatus::LoggedIn);
+    assert_eq!(private_status(&client).await, Status::Ok);
+
+    age_password(&client, 91).await;
+    assert_eq!(
+        log_in(&client, "Password123").await,
+        LoginStatus::PasswordChangeRequired
+    );
+    assert_eq!(private_status(&client).await, Status::Forbidden);
+    let user = users(&client)
+        .get_by_email("alice@example.com")
+        .await
+        .unwrap();
+    assert!(user.must_change_password());
+
+    change_password(&client, "Password123", "Password456").await;
+    assert_eq!(private_status(&client).await, Status::Ok);
+    assert_eq!(log_in(&client, "Password456").await, LoginStatus::LoggedIn);
+}
+
+#[rocket::async_test]
+async fn required_password_change() {
+    let client = client(false).await;
+    let users = users(&client);
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    users.require_password_change(user.id()).await.unwrap();
+    assert_eq!(
+        log_in(&client, "Password123").await,
+        LoginStatus::PasswordChangeRequired
+    );
+    assert_eq!(private_status(&client).await, Status::Forbidden);
+
+    change_password(&client, "Password123", "Password456").await;
+    assert_eq!(private_status(&client).await, Status::Ok);
+    let user = users.get_by_email("alice@example.com").await.unwrap();
+    assert!(!user.must_change_password());
+}
+
+#[rocket::async_test]
+async fn external_passwords_never_expire() {
+    let client = client(true).await;
+    age_password(&client, 91).await;
+    assert_eq!(log_in(&client, "anything").await, LoginStatus::LoggedIn);
+    assert_eq!(private_status(&client).await, Status::Ok);
+}
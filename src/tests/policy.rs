@@ -0,0 +1,34 @@
+//! Checks that the strength of a rejected password is sent to the client.
+use crate::prelude::*;
+use crate::PasswordPolicy;
+use rocket::http::ContentType;
+use rocket::local::asynchronous::Client;
+use serde_json::Value;
+
+#[rocket::async_test]
+async fn too_weak() {
+    let mut users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    let mut policy = PasswordPolicy::default();
+    policy.min_strength = Some(3);
+    users.set_password_policy(policy);
+    let rocket = rocket::build()
+        .mount("/auth", crate::routes())
+        .manage(users);
+    let client = Client::tracked(rocket).await.unwrap();
+    let response = client
+        .post("/auth/signup")
+        .header(ContentType::JSON)
+        .body(r#"{"email": "alice@example.com", "password": "Password123"}"#)
+        .dispatch()
+        .await;
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["status"], "error");
+    let error = &body["errors"]["password"][0];
+    assert!(error["params"]["score"].as_u64().unwrap() < 3, "{}", body);
+    assert!(error["params"]["suggestions"].is_array(), "{}", body);
+    assert!(error["params"].get("warning").is_some(), "{}", body);
+}
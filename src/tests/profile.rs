@@ -0,0 +1,76 @@
+//! Tests that the profile of a user is stored along with them on signup
+//! and that it is part of their json representation.
+use crate::hooks::UserHooks;
+use crate::prelude::*;
+use crate::{Auth, SignupWith};
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::Json;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+#[rocket::post("/signup", data = "<form>")]
+async fn signup(form: Json<SignupWith<Value>>, auth: Auth<'_>) -> Result<()> {
+    auth.signup_with_profile(&form).await
+}
+
+#[rocket::get("/me")]
+fn me(user: User) -> Json<User> {
+    Json(user)
+}
+
+struct SeenProfile(Arc<Mutex<Option<Value>>>);
+
+#[async_trait]
+impl UserHooks for SeenProfile {
+    async fn on_signup(&self, _users: &Users, user: &User) -> Result<()> {
+        *self.0.lock().unwrap() = user.profile()?;
+        Ok(())
+    }
+}
+
+#[rocket::async_test]
+async fn signup_with_profile() {
+    let mut users: Users = sqlx::SqlitePool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .into();
+    users.create_table().await.unwrap();
+    let seen = Arc::new(Mutex::new(None));
+    users.add_hooks(SeenProfile(seen.clone()));
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![signup, me])
+        .mount("/auth", crate::routes())
+        .manage(users);
+    let client = Client::tracked(rocket).await.unwrap();
+
+    let profile = json!({ "display_name": "Alice" });
+    let status = client
+        .post("/signup")
+        .header(ContentType::JSON)
+        .body(
+            json!({ "email": "alice@example.com", "password": "Password123", "profile": profile })
+                .to_string(),
+        )
+        .dispatch()
+        .await
+        .status();
+    assert_eq!(status, Status::Ok);
+    assert_eq!(seen.lock().unwrap().as_ref(), Some(&profile));
+
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{"email": "alice@example.com", "password": "Password123"}"#)
+        .dispatch()
+        .await;
+    let user: Value = client
+        .get("/me")
+        .dispatch()
+        .await
+        .into_json()
+        .await
+        .unwrap();
+    assert_eq!(user["profile"], profile);
+    assert!(user.get("password").is_none());
+}
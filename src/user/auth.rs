@@ -1,3 +1,4 @@
+use crate::audit::Client;
 use crate::prelude::*;
 use rocket::http::Status;
 use rocket::http::{Cookie, CookieJar};
@@ -28,8 +29,8 @@ use std::time::Duration;
 /// }
 ///
 /// #[post("/logout")]
-/// fn logout(auth: Auth) {
-///     auth.logout();
+/// async fn logout(auth: Auth<'_>) {
+///     auth.logout().await;
 /// }
 /// #[tokio::main]
 /// async fn main() -> Result<(), Error>{
@@ -49,6 +50,18 @@ pub struct Auth<'a> {
     pub users: &'a State<Users>,
     pub cookies: &'a CookieJar<'a>,
     pub session: Option<Session>,
+    pub(crate) client: Client,
+}
+
+/// The result of a successful [`Auth::login_with_status`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoginStatus {
+    /// The user is logged in.
+    LoggedIn,
+    /// The user is logged in, but they have to change their password with [`Auth::change_password`]
+    /// before the [`User`] guards accept their session, because it was required with
+    /// [`Users::require_password_change`] or because it expired.
+    PasswordChangeRequired,
 }
 
 #[async_trait]
@@ -71,6 +84,7 @@ impl<'r> FromRequest<'r> for Auth<'r> {
             users,
             session,
             cookies: req.cookies(),
+            client: Client::from_request(req),
         })
     }
 }
@@ -87,10 +101,31 @@ impl<'a> Auth<'a> {
     ///     auth.login(&form);
     /// }
     /// ```
+    /// If the user has to change their password, the session is created but the [`User`] guards reject it.
+    /// Use [`Auth::login_with_status`] to find out whether that is the case.
     #[throws(Error)]
     pub async fn login(&self, form: &Login) {
-        let key = self.users.login(form).await?;
-        let user = self.users.get_by_email(&form.email.to_lowercase()).await?;
+        self.login_with_status(form).await?;
+    }
+
+    /// Like [`Auth::login`], but it returns whether the user has to change their password.
+    /// In that case the session is created, but [`LoginStatus::PasswordChangeRequired`]
+    /// is returned, so they can be redirected to change it:
+    /// ```rust
+    /// # use rocket::{post, form::Form, response::Redirect};
+    /// # use rocket_auth::{Auth, Error, Login, LoginStatus};
+    /// #[post("/login", data="<form>")]
+    /// async fn login(form: Form<Login>, auth: Auth<'_>) -> Result<Redirect, Error> {
+    ///     match auth.login_with_status(&form).await? {
+    ///         LoginStatus::LoggedIn => Ok(Redirect::to("/")),
+    ///         LoginStatus::PasswordChangeRequired => Ok(Redirect::to("/change-password")),
+    ///     }
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub async fn login_with_status(&self, form: &Login) -> LoginStatus {
+        let (user, key) = self.users.login(form, &self.client).await?;
+        let status = self.users.login_status(&user);
         let session = Session {
             id: user.id,
             email: user.email,
@@ -99,6 +134,7 @@ impl<'a> Auth<'a> {
         };
         let to_str = format!("{}", json!(session));
         self.cookies.add_private(Cookie::new("rocket_auth", to_str));
+        status
     }
 
     /// Logs a user in for the specified period of time.
@@ -114,8 +150,15 @@ impl<'a> Auth<'a> {
     /// ```
     #[throws(Error)]
     pub async fn login_for(&self, form: &Login, time: Duration) {
-        let key = self.users.login_for(form, time).await?;
-        let user = self.users.get_by_email(&form.email.to_lowercase()).await?;
+        self.login_for_with_status(form, time).await?;
+    }
+
+    /// Like [`Auth::login_for`], but it returns whether the user has to change their password,
+    /// as [`Auth::login_with_status`] does.
+    #[throws(Error)]
+    pub async fn login_for_with_status(&self, form: &Login, time: Duration) -> LoginStatus {
+        let (user, key) = self.users.login_for(form, time, &self.client).await?;
+        let status = self.users.login_status(&user);
 
         let session = Session {
             id: user.id,
@@ -126,6 +169,7 @@ impl<'a> Auth<'a> {
         let to_str = format!("{}", json!(session));
         let cookie = Cookie::new("rocket_auth", to_str);
         self.cookies.add_private(cookie);
+        status
     }
 
     /// Creates a new user from a form or a json. The user will not be authenticated by default.
@@ -143,7 +187,57 @@ impl<'a> Auth<'a> {
     /// ```
     #[throws(Error)]
     pub async fn signup(&self, form: &Signup) {
-        self.users.signup(form).await?;
+        if !self.users.open_signup {
+            throw!(Error::SignupDisabled)
+        }
+        self.users.signup(form, Some(&self.client)).await?;
+    }
+
+    /// Creates a new user from a form or a json that also carries application defined profile data.
+    /// The profile can be retrieved later with [`User::profile`].
+    /// ```rust
+    /// # use rocket::{post, form::Form, FromForm};
+    /// # use rocket_auth::{Auth, SignupWith, Error};
+    /// # use serde::{Serialize, Deserialize};
+    /// #[derive(FromForm, Serialize, Deserialize)]
+    /// struct Profile {
+    ///     display_name: String,
+    /// }
+    ///
+    /// #[post("/signup", data="<form>")]
+    /// async fn signup(form: Form<SignupWith<Profile>>, auth: Auth<'_>) -> Result<&'static str, Error> {
+    ///     auth.signup_with_profile(&form).await?;
+    ///     auth.login(&(&*form).into()).await?;
+    ///     Ok("Logged in")
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub async fn signup_with_profile<T: Serialize>(&self, form: &SignupWith<T>) {
+        if !self.users.open_signup {
+            throw!(Error::SignupDisabled)
+        }
+        let profile = serde_json::to_string(&form.profile)?;
+        self.users
+            .signup_with_profile(&form.into(), Some(&profile), Some(&self.client))
+            .await?;
+    }
+
+    /// Creates a new user with an invitation created with [`Users::create_invite`].
+    /// It works even if open signup is disabled, but the email of the form must match the email of the invitation.
+    /// Each invitation can only be used once.
+    /// ```rust
+    /// # use rocket::{post, form::Form};
+    /// # use rocket_auth::{Auth, Signup, Error};
+    /// #[post("/signup?<invite>", data="<form>")]
+    /// async fn signup(invite: &str, form: Form<Signup>, auth: Auth<'_>) -> Result<&'static str, Error> {
+    ///     auth.signup_with_invite(&form, invite).await?;
+    ///     auth.login(&form.into()).await?;
+    ///     Ok("Welcome aboard")
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub async fn signup_with_invite(&self, form: &Signup, token: &str) {
+        self.users.signup_with_invite(form, token, &self.client).await?;
     }
 
     /// Creates a new user from a form or a json.
@@ -160,7 +254,7 @@ impl<'a> Auth<'a> {
     /// ```
     #[throws(Error)]
     pub async fn signup_for(&self, form: &Signup, time: Duration) {
-        self.users.signup(form).await?;
+        self.signup(form).await?;
         self.login_for(&form.clone().into(), time).await?;
     }
 
@@ -203,7 +297,7 @@ impl<'a> Auth<'a> {
         }
         let id = self.session.as_ref()?.id;
         if let Ok(user) = self.users.get_by_id(id).await {
-            Some(user)
+            Some(user).filter(User::is_active)
         } else {
             None
         }
@@ -214,14 +308,14 @@ impl<'a> Auth<'a> {
     /// # use rocket::post;
     /// # use rocket_auth::Auth;
     /// #[post("/logout")]
-    /// fn logout(auth: Auth)  {
-    ///     auth.logout();
+    /// async fn logout(auth: Auth<'_>) {
+    ///     auth.logout().await;
     /// }
     /// ```
     #[throws(Error)]
-    pub fn logout(&self) {
+    pub async fn logout(&self) {
         let session = self.get_session()?;
-        self.users.logout(session)?;
+        self.users.logout(session, &self.client).await?;
         self.cookies.remove_private(Cookie::named("rocket_auth"));
     }
     /// Deletes the account of the currently authenticated user.
@@ -237,7 +331,7 @@ impl<'a> Auth<'a> {
     pub async fn delete(&self) {
         if self.is_auth() {
             let session = self.get_session()?;
-            self.users.delete(session.id).await?;
+            self.users.delete_user(session.id, Some(&self.client)).await?;
             self.cookies.remove_private(Cookie::named("rocket_auth"));
         } else {
             throw!(Error::UnauthenticatedError)
@@ -258,8 +352,8 @@ impl<'a> Auth<'a> {
         if self.is_auth() {
             let session = self.get_session()?;
             let mut user = self.users.get_by_id(session.id).await?;
-            user.set_password(password)?;
-            self.users.modify(&user).await?;
+            self.users.set_password(&mut user, password).await?;
+            self.users.update_user(&user, Some(&self.client)).await?;
         } else {
             throw!(Error::UnauthorizedError)
         }
@@ -281,7 +375,7 @@ impl<'a> Auth<'a> {
             let session = self.get_session()?;
             let mut user = self.users.get_by_id(session.id).await?;
             user.email = email.to_lowercase();
-            self.users.modify(&user).await?;
+            self.users.update_user(&user, Some(&self.client)).await?;
         } else {
             throw!(Error::UnauthorizedError)
         }
@@ -310,7 +404,7 @@ impl<'a> Auth<'a> {
         if self.is_auth() {
             let session = self.get_session()?; 
             let user: User = self.users.get_by_id(session.id).await?;
-            user.compare_password(password)?
+            self.users.verify_password(&user, password).await?
         } else {
             throw!(Error::UnauthorizedError)
         }
@@ -1,10 +1,11 @@
 pub mod auth;
 mod user_impl;
 mod users;
+use crate::audit::{AuthEvent, Client};
 use crate::prelude::*;
-use argon2::verify_encoded as verify;
 
-use rand::random;
+use rand::distributions::Alphanumeric;
+use rand::{random, thread_rng, Rng};
 pub fn rand_string(size: usize) -> String {
     (0..)
         .map(|_| random::<char>())
@@ -14,6 +15,16 @@ pub fn rand_string(size: usize) -> String {
         .collect()
 }
 
+/// Like [`rand_string`], but restricted to alphanumeric characters,
+/// so the result can be safely sent in urls and headers.
+pub fn rand_token(size: usize) -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .map(char::from)
+        .take(size)
+        .collect()
+}
+
 impl Users {
     fn is_auth(&self, session: &Session) -> bool {
         let option = self.sess.get(session.id);
@@ -24,25 +35,71 @@ impl Users {
         }
     }
 
+    /// Checks the credentials of a login form without creating a session.
     #[throws(Error)]
-    async fn login(&self, form: &Login) -> String {
-        let form_pwd = &form.password.as_bytes();
-        let user = self
-            .conn
-            .get_user_by_email(&form.email.to_lowercase())
-            .await
-            .map_err(|_| Error::EmailDoesNotExist(form.email.clone()))?;
-        let user_pwd = &user.password;
-        if verify(user_pwd, form_pwd)? {
-            self.set_auth_key(user.id)?
+    pub(crate) async fn verify_credentials(&self, form: &Login) -> User {
+        let email = if form.email.contains('@') {
+            form.email.to_lowercase()
         } else {
-            throw!(Error::UnauthorizedError)
+            let user = self.get_by_username(&form.email).await;
+            user.map_err(|_| Error::UnauthorizedError)?.email
+        };
+        let user = self.verifier.verify(self, &email, &form.password).await?;
+        if !user.is_active() {
+            throw!(Error::AccountDisabledError)
+        }
+        user
+    }
+
+    /// Queries a user by the identifier of a login form, which is either their email or their username.
+    #[throws(Error)]
+    pub(crate) async fn get_by_login(&self, identifier: &str) -> User {
+        if identifier.contains('@') {
+            self.get_by_email(&identifier.to_lowercase()).await?
+        } else {
+            self.get_by_username(identifier).await?
+        }
+    }
+
+    /// Verifies the credentials of a login form, recording whether the attempt succeeded.
+    #[throws(Error)]
+    pub(crate) async fn authenticate(&self, form: &Login, client: &Client) -> User {
+        match self.verify_credentials(form).await {
+            Ok(user) => {
+                for hooks in &self.hooks {
+                    hooks.before_login(self, &user).await?;
+                }
+                self.audit(AuthEvent::Login, Some(user.id), Some(client)).await?;
+                user
+            }
+            Err(error) => {
+                if self.audit.is_some() {
+                    let user_id = self.get_by_login(&form.email).await.ok().map(|user| user.id);
+                    self.audit(AuthEvent::LoginFailed, user_id, Some(client)).await?;
+                }
+                throw!(error)
+            }
+        }
+    }
+
+    #[throws(Error)]
+    async fn login(&self, form: &Login, client: &Client) -> (User, String) {
+        let user = self.authenticate(form, client).await?;
+        let user = self.flag_expired_password(user, client).await?;
+        let key = self.set_auth_key(user.id)?;
+        for hooks in &self.hooks {
+            hooks.on_login(self, &user).await?;
         }
+        (user, key)
     }
     #[throws(Error)]
-    fn logout(&self, session: &Session) {
+    async fn logout(&self, session: &Session, client: &Client) {
         if self.is_auth(session) {
             self.sess.remove(session.id)?;
+            self.audit(AuthEvent::Logout, Some(session.id), Some(client)).await?;
+            for hooks in &self.hooks {
+                hooks.on_logout(self, session.id).await?;
+            }
         }
     }
 
@@ -61,36 +118,81 @@ impl Users {
     }
 
     #[throws(Error)]
-    async fn signup(&self, form: &Signup) {
+    pub(crate) async fn signup(&self, form: &Signup, client: Option<&Client>) -> User {
+        self.signup_with_profile(form, None, client).await?
+    }
+
+    /// Signs up a user with the json encoded `profile`, which is stored in the same
+    /// insert as the user, so it is already visible to the `on_signup` hooks.
+    #[throws(Error)]
+    pub(crate) async fn signup_with_profile(
+        &self,
+        form: &Signup,
+        profile: Option<&str>,
+        client: Option<&Client>,
+    ) -> User {
         form.validate()?;
+        self.check_password(&form.password, &form.email).await?;
+        for hooks in &self.hooks {
+            hooks.before_signup(self, form).await?;
+        }
         let email = &form.email.to_lowercase();
         let password = &form.password;
-        let result = self.create_user(email, password, false).await;
-        match result {
-            Ok(_) => (),
-            #[cfg(feature="sqlx")]
-            Err(Error::SqlxError(sqlx::Error::Database(error))) => {
-                if error.code() == Some("23000".into()) {
-                    throw!(Error::EmailAlreadyExists)
-                } else {
-                    throw!(Error::SqlxError(sqlx::Error::Database(error)))
-                }
-            }
-            Err(error) => {
-                throw!(error)
+        let username = form.username.as_deref();
+        if let Some(username) = username {
+            if self.conn.get_user_by_username(username).await.is_ok() {
+                throw!(Error::UsernameAlreadyExists)
             }
         }
+        self.insert_user(email, password, false, username, profile)
+            .await?;
+        let user = self.get_by_email(email).await?;
+        self.audit(AuthEvent::Signup, Some(user.id), client).await?;
+        for hooks in &self.hooks {
+            hooks.on_signup(self, &user).await?;
+        }
+        user
     }
 
     #[throws(Error)]
-    async fn login_for(&self, form: &Login, time: Duration) -> String {
-        let form_pwd = &form.password.as_bytes();
-        let user = self.conn.get_user_by_email(&form.email.to_lowercase()).await?;
-        let user_pwd = &user.password;
-        if verify(user_pwd, form_pwd)? {
-            self.set_auth_key_for(user.id, time)?
-        } else {
-            throw!(Error::UnauthorizedError)
+    async fn login_for(&self, form: &Login, time: Duration, client: &Client) -> (User, String) {
+        let user = self.authenticate(form, client).await?;
+        let user = self.flag_expired_password(user, client).await?;
+        let key = self.set_auth_key_for(user.id, time)?;
+        for hooks in &self.hooks {
+            hooks.on_login(self, &user).await?;
+        }
+        (user, key)
+    }
+}
+
+/// Serializes the profile of a [`User`] as the json value it stores,
+/// rather than as the string it is stored in.
+pub(crate) mod profile_json {
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    pub fn serialize<S: Serializer>(
+        profile: &Option<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let value = match profile {
+            Some(profile) => serde_json::from_str(profile).map_err(S::Error::custom)?,
+            None => Value::Null,
+        };
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            value => serde_json::to_string(&value)
+                .map(Some)
+                .map_err(D::Error::custom),
         }
     }
 }
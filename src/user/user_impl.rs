@@ -1,45 +1,54 @@
 use super::auth::Auth;
-use super::rand_string;
+use crate::hashing::{self, PasswordHasher};
 
 use crate::prelude::*;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
+use rocket::State;
+use serde::de::DeserializeOwned;
 
 impl User {
     /// This method allows to reset the password of a user.
     /// In order for the new password to be saved, it must be passed to a [`Users`] instance.
-    /// This function is meant for cases where the user lost their password.
-    /// In case the user is authenticated,
-    /// you can change it more easily with [`change_password`](`super::auth::Auth::change_password`).
-    /// This function will fail in case the password is not secure enough.
-    /// 
+    /// It only checks the default [`PasswordPolicy`], skipping the policy, breached password list
+    /// and password history configured in [`Users`], so the new password may be one the user
+    /// has used before. It always hashes with the default [`PasswordHasher`] and no pepper,
+    /// on the current thread. Use [`Users::set_password`] instead.
     /// ```rust
     /// # use rocket::{State, post};
-    /// # use rocket_auth::{Error, Users};
+    /// # use rocket_auth::{Error, Users, User};
     /// #[post("/reset-password/<new_password>")]
     /// async fn reset_password(mut user: User, users: &State<Users>, new_password: String) -> Result<(), Error> {
-    ///     user.set_password(&new_password);
+    ///     users.set_password(&mut user, &new_password).await?;
     ///     users.modify(&user).await?;
     ///     Ok(())
     /// }
     /// ```
+    #[deprecated(since = "0.4.0", note = "use `Users::set_password`, which applies the password policy, breach and history checks of `Users`")]
     #[throws(Error)]
     pub fn set_password(&mut self, new: &str) {
-        crate::forms::is_secure(new)?;
-        let password = new.as_bytes();
-        let salt = rand_string(10);
-        let config = argon2::Config::default();
-        let hash = argon2::hash_encoded(password, salt.as_bytes(), &config).unwrap();
-        self.password = hash;
+        PasswordPolicy::default().check(new, &self.email)?;
+        self.password = PasswordHasher::default().hash(new)?;
+        self.password_changed();
+    }
+
+    /// Marks the password as just changed, which fulfills a required password change.
+    pub(crate) fn password_changed(&mut self) {
+        self.password_changed_at = Some(now());
+        self.must_change_password = false;
     }
 
     /// Compares the password of the currently authenticated user with a another password.
     /// Useful for checking password before resetting email/password.
     /// To avoid bruteforcing this function should not be directly accessible from a route.
     /// Additionally, it is good to implement rate limiting on routes using this function.
+    /// It verifies the password on the current thread and fails for peppered hashes,
+    /// so [`Users::verify_password`] or [`Auth::compare_password`], which use the blocking thread pool
+    /// and the peppers of [`Users`], should be used instead.
+    #[deprecated(since = "0.4.0", note = "use `Users::verify_password`, which supports peppered hashes")]
     #[throws(Error)]
     pub fn compare_password(&self, password: &str) -> bool {
-        verify_encoded(&self.password, password.as_bytes())?
+        hashing::verify(&self.password, password)?
     }
 
     /// This is an accessor function for the private `id` field.
@@ -69,6 +78,101 @@ impl User {
         &self.email
     }
 
+    /// Deserializes the profile data stored along with the user, if there is any.
+    /// ```rust
+    /// # use rocket::get;
+    /// # use rocket_auth::{User, Error};
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct Profile {
+    ///     display_name: String,
+    /// }
+    ///
+    /// #[get("/hello")]
+    /// fn hello(user: User) -> Result<String, Error> {
+    ///     let name = match user.profile::<Profile>()? {
+    ///         Some(profile) => profile.display_name,
+    ///         None => user.email().into(),
+    ///     };
+    ///     Ok(format!("Hello {}", name))
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub fn profile<T: DeserializeOwned>(&self) -> Option<T> {
+        match &self.profile {
+            Some(profile) => Some(serde_json::from_str(profile)?),
+            None => None,
+        }
+    }
+
+    /// Replaces the profile data of the user. It is stored as json,
+    /// and it must be passed to a [`Users`] instance in order to be saved.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # use serde_json::json;
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let mut user = users.get_by_id(4).await?;
+    /// user.set_profile(&json!({ "display_name": "Alice" }))?;
+    /// users.modify(&user).await?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub fn set_profile<T: Serialize>(&mut self, profile: &T) {
+        self.profile = Some(serde_json::to_string(profile)?);
+    }
+
+    /// Returns `false` if the user was disabled with [`Users::disable`] or soft deleted.
+    /// Inactive users can't log in.
+    pub fn is_active(&self) -> bool {
+        self.disabled_at.is_none() && self.deleted_at.is_none()
+    }
+
+    /// The unix timestamp at which the user was disabled, if they are disabled.
+    pub fn disabled_at(&self) -> Option<i64> {
+        self.disabled_at
+    }
+
+    /// The unix timestamp at which the user was soft deleted, if they were.
+    pub fn deleted_at(&self) -> Option<i64> {
+        self.deleted_at
+    }
+
+    /// The unix timestamp of when the user signed up.
+    pub fn created_at(&self) -> i64 {
+        self.created_at
+    }
+
+    /// The unix timestamp of the last time the password of the user was set,
+    /// or `None` if it was set before it was tracked.
+    pub fn password_changed_at(&self) -> Option<i64> {
+        self.password_changed_at
+    }
+
+    /// Whether the user was required to change their password. It is cleared once the password is changed.
+    /// See [`Users::needs_password_change`], which also takes the maximum age of passwords into account.
+    pub fn must_change_password(&self) -> bool {
+        self.must_change_password
+    }
+
+    /// Requires the user to change their password. In order for it to be saved, it must be passed to a [`Users`] instance.
+    /// See [`Users::require_password_change`].
+    pub fn require_password_change(&mut self) {
+        self.must_change_password = true;
+    }
+
+    /// The username of the user, if they chose one.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Changes the username of a user. In order for it to be saved, it must be passed to a [`Users`] instance,
+    /// which will fail if the username is taken.
+    #[throws(Error)]
+    pub fn set_username(&mut self, username: &str) {
+        crate::forms::is_username(username)?;
+        self.username = Some(username.into());
+    }
+
     /// This functions allows to easily modify the email of a user.
     /// In case the input is not a valid email, it will return an error.
     /// In case the user corresponds to the authenticated client, it's easier to use [`Auth::change_email`].
@@ -110,16 +214,28 @@ impl<'r> FromRequest<'r> for User {
     type Error = Error;
     async fn from_request(request: &'r Request<'_>) -> Outcome<User, Error> {
         use rocket::outcome::Outcome::*;
+        let users: &State<Users> = match request.guard().await {
+            Success(users) => users,
+            _ => return Failure((Status::InternalServerError, Error::UnmanagedStateError)),
+        };
+        if let Some(proxy) = &users.proxy {
+            return match proxy.get_user(request, users).await {
+                Some(user) => Success(user),
+                None => Failure((Status::Unauthorized, Error::UnauthorizedError)),
+            };
+        }
         let guard = request.guard().await;
         let auth: Auth = match guard {
             Success(auth) => auth,
             Failure(x) => return Failure(x),
             Forward(x) => return Forward(x),
         };
-        if let Some(user) = auth.get_user().await {
-            Outcome::Success(user)
-        } else {
-            Outcome::Failure((Status::Unauthorized, Error::UnauthorizedError))
+        match auth.get_user().await {
+            Some(user) if users.needs_password_change(&user) => {
+                Outcome::Failure((Status::Forbidden, Error::PasswordChangeRequired))
+            }
+            Some(user) => Outcome::Success(user),
+            None => Outcome::Failure((Status::Unauthorized, Error::UnauthorizedError)),
         }
     }
 }
@@ -129,23 +245,20 @@ impl<'r> FromRequest<'r> for AdminUser {
     type Error = Error;
     async fn from_request(request: &'r Request<'_>) -> Outcome<AdminUser, Error> {
         use rocket::outcome::Outcome::*;
-        let guard = request.guard().await;
-        let auth: Auth = match guard {
-            Success(auth) => auth,
+        let user: User = match request.guard().await {
+            Success(user) => user,
             Failure(x) => return Failure(x),
             Forward(x) => return Forward(x),
         };
-        if let Some(user) = auth.get_user().await {
-            if user.is_admin {
-                return Outcome::Success(AdminUser(user));
-            }
+        if user.is_admin {
+            Outcome::Success(AdminUser(user))
+        } else {
+            Outcome::Failure((Status::Unauthorized, Error::UnauthorizedError))
         }
-        Outcome::Failure((Status::Unauthorized, Error::UnauthorizedError))
     }
 }
 
 use std::ops::*;
-use argon2::verify_encoded;
 
 impl Deref for AdminUser {
     type Target = User;
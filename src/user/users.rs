@@ -1,6 +1,10 @@
-use super::rand_string;
+use crate::audit::{AuthEvent, Client};
+use crate::credentials::PasswordVerifier;
 use crate::db::DBConnection;
+use crate::hashing::default_concurrency;
 use crate::prelude::*;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 
 #[cfg(feature = "rusqlite")]
 use std::path::Path;
@@ -72,6 +76,54 @@ impl Users {
         self.sess = Box::new(client);
     }
 
+    /// Replaces the way passwords are checked when users log in.
+    /// By default, they are compared against the hash stored in the database.
+    /// See [`CredentialVerifier`](crate::credentials::CredentialVerifier) for more information.
+    /// ```rust,no_run
+    /// # use rocket_auth::{Users, Error, credentials::{LdapBind, LdapVerifier}};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Error> {
+    /// let mut users = Users::open_sqlite("database.db").await?;
+    /// let bind = LdapBind::Template("uid={username},ou=people,dc=example,dc=com".into());
+    /// users.set_credential_verifier(LdapVerifier::new("ldap://localhost:389", bind));
+    /// # Ok(()) }
+    /// ```
+    pub fn set_credential_verifier(&mut self, verifier: impl CredentialVerifier + 'static) {
+        self.verifier = Box::new(verifier);
+    }
+
+    /// Makes the [`User`] and [`AdminUser`] guards trust the identity headers set by an
+    /// authentication gateway instead of the session cookie.
+    /// See [`ProxyAuth`](crate::ProxyAuth) for more information.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error, ProxyAuth};
+    /// # fn func(mut users: Users) -> Result<(), Error> {
+    /// users.set_proxy_auth(ProxyAuth::new(&["127.0.0.1"])?);
+    /// # Ok(()) }
+    /// ```
+    pub fn set_proxy_auth(&mut self, proxy: ProxyAuth) {
+        self.proxy = Some(proxy);
+    }
+
+    /// Enables or disables open signup. When it is disabled, [`Auth::signup`](crate::Auth::signup)
+    /// fails with [`Error::SignupDisabled`], and users can only sign up with an invitation
+    /// created with [`Users::create_invite`].
+    /// ```rust
+    /// # use rocket_auth::Users;
+    /// # fn func(mut users: Users) {
+    /// users.set_open_signup(false);
+    /// # }
+    /// ```
+    pub fn set_open_signup(&mut self, open: bool) {
+        self.open_signup = open;
+    }
+
+    /// Makes [`Users::delete`] mark users as deleted instead of removing them from the database.
+    /// Soft deleted users can't log in, and are removed for good by [`Users::purge_deleted`].
+    pub fn set_soft_delete(&mut self, enabled: bool) {
+        self.soft_delete = enabled;
+    }
+
     /// It creates a `Users` instance by connecting  it to a sqlite database.
     /// This method uses the [`rusqlite`] crate.
     /// If the database does not yet exist it will attempt to create it. By default,
@@ -93,10 +145,7 @@ impl Users {
     #[throws(Error)]
     pub fn open_rusqlite(path: impl AsRef<Path>) -> Self {
         use tokio::sync::Mutex;
-        let users = Users {
-            conn: Box::new(Mutex::new(rusqlite::Connection::open(path)?)),
-            sess: Box::new(chashmap::CHashMap::new()),
-        };
+        let users: Users = Mutex::new(rusqlite::Connection::open(path)?).into();
         futures::executor::block_on(users.conn.init())?;
         users
     }
@@ -122,10 +171,7 @@ impl Users {
         use sqlx::PgPool;
         let conn = PgPool::connect(path).await?;
         conn.init().await?;
-        let users = Users {
-            conn: Box::new(conn),
-            sess: Box::new(chashmap::CHashMap::new()),
-        };
+        let users: Users = conn.into();
         users
     }
 
@@ -187,6 +233,18 @@ impl Users {
         self.conn.get_user_by_id(user_id).await?
     }
 
+    /// It queries a user by their username. The comparison is case insensitive.
+    /// ```
+    /// # use rocket_auth::{Error, Users};
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let user = users.get_by_username("alice").await?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn get_by_username(&self, username: &str) -> User {
+        self.conn.get_user_by_username(username).await?
+    }
+
     /// Inserts a new user in the database. It will fail if the user already exists.
     /// ```rust
     /// # use rocket::{State, post};
@@ -200,15 +258,28 @@ impl Users {
     /// ```
     #[throws(Error)]
     pub async fn create_user(&self, email: &str, password: &str, is_admin: bool) {
-        let password = password.as_bytes();
-        let salt = rand_string(30);
-        let config = argon2::Config::default();
-        let hash = argon2::hash_encoded(password, salt.as_bytes(), &config).unwrap();
-        self.conn.create_user(email, &hash, is_admin).await?;
+        self.insert_user(email, password, is_admin, None, None).await?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn insert_user(
+        &self,
+        email: &str,
+        password: &str,
+        is_admin: bool,
+        username: Option<&str>,
+        profile: Option<&str>,
+    ) {
+        let hash = self.hash_password(password).await?;
+        self.conn
+            .create_user(email, &hash, is_admin, username, profile)
+            .await
+            .map_err(Error::into_user_conflict)?;
     }
 
-    /// Deletes a user from de database. Note that this method won't delete the session.
-    /// To do that use [`Auth::delete`](crate::Auth::delete).
+    /// Deletes a user from de database and logs them out, revoking the OAuth tokens issued for them.
+    /// If soft delete was enabled with [`Users::set_soft_delete`], the user is only marked as deleted,
+    /// so it can be restored with [`Users::restore`] until it is purged with [`Users::purge_deleted`].
     /// ```
     /// #[get("/delete_user/<id>")]
     /// async fn delete_user(id: i32, users: &State<Users>) -> Result<String> {
@@ -218,8 +289,153 @@ impl Users {
     /// ```
     #[throws(Error)]
     pub async fn delete(&self, id: i32) {
+        self.delete_user(id, None).await?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn delete_user(&self, id: i32, client: Option<&Client>) {
+        for hooks in &self.hooks {
+            hooks.before_delete(self, id).await?;
+        }
+        self.sess.remove(id)?;
+        self.conn.delete_user_oauth_tokens(id).await?;
+        if self.soft_delete {
+            self.conn.set_user_deleted_at(id, Some(now())).await?;
+        } else {
+            self.conn.delete_user_by_id(id).await?;
+        }
+        self.audit(AuthEvent::Deleted, Some(id), client).await?;
+        for hooks in &self.hooks {
+            hooks.on_delete(self, id).await?;
+        }
+    }
+
+    /// Restores a user that was soft deleted.
+    #[throws(Error)]
+    pub async fn restore(&self, id: i32) {
+        self.conn.set_user_deleted_at(id, None).await?;
+    }
+
+    /// Permanently deletes the users that were soft deleted more than `older_than` ago.
+    /// It returns the number of deleted users, and is meant to be run periodically.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # use std::time::Duration;
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// let thirty_days = Duration::from_secs(30 * 24 * 60 * 60);
+    /// let purged = users.purge_deleted(thirty_days).await?;
+    /// println!("purged {} users", purged);
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn purge_deleted(&self, older_than: Duration) -> u64 {
+        let deleted_before = now() - older_than.as_secs() as i64;
+        self.conn.purge_deleted_users(deleted_before).await?
+    }
+
+    /// Suspends a user. Disabled users are logged out and their OAuth tokens are revoked,
+    /// and they can't log in until they are enabled again.
+    /// ```rust
+    /// # use rocket::{post, State};
+    /// # use rocket_auth::{AdminUser, Users, Error};
+    /// #[post("/users/<id>/disable")]
+    /// async fn disable(id: i32, _admin: AdminUser, users: &State<Users>) -> Result<(), Error> {
+    ///     users.disable(id).await
+    /// }
+    /// ```
+    #[throws(Error)]
+    pub async fn disable(&self, id: i32) {
+        self.disable_user(id, None).await?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn disable_user(&self, id: i32, client: Option<&Client>) {
+        self.conn.set_user_disabled_at(id, Some(now())).await?;
         self.sess.remove(id)?;
-        self.conn.delete_user_by_id(id).await?;
+        self.conn.delete_user_oauth_tokens(id).await?;
+        self.audit(AuthEvent::Disabled, Some(id), client).await?;
+    }
+
+    /// Allows a disabled user to log in again.
+    #[throws(Error)]
+    pub async fn enable(&self, id: i32) {
+        self.enable_user(id, None).await?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn enable_user(&self, id: i32, client: Option<&Client>) {
+        self.conn.set_user_disabled_at(id, None).await?;
+        self.audit(AuthEvent::Enabled, Some(id), client).await?;
+    }
+
+    /// Ends the session of a user, so they have to log in again.
+    #[throws(Error)]
+    pub fn force_logout(&self, id: i32) {
+        self.sess.remove(id)?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn logout_user(&self, id: i32, client: Option<&Client>) {
+        self.sess.remove(id)?;
+        self.audit(AuthEvent::ForcedLogout, Some(id), client).await?;
+    }
+
+    /// Whether the user has to change their password before their session is accepted by the [`User`] guards,
+    /// either because it was required with [`Users::require_password_change`] or because it is older than
+    /// the [`max_age`](PasswordPolicy::max_age) of the [`PasswordPolicy`].
+    pub fn needs_password_change(&self, user: &User) -> bool {
+        user.must_change_password() || self.password_expired(user)
+    }
+
+    /// Requires a user to change their password, for example after a suspected leak or after setting a temporary password.
+    /// They can still log in, but their session is rejected by the [`User`] guards until the password is changed.
+    /// ```rust
+    /// # use rocket_auth::{Users, Error};
+    /// # async fn func(users: Users) -> Result<(), Error> {
+    /// users.create_user("new.hire@acme.com", "Temporary123", false).await?;
+    /// let user = users.get_by_email("new.hire@acme.com").await?;
+    /// users.require_password_change(user.id()).await?;
+    /// # Ok(()) }
+    /// ```
+    #[throws(Error)]
+    pub async fn require_password_change(&self, user_id: i32) {
+        self.require_user_password_change(user_id, None).await?;
+    }
+
+    #[throws(Error)]
+    pub(crate) async fn require_user_password_change(&self, user_id: i32, client: Option<&Client>) {
+        let mut user = self.get_by_id(user_id).await?;
+        user.require_password_change();
+        self.update_user(&user, client).await?;
+    }
+
+    /// Saves that the password of a user who is logging in has expired, so it is shown along with the user.
+    /// It is only written once, since the stored flag stays set until the password is changed.
+    #[throws(Error)]
+    pub(crate) async fn flag_expired_password(&self, mut user: User, client: &Client) -> User {
+        if !user.must_change_password() && self.password_expired(&user) {
+            user.require_password_change();
+            self.update_user(&user, Some(client)).await?;
+        }
+        user
+    }
+
+    pub(crate) fn login_status(&self, user: &User) -> LoginStatus {
+        if self.needs_password_change(user) {
+            LoginStatus::PasswordChangeRequired
+        } else {
+            LoginStatus::LoggedIn
+        }
+    }
+
+    fn password_expired(&self, user: &User) -> bool {
+        if !self.verifier.checks_local_passwords() {
+            return false;
+        }
+        match (self.password_policy.max_age, user.password_changed_at()) {
+            (Some(max_age), Some(changed_at)) => changed_at + max_age.as_secs() as i64 <= now(),
+            _ => false,
+        }
     }
 
     /// Modifies a user in the database.
@@ -228,13 +444,65 @@ impl Users {
     /// # async fn func(users: Users) -> Result<(), Error> {
     /// let mut user = users.get_by_id(4).await?;
     /// user.set_email("new@email.com");
-    /// user.set_password("new password");
+    /// users.set_password(&mut user, "new password").await?;
     /// users.modify(&user).await?;
     /// # Ok(())}
     /// ```
     #[throws(Error)]
     pub async fn modify(&self, user: &User) {
+        self.update_user(user, None).await?;
+    }
+
+    /// Modifies a user, recording changes to their email, password or admin status,
+    /// and running the hooks for email and password changes. Replaced passwords are added to the password history.
+    /// New passwords should be set with [`Users::set_password`], which checks them against that history.
+    #[throws(Error)]
+    pub(crate) async fn update_user(&self, user: &User, client: Option<&Client>) {
+        if self.audit.is_none() && self.hooks.is_empty() && self.password_history == 0 {
+            return self.conn.update_user(user).await?;
+        }
+        let old = self.conn.get_user_by_id(user.id).await?;
+        let email_changed = old.email != user.email;
+        let password_changed = old.password != user.password;
+        for hooks in &self.hooks {
+            if email_changed {
+                hooks.before_email_change(self, user, &old.email).await?;
+            }
+            if password_changed {
+                hooks.before_password_change(self, user).await?;
+            }
+        }
         self.conn.update_user(user).await?;
+        if password_changed {
+            self.record_password_history(user.id, &old.password).await?;
+        }
+        let mut events = vec![];
+        if email_changed {
+            events.push(AuthEvent::EmailChanged);
+        }
+        if password_changed {
+            events.push(AuthEvent::PasswordChanged);
+        }
+        if old.is_admin != user.is_admin {
+            events.push(match user.is_admin {
+                true => AuthEvent::AdminGranted,
+                false => AuthEvent::AdminRevoked,
+            });
+        }
+        if !old.must_change_password && user.must_change_password {
+            events.push(AuthEvent::PasswordChangeRequired);
+        }
+        for event in events {
+            self.audit(event, Some(user.id), client).await?;
+        }
+        for hooks in &self.hooks {
+            if email_changed {
+                hooks.on_email_change(self, user, &old.email).await?;
+            }
+            if password_changed {
+                hooks.on_password_change(self, user).await?;
+            }
+        }
     }
 }
 
@@ -256,6 +524,20 @@ impl<Conn: 'static + DBConnection> From<Conn> for Users {
         Users {
             conn: Box::from(db),
             sess: Box::new(chashmap::CHashMap::new()),
+            verifier: Box::new(PasswordVerifier),
+            proxy: None,
+            audit: None,
+            hooks: vec![],
+            mailer: None,
+            templates: Default::default(),
+            hasher: Default::default(),
+            hashing_permits: Arc::new(Semaphore::new(default_concurrency())),
+            peppers: Default::default(),
+            password_policy: Default::default(),
+            breached_passwords: None,
+            password_history: 0,
+            open_signup: true,
+            soft_delete: false,
         }
     }
 }
@@ -279,9 +561,8 @@ impl<Conn: 'static + DBConnection> From<Conn> for Users {
 /// ```
 impl<T0: 'static + DBConnection, T1: 'static + SessionManager> From<(T0, T1)> for Users {
     fn from((db, ss): (T0, T1)) -> Users {
-        Users {
-            conn: Box::from(db),
-            sess: Box::new(ss),
-        }
+        let mut users: Users = db.into();
+        users.sess = Box::new(ss);
+        users
     }
 }